@@ -10,7 +10,7 @@ extern crate tokio;
 
 use std::{
     io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
-    time::{Duration, Instant},
+    time::Instant,
 };
 
 use nom::error::VerboseError;
@@ -18,10 +18,10 @@ use nom::error::VerboseError;
 use libsip::*;
 use tokio::net::UdpSocket;
 
-const USERNAME: &'static str = "20";
-const PASSWORD: &'static str = "program";
-const SOCKET_ADDRESS: &'static str = "192.168.1.129:5060";
-const SERVER_SOCK_ADDRESS: &'static str = "192.168.1.133:5060";
+const USERNAME: &str = "20";
+const PASSWORD: &str = "program";
+const SOCKET_ADDRESS: &str = "192.168.1.129:5060";
+const SERVER_SOCK_ADDRESS: &str = "192.168.1.133:5060";
 
 async fn registration_process(
     reg: &mut RegistrationManager,
@@ -29,47 +29,33 @@ async fn registration_process(
     verbose: bool,
 ) -> IoResult<()> {
     let mut buf = vec![0; 65535];
-    let request = reg.get_request(&Default::default())?;
-    if verbose {
-        print_sip_message_send(&request);
-    }
-    sock.send_to(&format!("{}", request).as_ref(), SERVER_SOCK_ADDRESS)
-        .await?;
-    let (amt, _src) = sock.recv_from(&mut buf).await?;
-    let (_, msg) = parse_message::<VerboseError<&[u8]>>(&buf[..amt]).unwrap();
-    if verbose {
-        print_sip_message_recv(&msg);
-    }
-    if msg.is_response() && msg.status_code() == Ok(200) {
-        return Ok(());
-    }
-    reg.set_challenge(msg)?;
-    let auth_request = reg.get_request(&Default::default())?;
-    if verbose {
-        print_sip_message_send(&auth_request);
-    }
-    sock.send_to(format!("{}", auth_request).as_ref(), SERVER_SOCK_ADDRESS)
-        .await?;
-    let (amt, _src) = sock.recv_from(&mut buf).await.unwrap();
-    let (_, msg) = parse_message::<VerboseError<&[u8]>>(&buf[..amt]).unwrap();
-    if verbose {
-        print_sip_message_recv(&msg);
-    }
-    match msg {
-        SipMessage::Response { code, .. } => {
-            if code == 200 {
-                Ok(())
-            } else {
-                Err(From::from(IoError::new(
-                    IoErrorKind::InvalidInput,
-                    "Failed to authenticate",
-                )))
+    loop {
+        let request = reg.get_request(&Default::default())?;
+        if verbose {
+            print_sip_message_send(&request);
+        }
+        sock.send_to(format!("{}", request).as_ref(), SERVER_SOCK_ADDRESS)
+            .await?;
+        let msg = loop {
+            let (amt, _src) = sock.recv_from(&mut buf).await?;
+            let (_, msg) = parse_message::<VerboseError<&[u8]>>(&buf[..amt]).unwrap();
+            if verbose {
+                print_sip_message_recv(&msg);
             }
-        },
-        _ => Err(From::from(IoError::new(
-            IoErrorKind::InvalidInput,
-            "Failed to authenticate",
-        ))),
+            if msg.is_response() && msg.status_code()? >= 200 {
+                break msg;
+            }
+        };
+        match reg.handle_response(&msg, Instant::now())? {
+            RegistrationStatus::Registered(_) => return Ok(()),
+            RegistrationStatus::Challenged | RegistrationStatus::IntervalTooBrief(_) => continue,
+            _ => {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidInput,
+                    "Failed to register",
+                ))
+            },
+        }
     }
 }
 
@@ -83,15 +69,9 @@ async fn main() -> IoResult<()> {
     registration_process(&mut registrar, &mut sock, verbose).await?;
 
     let mut buf = vec![0; 65535];
-    let mut expire_time = Instant::now();
     loop {
-        let timeout = registrar.expires() as u64;
-        if expire_time.elapsed().as_secs() > timeout {
-            registration_process(&mut registrar, &mut sock, verbose).await?;
-            expire_time = Instant::now();
-            continue;
-        }
-        let timeout_duration = Duration::from_secs(expire_time.elapsed().as_secs() + timeout);
+        let refresh_at = registrar.refresh_at().unwrap_or_else(Instant::now);
+        let timeout_duration = refresh_at.saturating_duration_since(Instant::now());
         let result = tokio::time::timeout(timeout_duration, sock.recv_from(&mut buf)).await;
         match result {
             Ok(Ok(value)) => {
@@ -102,7 +82,6 @@ async fn main() -> IoResult<()> {
             Ok(Err(err)) => panic!("{}", err),
             Err(_err) => {
                 registration_process(&mut registrar, &mut sock, verbose).await?;
-                expire_time = Instant::now();
             },
         }
     }
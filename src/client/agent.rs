@@ -0,0 +1,637 @@
+use tokio::{
+    net::{
+        udp::{RecvHalf, SendHalf},
+        UdpSocket,
+    },
+    runtime::Handle,
+};
+
+use crate::{
+    core::DEFAULT_MAX_MESSAGE_SIZE,
+    transaction::TimerConfig,
+    transport::{ConnectionKey, TransportEvent, TransportLayer},
+    *,
+};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    net::{SocketAddr, UdpSocket as StdUdpSocket},
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+
+/// Receives the requests that arrive at a `UserAgent`. Every method has a
+/// default implementation, the responses required by the protocol are
+/// sent by the agent.
+pub trait RequestHandler: Send + 'static {
+    /// A new call arrived and `180 Ringing` has been sent. Returning a body
+    /// accepts the call with a `200 OK` carrying it, returning `None` leaves
+    /// it ringing until `UserAgent::accept` is called.
+    fn on_invite(&mut self, _invite: &SipMessage) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// A re-INVITE arrived for an established call, modifying it's session.
+    /// Returning a body accepts it with a `200 OK` carrying it, returning
+    /// `None` rejects it with `488 Not Acceptable Here` and the session
+    /// stays as it was.
+    fn on_reinvite(&mut self, _invite: &SipMessage) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// A MESSAGE request arrived, it has been answered with `200 OK`.
+    fn on_message(&mut self, _message: &SipMessage) {}
+
+    /// A NOTIFY request arrived, it has been answered with `200 OK`.
+    fn on_notify(&mut self, _notify: &SipMessage) {}
+
+    /// Processing a message or timer failed, or a datagram could not be
+    /// sent. The agent carries on with the next one.
+    fn on_error(&mut self, _error: IoError) {}
+}
+
+impl RequestHandler for () {}
+
+#[derive(Debug, Default)]
+struct Slot {
+    result: Option<IoResult<SipMessage>>,
+    waker: Option<Waker>,
+}
+
+fn complete(slot: &Arc<Mutex<Slot>>, result: IoResult<SipMessage>) {
+    let mut slot = slot.lock().unwrap();
+    slot.result = Some(result);
+    if let Some(waker) = slot.waker.take() {
+        waker.wake();
+    }
+}
+
+/// Resolves to the final response of an outgoing transaction, or a
+/// `TimedOut` error when Timer B or F fires first.
+#[derive(Debug)]
+pub struct ResponseFuture(Arc<Mutex<Slot>>);
+
+impl Future for ResponseFuture {
+    type Output = IoResult<SipMessage>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut slot = self.0.lock().unwrap();
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+/// What to do with the final response of an outgoing transaction.
+enum Pending {
+    Request(Arc<Mutex<Slot>>),
+    /// A REGISTER request, challenges are answered and refreshes are
+    /// sent without anyone waiting on them.
+    Register(Option<Arc<Mutex<Slot>>>),
+    /// An INVITE, a challenge is answered once with the credentials of
+    /// the registration manager.
+    Invite {
+        slot: Arc<Mutex<Slot>>,
+        invite: SipMessage,
+        retried: bool,
+    },
+}
+
+struct State<H> {
+    phone: SoftPhone,
+    transactions: TransactionLayer,
+    transport: TransportLayer,
+    pending: HashMap<TransactionKey, Pending>,
+    handler: H,
+}
+
+/// Datagrams waiting to be sent. The socket's send half is taken by the
+/// task flushing the queue and put back once it is empty.
+struct Outgoing {
+    sender: Option<SendHalf>,
+    queue: VecDeque<(Vec<u8>, SocketAddr)>,
+}
+
+struct Shared<H> {
+    state: Mutex<State<H>>,
+    outgoing: Mutex<Outgoing>,
+    receiver: Mutex<Option<RecvHalf>>,
+    local_addr: SocketAddr,
+    proxy: SocketAddr,
+    runtime: Handle,
+}
+
+/// An asynchronous SIP user agent built on a `SoftPhone`, available with
+/// the `tokio` feature. It owns a UDP socket, drives the transaction and
+/// transport layers, keeps the registration refreshed and passes incoming
+/// requests to a `RequestHandler`. Every request is sent to the outbound
+/// `proxy`, usually the registrar.
+///
+/// The agent is a cheap handle that can be cloned, `run` must be polled
+/// for any of the returned futures to complete.
+pub struct UserAgent<H> {
+    shared: Arc<Shared<H>>,
+}
+
+impl<H> Clone for UserAgent<H> {
+    fn clone(&self) -> UserAgent<H> {
+        UserAgent {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<H: RequestHandler> UserAgent<H> {
+    /// Create a new agent using a bound `socket`, the local uri of `phone`
+    /// should match it's address. Must be called from within a tokio
+    /// runtime.
+    pub fn new(
+        socket: StdUdpSocket,
+        proxy: SocketAddr,
+        phone: SoftPhone,
+        handler: H,
+    ) -> IoResult<UserAgent<H>> {
+        socket.set_nonblocking(true)?;
+        let socket = UdpSocket::from_std(socket)?;
+        let local_addr = socket.local_addr()?;
+        let (receiver, sender) = socket.split();
+        Ok(UserAgent {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    phone,
+                    transactions: TransactionLayer::new(TimerConfig::default()),
+                    // Large requests stay on UDP, this agent has no TCP socket.
+                    transport: TransportLayer::new().mtu(DEFAULT_MAX_MESSAGE_SIZE),
+                    pending: HashMap::new(),
+                    handler,
+                }),
+                outgoing: Mutex::new(Outgoing {
+                    sender: Some(sender),
+                    queue: VecDeque::new(),
+                }),
+                receiver: Mutex::new(Some(receiver)),
+                local_addr,
+                proxy,
+                runtime: Handle::current(),
+            }),
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, State<H>> {
+        self.shared.state.lock().unwrap()
+    }
+
+    /// The address of the agent's socket.
+    pub fn local_addr(&self) -> IoResult<SocketAddr> {
+        Ok(self.shared.local_addr)
+    }
+
+    /// Call `f` with the `SoftPhone`, to build requests or inspect the
+    /// registration.
+    pub fn with_phone<R, F: FnOnce(&mut SoftPhone) -> R>(&self, f: F) -> R {
+        f(&mut self.state().phone)
+    }
+
+    /// Call `f` with the `RequestHandler`.
+    pub fn with_handler<R, F: FnOnce(&mut H) -> R>(&self, f: F) -> R {
+        f(&mut self.state().handler)
+    }
+
+    /// Determine if the registration is currently valid.
+    pub fn is_registered(&self) -> bool {
+        self.state().phone.registry().is_registered(Instant::now())
+    }
+
+    /// Start a client transaction for `request`. The returned future
+    /// resolves to it's final response.
+    pub fn send_request(&self, request: SipMessage) -> IoResult<ResponseFuture> {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        let mut state = self.state();
+        self.start(
+            &mut state,
+            request,
+            Pending::Request(slot.clone()),
+            Instant::now(),
+        )?;
+        Ok(ResponseFuture(slot))
+    }
+
+    /// Call `uri`, offering `sdp`. A `401` or `407` response is answered
+    /// once with the credentials of the registration manager, the returned
+    /// future resolves to the final response after that. A `2xx` response
+    /// must be acknowledged with `send`.
+    pub fn invite(&self, uri: Uri, sdp: Vec<u8>) -> IoResult<ResponseFuture> {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        let mut state = self.state();
+        let invite = state.phone.send_invite(sdp, uri)?;
+        let pending = Pending::Invite {
+            slot: slot.clone(),
+            invite: invite.clone(),
+            retried: false,
+        };
+        self.start(&mut state, invite, pending, Instant::now())?;
+        Ok(ResponseFuture(slot))
+    }
+
+    /// Send `msg` to the proxy outside of any transaction, as done for the
+    /// ACK of a `2xx` response.
+    pub fn send(&self, msg: &SipMessage) -> IoResult<()> {
+        let mut state = self.state();
+        self.transmit(&mut state, msg, Instant::now())
+    }
+
+    /// Register with the registrar. Challenges and `423 Interval Too Brief`
+    /// responses are handled, the returned future resolves to the final
+    /// response. `run` refreshes the registration from then on.
+    pub fn register(&self) -> IoResult<ResponseFuture> {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        let mut state = self.state();
+        self.start_register(&mut state, Some(slot.clone()), Instant::now())?;
+        Ok(ResponseFuture(slot))
+    }
+
+    /// Accept the ringing call `call_id`, sending a `200 OK` with `body`.
+    /// `run` retransmits it until the ACK arrives.
+    pub fn accept(&self, call_id: &str, body: Vec<u8>) -> IoResult<()> {
+        let mut state = self.state();
+        let now = Instant::now();
+        let ok = state.phone.get_accept_request_at(body, call_id, now)?;
+        self.respond(&mut state, ok, now)
+    }
+
+    /// Reject the ringing call `call_id` with `code`, such as `486` or
+    /// `603`, adding a Reason header when `reason` is given.
+    pub fn reject(&self, call_id: &str, code: u32, reason: Option<&str>) -> IoResult<()> {
+        let mut state = self.state();
+        let response = state.phone.get_reject_request(call_id, code, reason)?;
+        self.respond(&mut state, response, Instant::now())
+    }
+
+    /// Receive and process messages and fire timers until the socket fails.
+    /// Only one call can be running at a time.
+    pub async fn run(&self) -> IoResult<()> {
+        let receiver = self.shared.receiver.lock().unwrap().take().ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::AlreadyExists,
+                "The user agent is already running",
+            )
+        })?;
+        let mut running = Running {
+            slot: &self.shared.receiver,
+            receiver: Some(receiver),
+        };
+        self.receive_loop(running.receiver.as_mut().unwrap()).await
+    }
+
+    async fn receive_loop(&self, receiver: &mut RecvHalf) -> IoResult<()> {
+        let mut buf = vec![0; DEFAULT_MAX_MESSAGE_SIZE];
+        loop {
+            let deadline = self.next_timeout();
+            let received = match deadline {
+                Some(deadline) => {
+                    let duration = deadline.saturating_duration_since(Instant::now());
+                    match tokio::time::timeout(duration, receiver.recv_from(&mut buf)).await {
+                        Ok(received) => Some(received?),
+                        Err(_) => None,
+                    }
+                },
+                None => Some(receiver.recv_from(&mut buf).await?),
+            };
+            let now = Instant::now();
+            let mut state = self.state();
+            if let Some((amount, source)) = received {
+                let key = ConnectionKey::new(Transport::Udp, source);
+                self.receive(&mut state, key, &buf[..amount], now);
+            }
+            self.on_timer(&mut state, now);
+        }
+    }
+
+    fn next_timeout(&self) -> Option<Instant> {
+        let state = self.state();
+        let refresh_at = if state.registering() {
+            None
+        } else {
+            state.phone.registry().refresh_at()
+        };
+        vec![
+            state.transactions.next_timeout(),
+            refresh_at,
+            state.phone.next_timeout(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Fire the expired timers, a failing one is reported to the handler
+    /// and the others still fire.
+    fn on_timer(&self, state: &mut State<H>, now: Instant) {
+        let events = state.transactions.on_timer(now);
+        self.dispatch(state, events, now);
+        // Retransmitted 2xx responses are sent outside of any transaction,
+        // the BYE sent when no ACK arrived gets one of it's own.
+        let messages = match state.phone.on_timer(now) {
+            Ok(messages) => messages,
+            Err(err) => {
+                state.handler.on_error(err);
+                vec![]
+            },
+        };
+        for msg in messages {
+            let result = if msg.is_request() {
+                let slot = Arc::new(Mutex::new(Slot::default()));
+                self.start(state, msg, Pending::Request(slot), now)
+            } else {
+                self.transmit(state, &msg, now)
+            };
+            if let Err(err) = result {
+                state.handler.on_error(err);
+            }
+        }
+        if !state.registering() && state.phone.registry().needs_refresh(now) {
+            if let Err(err) = self.start_register(state, None, now) {
+                state.handler.on_error(err);
+            }
+        }
+    }
+
+    /// Process a received datagram. A malformed or unexpected message must
+    /// not stop the agent, errors are reported to the handler.
+    fn receive(&self, state: &mut State<H>, key: ConnectionKey, data: &[u8], now: Instant) {
+        for event in state.transport.receive(key, data, now) {
+            if let TransportEvent::Message(_, msg) = event {
+                match state.transactions.receive(msg, now) {
+                    Ok(events) => self.dispatch(state, events, now),
+                    Err(err) => state.handler.on_error(err),
+                }
+            }
+        }
+    }
+
+    fn start(
+        &self,
+        state: &mut State<H>,
+        request: SipMessage,
+        pending: Pending,
+        now: Instant,
+    ) -> IoResult<()> {
+        let key = TransactionKey::from_message(&request)?;
+        let events = state.transactions.send_request(request, now)?;
+        state.pending.insert(key, pending);
+        self.dispatch(state, events, now);
+        Ok(())
+    }
+
+    fn start_register(
+        &self,
+        state: &mut State<H>,
+        slot: Option<Arc<Mutex<Slot>>>,
+        now: Instant,
+    ) -> IoResult<()> {
+        let request = state.phone.get_register_request()?;
+        self.start(state, request, Pending::Register(slot), now)
+    }
+
+    fn respond(&self, state: &mut State<H>, response: SipMessage, now: Instant) -> IoResult<()> {
+        let events = state.transactions.send_response(response, now)?;
+        self.dispatch(state, events, now);
+        Ok(())
+    }
+
+    /// Handle the events of the transaction layer, the error of one is
+    /// reported to the handler and the remaining events are still handled.
+    fn dispatch(&self, state: &mut State<H>, events: Vec<TransactionEvent>, now: Instant) {
+        for event in events {
+            let result = match event {
+                TransactionEvent::Send(msg) => self.transmit(state, &msg, now),
+                TransactionEvent::Deliver(msg) if msg.is_request() => {
+                    let result = self.on_request(state, msg.clone(), now);
+                    if result.is_err() && !matches!(msg.method(), Ok(Method::Ack)) {
+                        // Answer the request so the transaction doesn't wait
+                        // for a response. This fails if a final response was
+                        // already sent, leaving the transaction as it is.
+                        if let Ok(response) = simple_response(&msg, 500) {
+                            let _ = self.respond(state, response, now);
+                        }
+                    }
+                    result
+                },
+                TransactionEvent::Deliver(msg) => self.on_response(state, msg, now),
+                TransactionEvent::Timeout(key) => {
+                    let slot = match state.pending.remove(&key) {
+                        Some(Pending::Request(slot)) | Some(Pending::Invite { slot, .. }) => {
+                            Some(slot)
+                        },
+                        Some(Pending::Register(slot)) => {
+                            state.phone.registry_mut().timed_out(now);
+                            slot
+                        },
+                        None => None,
+                    };
+                    if let Some(slot) = slot {
+                        complete(
+                            &slot,
+                            Err(IoError::new(IoErrorKind::TimedOut, "Transaction timed out")),
+                        );
+                    }
+                    Ok(())
+                },
+                TransactionEvent::Terminated(_) => Ok(()),
+            };
+            if let Err(err) = result {
+                state.handler.on_error(err);
+            }
+        }
+    }
+
+    fn transmit(&self, state: &mut State<H>, msg: &SipMessage, now: Instant) -> IoResult<()> {
+        let events = if msg.is_request() {
+            state
+                .transport
+                .send(msg, Transport::Udp, self.shared.proxy, now)
+        } else {
+            state.transport.send_response(msg, now)?
+        };
+        let mut outgoing = self.shared.outgoing.lock().unwrap();
+        for event in events {
+            if let TransportEvent::Send(key, data) = event {
+                outgoing.queue.push_back((data, key.remote));
+            }
+        }
+        if let Some(sender) = outgoing.sender.take() {
+            self.shared.runtime.spawn(self.clone().flush(sender));
+        }
+        Ok(())
+    }
+
+    /// Send the queued datagrams, handing `sender` back once none are left.
+    async fn flush(self, mut sender: SendHalf) {
+        loop {
+            let (data, remote) = {
+                let mut outgoing = self.shared.outgoing.lock().unwrap();
+                match outgoing.queue.pop_front() {
+                    Some(datagram) => datagram,
+                    None => {
+                        outgoing.sender = Some(sender);
+                        return;
+                    },
+                }
+            };
+            // The datagram is lost as it could be on the network, the
+            // transaction layer retransmits requests and final responses.
+            if let Err(err) = sender.send_to(&data, &remote).await {
+                self.state().handler.on_error(err);
+            }
+        }
+    }
+
+    fn on_response(
+        &self,
+        state: &mut State<H>,
+        response: SipMessage,
+        now: Instant,
+    ) -> IoResult<()> {
+        if response.status_code()? < 200 {
+            return Ok(());
+        }
+        let key = TransactionKey::from_message(&response)?;
+        match state.pending.remove(&key) {
+            Some(Pending::Request(slot)) => complete(&slot, Ok(response)),
+            Some(Pending::Invite {
+                slot,
+                invite,
+                retried: false,
+            }) if matches!(response.status_code()?, 401 | 407) => {
+                match state.phone.authenticate_invite(&invite, &response) {
+                    Ok(invite) => {
+                        let pending = Pending::Invite {
+                            slot,
+                            invite: invite.clone(),
+                            retried: true,
+                        };
+                        self.start(state, invite, pending, now)?
+                    },
+                    // Without credentials the challenge is the final response.
+                    Err(_) => complete(&slot, Ok(response)),
+                }
+            },
+            Some(Pending::Invite { slot, .. }) => complete(&slot, Ok(response)),
+            Some(Pending::Register(slot)) => {
+                match state.phone.registry_mut().handle_response(&response, now)? {
+                    RegistrationStatus::Challenged | RegistrationStatus::IntervalTooBrief(_) => {
+                        self.start_register(state, slot, now)?
+                    },
+                    _ => {
+                        if let Some(slot) = slot {
+                            complete(&slot, Ok(response));
+                        }
+                    },
+                }
+            },
+            // A retransmitted 2xx response to an INVITE.
+            None => {},
+        }
+        Ok(())
+    }
+
+    fn on_request(&self, state: &mut State<H>, request: SipMessage, now: Instant) -> IoResult<()> {
+        let response = match request.method()? {
+            Method::Ack => {
+                state.phone.receive_ack(&request)?;
+                return Ok(());
+            },
+            Method::Invite => {
+                let call_id = request.call_id()?.clone();
+                let reinvite = request.to_header_tag().is_ok();
+                let response = state.phone.get_received_request(request.clone())?;
+                if response.status_code()? >= 200 {
+                    return self.respond(state, response, now);
+                }
+                // The transaction layer already sent the 100 Trying of a re-INVITE.
+                if !reinvite {
+                    self.respond(state, response, now)?;
+                }
+                let body = if reinvite {
+                    state.handler.on_reinvite(&request)
+                } else {
+                    state.handler.on_invite(&request)
+                };
+                match body {
+                    Some(body) => state.phone.get_accept_request_at(body, &call_id, now)?,
+                    None if reinvite => state.phone.get_reject_request(&call_id, 488, None)?,
+                    None => return Ok(()),
+                }
+            },
+            Method::Message => {
+                state.handler.on_message(&request);
+                MessageHelper::new(request)?.received(state.phone.header_cfg())?
+            },
+            Method::Notify => {
+                state.handler.on_notify(&request);
+                simple_response(&request, 200)?
+            },
+            Method::Bye => state.phone.receive_bye(&request)?,
+            Method::Cancel => {
+                let call_id = request.call_id()?.clone();
+                match state.phone.get_cancel_request(&call_id) {
+                    // The 487 ends the INVITE through it's own transaction.
+                    Ok((ok, terminated)) => {
+                        self.respond(state, terminated, now)?;
+                        ok
+                    },
+                    Err(err) if err.kind() == IoErrorKind::NotFound => {
+                        simple_response(&request, 481)?
+                    },
+                    // The call was already answered.
+                    Err(_) => simple_response(&request, 200)?,
+                }
+            },
+            Method::Options => simple_response(&request, 200)?,
+            _ => simple_response(&request, 501)?,
+        };
+        self.respond(state, response, now)
+    }
+}
+
+/// Hands the receive half of the socket back when `run` returns or is
+/// dropped.
+struct Running<'a> {
+    slot: &'a Mutex<Option<RecvHalf>>,
+    receiver: Option<RecvHalf>,
+}
+
+impl<'a> Drop for Running<'a> {
+    fn drop(&mut self) {
+        *self.slot.lock().unwrap() = self.receiver.take();
+    }
+}
+
+impl<H> State<H> {
+    fn registering(&self) -> bool {
+        self.pending
+            .values()
+            .any(|pending| matches!(pending, Pending::Register(_)))
+    }
+}
+
+fn simple_response(request: &SipMessage, code: u32) -> IoResult<SipMessage> {
+    let mut response = ResponseGenerator::from_request(request)
+        .code(code)
+        .header(Header::ContentLength(0))
+        .build()?;
+    if let Ok(to) = response.to_header_mut() {
+        if !to.parameters.contains_key("tag") {
+            to.set_param("tag", Some(core::generate_tag()));
+        }
+    }
+    Ok(response)
+}
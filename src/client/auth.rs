@@ -0,0 +1,167 @@
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+
+use crate::{core::generate_branch, *};
+
+/// A challenge received in a WWW-Authenticate or Proxy-Authenticate header.
+#[derive(Debug, PartialEq, Clone)]
+struct Challenge {
+    /// Whether the challenge came from a proxy (407) or the registrar
+    /// or user agent (401).
+    proxy: bool,
+    header: AuthHeader,
+    /// The number of requests sent with this challenge's nonce.
+    nc: u32,
+}
+
+impl Challenge {
+    fn realm(&self) -> Option<&String> {
+        self.header.1.get("realm")
+    }
+}
+
+/// Keeps the digest challenges received in 401 and 407 responses and
+/// answers them in later requests, as described in
+/// [RFC3261: Section 22.2](https://tools.ietf.org/html/rfc3261#section-22.2)
+/// and [Section 22.3](https://tools.ietf.org/html/rfc3261#section-22.3).
+/// One challenge is kept per realm so a response containing challenges
+/// from several proxies is answered with one header for each of them.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AuthChallenges {
+    challenges: Vec<Challenge>,
+}
+
+impl AuthChallenges {
+    /// Create an empty set of challenges.
+    pub fn new() -> AuthChallenges {
+        AuthChallenges::default()
+    }
+
+    /// Determine if no challenges have been received.
+    pub fn is_empty(&self) -> bool {
+        self.challenges.is_empty()
+    }
+
+    /// Store the WWW-Authenticate and Proxy-Authenticate challenges of
+    /// `response`, replacing any previous challenge for the same realm.
+    /// Returns the number of challenges found.
+    pub fn set_challenge(&mut self, response: &SipMessage) -> IoResult<usize> {
+        if !response.is_response() {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "Challenge Response was not a SIP response",
+            ));
+        }
+        let mut count = 0;
+        for header in response.headers().iter() {
+            let (proxy, auth) = match header {
+                Header::WwwAuthenticate(auth) => (false, auth),
+                Header::ProxyAuthenticate(auth) => (true, auth),
+                _ => continue,
+            };
+            let challenge = Challenge {
+                proxy,
+                header: auth.clone(),
+                nc: 0,
+            };
+            self.challenges
+                .retain(|item| item.proxy != proxy || item.realm() != challenge.realm());
+            self.challenges.push(challenge);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Determine if `response` challenges a realm that was already answered
+    /// without marking the nonce as stale, meaning the credentials sent for
+    /// it were rejected
+    /// ([RFC3261: Section 22.2](https://tools.ietf.org/html/rfc3261#section-22.2)).
+    pub fn rejected(&self, response: &SipMessage) -> bool {
+        response.headers().iter().any(|header| {
+            let (proxy, auth) = match header {
+                Header::WwwAuthenticate(auth) => (false, auth),
+                Header::ProxyAuthenticate(auth) => (true, auth),
+                _ => return false,
+            };
+            let stale =
+                matches!(auth.1.get("stale"), Some(stale) if stale.eq_ignore_ascii_case("true"));
+            !stale
+                && self
+                    .challenges
+                    .iter()
+                    .any(|item| item.proxy == proxy && item.realm() == auth.1.get("realm"))
+        })
+    }
+
+    /// Add an Authorization or Proxy-Authorization header to `request`
+    /// answering each stored challenge, replacing the credentials
+    /// already present in the request.
+    pub fn authorize(&mut self, request: &mut SipMessage, user: &str, pass: &str) -> IoResult<()> {
+        let (method, uri) = match request {
+            SipMessage::Request { method, uri, .. } => (*method, uri.clone()),
+            SipMessage::Response { .. } => {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidInput,
+                    "Only requests can be authorized",
+                ))
+            },
+        };
+        let body = request.body().clone();
+        let mut headers = vec![];
+        for challenge in self.challenges.iter_mut() {
+            challenge.nc += 1;
+            let ctx = AuthContext::new(user, pass, method, &uri)
+                .nc(challenge.nc)
+                .body(&body);
+            let auth = challenge.header.authenticate(ctx)?;
+            headers.push(if challenge.proxy {
+                Header::ProxyAuthorization(auth)
+            } else {
+                Header::Authorization(auth)
+            });
+        }
+        let existing = &mut request.headers_mut().0;
+        existing.retain(|header| {
+            !matches!(
+                header,
+                Header::Authorization(_) | Header::ProxyAuthorization(_)
+            )
+        });
+        existing.extend(headers);
+        Ok(())
+    }
+
+    /// Store the challenges of the 401 or 407 `response` and build the
+    /// request to resend in place of `request`. The new request uses
+    /// CSeq number `cseq`, a new Via branch and answers every challenge.
+    pub fn retry(
+        &mut self,
+        request: &SipMessage,
+        response: &SipMessage,
+        cseq: u32,
+        user: &str,
+        pass: &str,
+    ) -> IoResult<SipMessage> {
+        match response.status_code()? {
+            401 | 407 => {},
+            code => {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidInput,
+                    format!("{} is not an authentication challenge", code),
+                ))
+            },
+        }
+        if self.set_challenge(response)? == 0 {
+            return Err(IoError::new(
+                IoErrorKind::InvalidData,
+                "response does not contain any challenges",
+            ));
+        }
+        let mut request = request.clone();
+        *request.cseq_mut()?.0 = cseq;
+        if let Ok(via) = request.via_header_mut() {
+            via.branch = Some(generate_branch());
+        }
+        self.authorize(&mut request, user, pass)?;
+        Ok(request)
+    }
+}
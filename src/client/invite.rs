@@ -1,6 +1,14 @@
-use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    time::{Duration, Instant},
+};
 
-use crate::*;
+use crate::{
+    core::{generate_branch, generate_tag},
+    parse::quote,
+    transaction::TimerConfig,
+    *,
+};
 
 macro_rules! impl_simple_header_method {
     ($name:ident, $variant:ident, $ty:ident) => {
@@ -24,11 +32,38 @@ macro_rules! impl_simple_header_method {
 /// Structure to ease getting data from a Sip INVITE request.
 /// Also is used to generate the appropiate Ringing and Ok
 /// responses.
+///
+/// Every response carries the same To tag, chosen when the helper is
+/// created. Once accepted the `200 OK` is retransmitted from `on_timer`
+/// until the ACK arrives, as described in
+/// [RFC3261: Section 13.3.1.4](https://tools.ietf.org/html/rfc3261#section-13.3.1.4).
 #[derive(Debug)]
 pub struct InviteHelper {
     pub uri: Uri,
     pub headers: Headers,
     pub body: Vec<u8>,
+    /// The To tag of every response sent for this invitation.
+    tag: String,
+    /// The Contact header added to responses that create a dialog.
+    contact: Option<ContactHeader>,
+    timers: TimerConfig,
+    /// The dialog created once the invitation was accepted.
+    dialog: Option<Dialog>,
+    /// The status code of the final response, once sent.
+    final_code: Option<u32>,
+    /// The accepted response, until it is acknowledged or Timer H fires.
+    retransmission: Option<Retransmission>,
+}
+
+/// Tracks the retransmissions of a `2xx` response awaiting it's ACK.
+#[derive(Debug)]
+struct Retransmission {
+    response: SipMessage,
+    /// Timer G, doubling up to T2 after each retransmission.
+    interval: Duration,
+    next: Instant,
+    /// Timer H, the ACK is given up on at this point.
+    deadline: Instant,
 }
 
 impl InviteHelper {
@@ -53,9 +88,79 @@ impl InviteHelper {
         }
     }
 
-    /// Create an InviteHelper from the given variables.
+    /// Create an InviteHelper from the given variables. A re-INVITE
+    /// already carries our To tag, which is kept.
     pub fn new_from_vars(uri: Uri, headers: Headers, body: Vec<u8>) -> IoResult<InviteHelper> {
-        Ok(InviteHelper { uri, headers, body })
+        let tag = match headers.to() {
+            Some(Header::To(mut to)) => to.parameters.remove("tag").flatten(),
+            _ => None,
+        }
+        .unwrap_or_else(generate_tag);
+        Ok(InviteHelper {
+            uri,
+            headers,
+            body,
+            tag,
+            contact: None,
+            timers: TimerConfig::default(),
+            dialog: None,
+            final_code: None,
+            retransmission: None,
+        })
+    }
+
+    /// Set the Contact header sent in provisional and `2xx` responses,
+    /// where the caller will send requests within the dialog.
+    pub fn contact(mut self, contact: ContactHeader) -> InviteHelper {
+        self.contact = Some(contact);
+        self
+    }
+
+    /// Answer a re-INVITE received within `dialog`, the dialog of the call
+    /// it belongs to. Accepting it keeps `dialog` rather than creating a
+    /// new one ([RFC3261: Section 14.2](https://tools.ietf.org/html/rfc3261#section-14.2)).
+    pub fn in_dialog(mut self, dialog: Dialog) -> InviteHelper {
+        self.dialog = Some(dialog);
+        self
+    }
+
+    /// Determine if the invitation is a re-INVITE, it's To header
+    /// already carries a tag.
+    pub fn is_reinvite(&self) -> bool {
+        matches!(self.headers.to(), Some(Header::To(to)) if to.parameters.contains_key("tag"))
+    }
+
+    /// Set the timer values used for `2xx` retransmissions.
+    pub fn timers(mut self, timers: TimerConfig) -> InviteHelper {
+        self.timers = timers;
+        self
+    }
+
+    /// The To tag added to the responses for this invitation.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Rebuild the received INVITE request.
+    pub fn request(&self) -> SipMessage {
+        SipMessage::Request {
+            method: Method::Invite,
+            uri: self.uri.clone(),
+            version: Version::default(),
+            headers: self.headers.clone(),
+            body: self.body.clone(),
+        }
+    }
+
+    /// Return the dialog established by accepting this invitation.
+    pub fn dialog(&self) -> Option<&Dialog> {
+        self.dialog.as_ref()
+    }
+
+    /// Return a mutable reference to the dialog established by
+    /// accepting this invitation.
+    pub fn dialog_mut(&mut self) -> Option<&mut Dialog> {
+        self.dialog.as_mut()
     }
 
     /// Return a clone of the body of this message.
@@ -63,47 +168,171 @@ impl InviteHelper {
         self.body.clone()
     }
 
+    /// Get a Trying(100) response to this invite, it carries no To tag.
+    pub fn trying(&self, header_cfg: &HeaderWriteConfig) -> IoResult<SipMessage> {
+        let mut res = self.response(100, false).header(Header::ContentLength(0));
+        header_cfg.write_headers(res.headers_ref_mut());
+        res.build()
+    }
+
     /// Get A Ringing(180) request to answer this invite.
     pub fn ringing(&self, header_cfg: &HeaderWriteConfig) -> IoResult<SipMessage> {
-        let mut req = ResponseGenerator::new()
-            .code(180)
-            .header(self.headers.from().unwrap())
-            .header(self.headers.to().unwrap())
-            .header(self.headers.call_id().unwrap())
-            .header(self.headers.cseq().unwrap())
-            .header(self.headers.via().unwrap())
-            .header(Header::ContentLength(0));
-        header_cfg.write_headers(req.headers_ref_mut());
-        req.build()
+        self.provisional(180, None, header_cfg)
+    }
+
+    /// Get a Session Progress(183) response, `sdp` is the early media
+    /// session description.
+    pub fn session_progress(
+        &self,
+        sdp: Option<Vec<u8>>,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        self.provisional(183, sdp, header_cfg)
+    }
+
+    /// Get a provisional response with a `code` between 101 and 199,
+    /// optionally carrying an early media session description.
+    pub fn provisional(
+        &self,
+        code: u32,
+        sdp: Option<Vec<u8>>,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        if !(101..=199).contains(&code) {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "Provisional responses must have a code between 101 and 199",
+            ));
+        }
+        self.dialog_response(code, sdp.unwrap_or_default(), header_cfg)
     }
 
     /// Generate a response that will accept the invite with the sdp as the body.
-    pub fn accept(&self, sdp: Vec<u8>, header_cfg: &HeaderWriteConfig) -> IoResult<SipMessage> {
-        let mut req = ResponseGenerator::new()
-            .code(200)
-            .header(self.headers.cseq().unwrap())
-            .header(self.headers.via().unwrap())
-            .header(self.headers.to().unwrap())
-            .header(self.headers.from().unwrap())
-            .header(self.headers.call_id().unwrap())
-            .header(Header::ContentLength(sdp.len() as u32))
-            .body(sdp);
-        header_cfg.write_headers(req.headers_ref_mut());
-        req.build()
+    /// This also establishes the dialog used for later requests. The
+    /// response is retransmitted by `on_timer` until `receive_ack` is
+    /// given the ACK.
+    pub fn accept(&mut self, sdp: Vec<u8>, header_cfg: &HeaderWriteConfig) -> IoResult<SipMessage> {
+        self.accept_at(sdp, header_cfg, Instant::now())
     }
 
-    /// Generate a Bye response for this Invite Request.
-    pub fn bye(&self, header_cfg: &HeaderWriteConfig) -> IoResult<SipMessage> {
-        let mut req = RequestGenerator::new()
-            .method(Method::Bye)
-            .uri(self.uri.clone())
-            .header(self.headers.cseq().unwrap())
-            .header(self.headers.via().unwrap())
-            .header(self.headers.to().unwrap())
-            .header(self.headers.from().unwrap())
-            .header(self.headers.call_id().unwrap());
-        header_cfg.write_headers(req.headers_ref_mut());
-        req.build()
+    /// Same as `accept`, scheduling the first retransmission from `now`.
+    pub fn accept_at(
+        &mut self,
+        sdp: Vec<u8>,
+        header_cfg: &HeaderWriteConfig,
+        now: Instant,
+    ) -> IoResult<SipMessage> {
+        self.check_unanswered()?;
+        let res = self.dialog_response(200, sdp, header_cfg)?;
+        if self.dialog.is_none() {
+            self.dialog = Some(Dialog::from_uas(&self.request(), &res)?);
+        }
+        self.final_code = Some(200);
+        self.retransmission = Some(Retransmission {
+            response: res.clone(),
+            interval: self.timers.t1,
+            next: now + self.timers.t1,
+            deadline: now + self.timers.transaction_timeout(),
+        });
+        Ok(res)
+    }
+
+    /// Generate a final error response such as `486 Busy Here` or
+    /// `603 Decline`. When `reason` is given a Reason header carrying it
+    /// and the status code is added
+    /// ([RFC3326](https://tools.ietf.org/html/rfc3326)).
+    pub fn reject(
+        &mut self,
+        code: u32,
+        reason: Option<&str>,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        if !(300..=699).contains(&code) {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "Rejections must have a code between 300 and 699",
+            ));
+        }
+        self.check_unanswered()?;
+        let mut res = self.response(code, true);
+        if let Some(reason) = reason {
+            res = res.header(Header::Other(
+                "Reason".into(),
+                format!("SIP;cause={};text={}", code, quote(reason)),
+            ));
+        }
+        res = res.header(Header::ContentLength(0));
+        header_cfg.write_headers(res.headers_ref_mut());
+        let res = res.build()?;
+        self.final_code = Some(code);
+        Ok(res)
+    }
+
+    /// Process the ACK for the accepted response, which stops it's
+    /// retransmissions. Returns false if `ack` doesn't acknowledge it.
+    pub fn receive_ack(&mut self, ack: &SipMessage) -> IoResult<bool> {
+        let (cseq, _) = ack.cseq()?;
+        if ack.method()? != Method::Ack
+            || !self.check_cseq(cseq)?
+            || ack.to_header_tag().ok() != Some(&self.tag)
+            || self.final_code != Some(200)
+        {
+            return Ok(false);
+        }
+        self.retransmission = None;
+        if let Some(dialog) = &mut self.dialog {
+            dialog.on_request(ack)?;
+        }
+        Ok(true)
+    }
+
+    /// Determine if the accepted response is still waiting for it's ACK.
+    pub fn awaiting_ack(&self) -> bool {
+        self.retransmission.is_some()
+    }
+
+    /// Fire the retransmission timers at `now`. Returns the `2xx` response
+    /// when it must be sent again, or a BYE ending the dialog when no ACK
+    /// arrived within 64*T1.
+    pub fn on_timer(
+        &mut self,
+        now: Instant,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<Option<SipMessage>> {
+        let t2 = self.timers.t2;
+        let timed_out = match &mut self.retransmission {
+            Some(retransmission) if now >= retransmission.deadline => true,
+            Some(retransmission) if now >= retransmission.next => {
+                retransmission.interval = (retransmission.interval * 2).min(t2);
+                retransmission.next += retransmission.interval;
+                return Ok(Some(retransmission.response.clone()));
+            },
+            _ => false,
+        };
+        if timed_out {
+            self.retransmission = None;
+            return Ok(Some(self.bye(header_cfg)?));
+        }
+        Ok(None)
+    }
+
+    /// The instant `on_timer` should next be called.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        self.retransmission
+            .as_ref()
+            .map(|retransmission| retransmission.next.min(retransmission.deadline))
+    }
+
+    /// Generate a Bye request ending the dialog established by `accept`.
+    pub fn bye(&mut self, header_cfg: &HeaderWriteConfig) -> IoResult<SipMessage> {
+        if let Some(dialog) = &mut self.dialog {
+            dialog.bye(header_cfg)
+        } else {
+            Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "invitation has not been accepted",
+            ))
+        }
     }
 
     /// Verify the CSeq header is equal to `cseq`.
@@ -118,34 +347,80 @@ impl InviteHelper {
         Ok(false)
     }
 
-    /// Get the messages required to cancel a invitation.
+    /// Get the messages required to cancel a invitation, the `200 OK`
+    /// answering the CANCEL and the `487 Request Terminated` ending the
+    /// invitation ([RFC3261: Section 9.2](https://tools.ietf.org/html/rfc3261#section-9.2)).
+    /// An error is returned once a final response has been sent, the
+    /// CANCEL then has no effect on the call.
     pub fn cancel(&mut self, header_cfg: &HeaderWriteConfig) -> IoResult<(SipMessage, SipMessage)> {
-        let mut out_headers = vec![];
+        self.check_unanswered()?;
+        let mut ok = self.response(200, true).header(Header::ContentLength(0));
+        header_cfg.write_headers(ok.headers_ref_mut());
+        let mut ok = ok.build()?;
+        *ok.cseq_mut()?.1 = Method::Cancel;
+        let terminated = self.reject(487, None, header_cfg)?;
+        Ok((ok, terminated))
+    }
+
+    fn check_unanswered(&self) -> IoResult<()> {
+        if self.final_code.is_some() {
+            Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "A final response has already been sent",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// A response carrying the Contact header and echoing the Record-Route
+    /// headers, as needed for responses creating a dialog
+    /// ([RFC3261: Section 12.1.1](https://tools.ietf.org/html/rfc3261#section-12.1.1)).
+    fn dialog_response(
+        &self,
+        code: u32,
+        sdp: Vec<u8>,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        let mut res = self.response(code, true);
+        for header in self.headers.iter() {
+            if let Header::RecordRoute(_) = header {
+                res = res.header(header.clone());
+            }
+        }
+        if let Some(contact) = &self.contact {
+            res = res.header(Header::Contact(contact.clone()));
+        }
+        if !sdp.is_empty() {
+            res = res.header(Header::ContentType(ContentType::Sdp));
+        }
+        res = res
+            .header(Header::ContentLength(sdp.len() as u32))
+            .body(sdp);
+        header_cfg.write_headers(res.headers_ref_mut());
+        res.build()
+    }
+
+    /// A response copying the Via, From, To, Call-ID and CSeq headers of
+    /// the request, with our tag added to the To header if `tagged`.
+    fn response(&self, code: u32, tagged: bool) -> ResponseGenerator {
+        let mut res = ResponseGenerator::new().code(code);
         for header in self.headers.iter() {
             match header {
-                Header::CSeq(a, b) => out_headers.push(Header::CSeq(*a, *b)),
-                Header::CallId(call) => out_headers.push(Header::CallId(call.clone())),
-                Header::From(from) => out_headers.push(Header::From(from.clone())),
-                Header::To(to) => out_headers.push(Header::To(to.clone())),
-                Header::Via(via) => out_headers.push(Header::Via(via.clone())),
+                Header::To(to) if tagged => {
+                    let mut to = to.clone();
+                    to.set_param("tag", Some(self.tag.clone()));
+                    res = res.header(Header::To(to));
+                },
+                Header::Via(_)
+                | Header::From(_)
+                | Header::To(_)
+                | Header::CallId(_)
+                | Header::CSeq(_, _) => res = res.header(header.clone()),
                 _ => {},
             }
         }
-        let mut final_headers = Headers(out_headers);
-        header_cfg.write_headers(&mut final_headers);
-
-        Ok((
-            ResponseGenerator::new()
-                .code(200)
-                .headers(final_headers.clone().0)
-                .header(Header::ContentLength(0))
-                .build()?,
-            ResponseGenerator::new()
-                .code(487)
-                .headers(final_headers.0)
-                .header(Header::ContentLength(0))
-                .build()?,
-        ))
+        res
     }
 }
 
@@ -155,28 +430,98 @@ impl InviteHelper {
 pub struct InviteWriter {
     cseq: u32,
     uri: Uri,
+    challenges: AuthChallenges,
 }
 
 impl InviteWriter {
     /// Create a new InviteHelper struct. `uri` is the uri to send
     /// the request too.
     pub fn new(uri: Uri) -> InviteWriter {
-        InviteWriter { cseq: 0, uri }
+        InviteWriter {
+            cseq: 0,
+            uri,
+            challenges: AuthChallenges::new(),
+        }
     }
 
-    /// Generate a Invite Request.
-    pub fn generate_invite(&mut self, uri: Uri, sdp: Vec<u8>) -> IoResult<SipMessage> {
+    /// Generate a Invite Request to `uri` carrying `sdp`. The Via header
+    /// is given a new branch and the From header a new tag, `contact` is
+    /// where the remote side will send requests within the dialog. The
+    /// headers of `header_cfg` are added as well.
+    pub fn generate_invite(
+        &mut self,
+        uri: Uri,
+        sdp: Vec<u8>,
+        via_header: Header,
+        contact: ContactHeader,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
         self.cseq += 1;
         let me_uri = self.uri.clone();
-        RequestGenerator::new()
+        let mut from = __named_header!(me_uri);
+        from.set_param("tag", Some(generate_tag()));
+        let via_header = match via_header {
+            Header::Via(via) => Header::Via(ViaHeader {
+                branch: Some(generate_branch()),
+                ..via
+            }),
+            header => header,
+        };
+        let mut req = RequestGenerator::new()
             .method(Method::Invite)
             .uri(uri.clone())
-            .header(self.cseq()?)
-            .header(Header::From(__named_header!(me_uri)))
+            .header(via_header)
+            .header(Header::MaxForwards(70))
+            .header(Header::From(from))
             .header(Header::To(__named_header!(uri)))
             .header(Header::CallId(InviteWriter::generate_call_id()))
-            .body(sdp)
-            .build()
+            .header(self.cseq()?)
+            .header(Header::Contact(contact));
+        if !sdp.is_empty() {
+            req = req.header(Header::ContentType(ContentType::Sdp));
+        }
+        req = req.header(Header::ContentLength(sdp.len() as u32));
+        header_cfg.write_headers(req.headers_ref_mut());
+        req.body(sdp).build()
+    }
+
+    /// Generate the ACK for a final response to `invite`. Non-2xx responses
+    /// are acknowledged within the INVITE transaction, reusing it's branch.
+    /// A 2xx response establishes a dialog, which is returned along with an
+    /// ACK sent to the remote target over the dialog's route set.
+    /// The dialog is `None` for non-2xx responses.
+    pub fn ack(
+        &self,
+        invite: &SipMessage,
+        response: &SipMessage,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<(SipMessage, Option<Dialog>)> {
+        match response.status_code()? {
+            200..=299 => {
+                let mut dialog = Dialog::from_uac(invite, response)?;
+                let ack = dialog.ack(response, header_cfg)?;
+                Ok((ack, Some(dialog)))
+            },
+            300..=699 => Ok((transaction::non_2xx_ack(invite, response)?, None)),
+            _ => Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "Provisional responses are not acknowledged",
+            )),
+        }
+    }
+
+    /// Answer the 401 or 407 `response` to `invite`, returning the INVITE
+    /// to send again with the next CSeq and credentials for every challenge.
+    pub fn authenticate(
+        &mut self,
+        invite: &SipMessage,
+        response: &SipMessage,
+        user: &str,
+        pass: &str,
+    ) -> IoResult<SipMessage> {
+        self.cseq += 1;
+        self.challenges
+            .retry(invite, response, self.cseq, user, pass)
     }
 
     /// Generate a CSeq header.
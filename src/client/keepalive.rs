@@ -0,0 +1,84 @@
+use rand::Rng;
+
+use std::time::{Duration, Instant};
+
+/// Sends the CRLF keep-alives of
+/// [RFC5626: Section 4.4.1](https://tools.ietf.org/html/rfc5626#section-4.4.1)
+/// over a connection oriented flow and detects when the flow has failed.
+/// The pings and pongs are framed by `MessageDecoder` as `Decoded::Ping`
+/// and `Decoded::Pong`. Like the transaction layer this performs no IO,
+/// every call takes the current `Instant`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct KeepAlive {
+    /// The Flow-Timer the registrar returned, if any.
+    flow_timer: Option<u32>,
+    /// When the next ping should be sent.
+    next_ping: Instant,
+    /// When the unanswered ping was sent.
+    ping_sent: Option<Instant>,
+}
+
+impl KeepAlive {
+    /// The keep-alive ping, a double CRLF.
+    pub const PING: &'static [u8] = b"\r\n\r\n";
+    /// The answer to a keep-alive ping, a single CRLF.
+    pub const PONG: &'static [u8] = b"\r\n";
+    /// How long to wait for a pong before the flow is considered failed.
+    pub const PONG_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Start sending keep-alives at `now`. `flow_timer` is the value of the
+    /// Flow-Timer header in the registrar's response, pings are sent at 80%
+    /// to 100% of it. Without one, or with a Flow-Timer of 0, they are
+    /// sent every 95 to 120 seconds.
+    pub fn new(flow_timer: Option<u32>, now: Instant) -> KeepAlive {
+        let mut keep_alive = KeepAlive {
+            flow_timer: flow_timer.filter(|timer| *timer > 0),
+            next_ping: now,
+            ping_sent: None,
+        };
+        keep_alive.schedule(now);
+        keep_alive
+    }
+
+    fn schedule(&mut self, now: Instant) {
+        let seconds = match self.flow_timer {
+            Some(timer) => rand::thread_rng().gen_range(timer as f64 * 0.8, timer as f64),
+            None => rand::thread_rng().gen_range(95.0, 120.0),
+        };
+        self.next_ping = now + Duration::from_secs_f64(seconds);
+    }
+
+    /// Returns the ping to send if one is due at `now`.
+    pub fn poll(&mut self, now: Instant) -> Option<&'static [u8]> {
+        if self.ping_sent.is_none() && now >= self.next_ping {
+            self.ping_sent = Some(now);
+            Some(KeepAlive::PING)
+        } else {
+            None
+        }
+    }
+
+    /// A pong was received at `now`.
+    pub fn pong(&mut self, now: Instant) {
+        if self.ping_sent.take().is_some() {
+            self.schedule(now);
+        }
+    }
+
+    /// Determine if the last ping went unanswered for longer than
+    /// `PONG_TIMEOUT`. The flow should then be closed and a new one
+    /// registered.
+    pub fn is_failed(&self, now: Instant) -> bool {
+        self.ping_sent
+            .map(|sent| now >= sent + KeepAlive::PONG_TIMEOUT)
+            .unwrap_or(false)
+    }
+
+    /// When `poll` or `is_failed` should next be called.
+    pub fn next_timeout(&self) -> Instant {
+        match self.ping_sent {
+            Some(sent) => sent + KeepAlive::PONG_TIMEOUT,
+            None => self.next_ping,
+        }
+    }
+}
@@ -86,6 +86,7 @@ pub struct MessageWriter {
     cseq: u32,
     uri: Uri,
     call_id: String,
+    challenges: AuthChallenges,
 }
 
 impl MessageWriter {
@@ -98,6 +99,7 @@ impl MessageWriter {
             cseq: 0,
             uri,
             call_id,
+            challenges: AuthChallenges::new(),
         }
     }
 
@@ -128,6 +130,20 @@ impl MessageWriter {
         req.body(body).build()
     }
 
+    /// Answer the 401 or 407 `response` to `message`, returning the MESSAGE
+    /// to send again with the next CSeq and credentials for every challenge.
+    pub fn authenticate(
+        &mut self,
+        message: &SipMessage,
+        response: &SipMessage,
+        user: &str,
+        pass: &str,
+    ) -> IoResult<SipMessage> {
+        self.cseq += 1;
+        self.challenges
+            .retry(message, response, self.cseq, user, pass)
+    }
+
     /// Get a new CSeq header.
     pub fn cseq(&self) -> Header {
         Header::CSeq(self.cseq, Method::Message)
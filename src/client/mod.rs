@@ -2,8 +2,14 @@
 //! processing and generating SIP requests. Encapsulating
 //! all this functionality is the SoftPhone struct.
 
+mod auth;
+pub use self::auth::AuthChallenges;
+
+mod keepalive;
+pub use self::keepalive::KeepAlive;
+
 mod registration;
-pub use self::registration::RegistrationManager;
+pub use self::registration::{RegistrationManager, RegistrationStatus};
 
 mod messaging;
 pub use self::messaging::{MessageHelper, MessageWriter};
@@ -11,11 +17,17 @@ pub use self::messaging::{MessageHelper, MessageWriter};
 mod invite;
 pub use self::invite::{InviteHelper, InviteWriter};
 
-use crate::{Header, Headers, Method, SipMessage, Uri};
+#[cfg(feature = "tokio")]
+mod agent;
+#[cfg(feature = "tokio")]
+pub use self::agent::{RequestHandler, ResponseFuture, UserAgent};
+
+use crate::{DialogId, Header, Headers, Method, ResponseGenerator, SipMessage, SipMessageExt, Uri};
 
 use std::{
     collections::HashMap,
     io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    time::Instant,
 };
 
 /// This struct is used in the client module when creating sip messages
@@ -30,6 +42,9 @@ pub struct HeaderWriteConfig {
     /// By default set to Invite, Cancel, Bye, Message.
     /// Set to None to disable adding header.
     pub allowed_methods: Option<Vec<Method>>,
+    /// The option tags for the Supported Header.
+    /// By default set to None, which disables adding the header.
+    pub supported: Option<Vec<String>>,
 }
 
 impl HeaderWriteConfig {
@@ -41,6 +56,9 @@ impl HeaderWriteConfig {
         if let Some(allowed) = &self.allowed_methods {
             m.push(Header::Allow(allowed.clone()));
         }
+        if let Some(supported) = &self.supported {
+            m.push(Header::Supported(supported.clone()));
+        }
     }
 
     /// Write configured headers into the provided Headers Map.
@@ -51,6 +69,9 @@ impl HeaderWriteConfig {
         if let Some(allowed) = &self.allowed_methods {
             m.push(Header::Allow(allowed.clone()));
         }
+        if let Some(supported) = &self.supported {
+            m.push(Header::Supported(supported.clone()));
+        }
     }
 }
 
@@ -64,6 +85,7 @@ impl Default for HeaderWriteConfig {
                 Method::Bye,
                 Method::Message,
             ]),
+            supported: None,
         }
     }
 }
@@ -143,7 +165,7 @@ impl SoftPhone {
 
     /// Simple pass through method to get a registration request.
     pub fn get_register_request(&mut self) -> IoResult<SipMessage> {
-        Ok(self.reg.get_request(&self.header_cfg)?)
+        self.reg.get_request(&self.header_cfg)
     }
 
     /// Set the received auth challenge request.
@@ -154,31 +176,96 @@ impl SoftPhone {
 
     /// Send a new Message to `uri`.
     pub fn write_message(&mut self, b: Vec<u8>, uri: Uri) -> IoResult<SipMessage> {
-        Ok(self
-            .msg
-            .write_message(b, uri, self.reg.via_header(), &self.header_cfg)?)
+        self.reg.check_local_uri()?;
+        self.msg
+            .write_message(b, uri, self.reg.via_header(), &self.header_cfg)
     }
 
-    /// Send a new Invite Request to `uri`.
+    /// Send a new Invite Request to `uri`. The Via and Contact headers
+    /// are taken from the registration manager.
     pub fn send_invite(&mut self, body: Vec<u8>, uri: Uri) -> IoResult<SipMessage> {
-        self.invite.generate_invite(uri, body)
+        self.reg.check_local_uri()?;
+        self.invite.generate_invite(
+            uri,
+            body,
+            self.reg.via_header(),
+            self.reg.dialog_contact(),
+            &self.header_cfg,
+        )
+    }
+
+    /// Answer the 401 or 407 `response` to `invite` using the credentials
+    /// of the registration manager, returning the INVITE to send again.
+    pub fn authenticate_invite(
+        &mut self,
+        invite: &SipMessage,
+        response: &SipMessage,
+    ) -> IoResult<SipMessage> {
+        let (user, pass) = self.reg.credentials().ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::InvalidInput,
+                "No credentials have been configured",
+            )
+        })?;
+        self.invite.authenticate(invite, response, user, pass)
     }
 
     /// Give the softphone a received call, returns the
-    /// ringing response to be sent.
+    /// ringing response to be sent. A re-INVITE is handled within the
+    /// dialog of it's call and gets a `100 Trying` instead, or a final
+    /// response when it matches no call or arrived out of order
+    /// ([RFC3261: Section 12.2.2](https://tools.ietf.org/html/rfc3261#section-12.2.2)).
     pub fn get_received_request(&mut self, msg: SipMessage) -> IoResult<SipMessage> {
-        let invite = InviteHelper::new(msg)?;
+        if msg.to_header_tag().is_ok() {
+            return self.receive_reinvite(msg);
+        }
+        let invite = InviteHelper::new(msg)?.contact(self.reg.dialog_contact());
         let call_id = invite.call_id()?;
         let received = invite.ringing(&self.header_cfg)?;
         self.calls.insert(call_id, invite);
         Ok(received)
     }
 
+    fn receive_reinvite(&mut self, msg: SipMessage) -> IoResult<SipMessage> {
+        let call_id = msg.call_id()?.clone();
+        let id = DialogId::from_incoming_request(&msg).ok();
+        let dialog = self
+            .calls
+            .get(&call_id)
+            .and_then(InviteHelper::dialog)
+            .filter(|dialog| Some(dialog.id()) == id.as_ref());
+        let mut dialog = match dialog {
+            Some(dialog) => dialog.clone(),
+            None => return self.simple_response(&msg, 481),
+        };
+        if dialog.on_request(&msg).is_err() {
+            return self.simple_response(&msg, 500);
+        }
+        let invite = InviteHelper::new(msg)?
+            .contact(self.reg.dialog_contact())
+            .in_dialog(dialog);
+        let trying = invite.trying(&self.header_cfg)?;
+        self.calls.insert(call_id, invite);
+        Ok(trying)
+    }
+
     /// Get a SIP Message that will accept a previously
-    /// recieved invitation.
+    /// recieved invitation. It is retransmitted by `on_timer`
+    /// until the ACK is given to `receive_ack`.
     pub fn get_accept_request(&mut self, body: Vec<u8>, call: &str) -> IoResult<SipMessage> {
+        self.get_accept_request_at(body, call, Instant::now())
+    }
+
+    /// Same as `get_accept_request`, scheduling the first
+    /// retransmission from `now`.
+    pub fn get_accept_request_at(
+        &mut self,
+        body: Vec<u8>,
+        call: &str,
+        now: Instant,
+    ) -> IoResult<SipMessage> {
         if let Some(invite) = self.calls.get_mut(call) {
-            Ok(invite.accept(body, &self.header_cfg)?)
+            Ok(invite.accept_at(body, &self.header_cfg, now)?)
         } else {
             Err(IoError::new(IoErrorKind::NotFound, "Call not found"))
         }
@@ -194,12 +281,96 @@ impl SoftPhone {
         }
     }
 
-    /// Get the messages required to cancel a invitation.
+    /// Get the messages required to cancel a invitation, see
+    /// `InviteHelper::cancel`. The call is ended unless this
+    /// cancels a re-INVITE.
     pub fn get_cancel_request(&mut self, call: &str) -> IoResult<(SipMessage, SipMessage)> {
         if let Some(invite) = self.calls.get_mut(call) {
-            invite.cancel(&self.header_cfg)
+            let messages = invite.cancel(&self.header_cfg)?;
+            if !invite.is_reinvite() {
+                self.calls.remove(call);
+            }
+            Ok(messages)
         } else {
             Err(IoError::new(IoErrorKind::NotFound, "Call not found"))
         }
     }
+
+    /// Get a SIP message rejecting a previously received
+    /// invitation with `code`, see `InviteHelper::reject`.
+    /// The call is ended unless this rejects a re-INVITE, which
+    /// keeps the session as it was.
+    pub fn get_reject_request(
+        &mut self,
+        call: &str,
+        code: u32,
+        reason: Option<&str>,
+    ) -> IoResult<SipMessage> {
+        if let Some(invite) = self.calls.get_mut(call) {
+            let response = invite.reject(code, reason, &self.header_cfg)?;
+            if !invite.is_reinvite() {
+                self.calls.remove(call);
+            }
+            Ok(response)
+        } else {
+            Err(IoError::new(IoErrorKind::NotFound, "Call not found"))
+        }
+    }
+
+    /// Give the softphone a received BYE, ending the call it belongs to.
+    /// Returns the response to send, a `481 Call/Transaction Does Not Exist`
+    /// when the BYE matches no call
+    /// ([RFC3261: Section 15.1.2](https://tools.ietf.org/html/rfc3261#section-15.1.2)).
+    pub fn receive_bye(&mut self, bye: &SipMessage) -> IoResult<SipMessage> {
+        let call_id = bye.call_id()?.clone();
+        let id = DialogId::from_incoming_request(bye).ok();
+        let known = matches!(
+            self.calls.get(&call_id).and_then(InviteHelper::dialog),
+            Some(dialog) if Some(dialog.id()) == id.as_ref()
+        );
+        let code = if known {
+            self.calls.remove(&call_id);
+            200
+        } else {
+            481
+        };
+        self.simple_response(bye, code)
+    }
+
+    fn simple_response(&self, request: &SipMessage, code: u32) -> IoResult<SipMessage> {
+        let mut response = ResponseGenerator::from_request(request)
+            .code(code)
+            .header(Header::ContentLength(0));
+        self.header_cfg.write_headers(response.headers_ref_mut());
+        response.build()
+    }
+
+    /// Give the softphone a received ACK. Returns true if it
+    /// acknowledged an accepted call.
+    pub fn receive_ack(&mut self, ack: &SipMessage) -> IoResult<bool> {
+        match self.calls.get_mut(ack.call_id()?) {
+            Some(invite) => invite.receive_ack(ack),
+            None => Ok(false),
+        }
+    }
+
+    /// Fire the timers of every call at `now`, returning the
+    /// responses to retransmit and the requests to send.
+    pub fn on_timer(&mut self, now: Instant) -> IoResult<Vec<SipMessage>> {
+        let mut messages = vec![];
+        for invite in self.calls.values_mut() {
+            if let Some(msg) = invite.on_timer(now, &self.header_cfg)? {
+                messages.push(msg);
+            }
+        }
+        Ok(messages)
+    }
+
+    /// The instant `on_timer` should next be called.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        self.calls
+            .values()
+            .filter_map(InviteHelper::next_timeout)
+            .min()
+    }
 }
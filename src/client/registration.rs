@@ -1,6 +1,40 @@
-use crate::*;
+use crate::{core::generate_branch, *};
 
-use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+use rand::Rng;
+
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    time::{Duration, Instant},
+};
+
+/// The base and maximum seconds a failed refresh is delayed by
+/// ([RFC5626: Section 4.5](https://tools.ietf.org/html/rfc5626#section-4.5)).
+const BACKOFF_BASE: u32 = 30;
+const BACKOFF_MAX: u32 = 1800;
+
+/// The outcome of a response to a REGISTER request.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RegistrationStatus {
+    /// The registrar accepted the binding for this many seconds.
+    Registered(u32),
+    /// The registrar removed the bindings.
+    Unregistered,
+    /// The request was challenged, send `get_request` again.
+    Challenged,
+    /// The registrar requires an interval of at least this many seconds,
+    /// send `get_request` again.
+    IntervalTooBrief(u32),
+    /// The request failed with this status code.
+    Failed(u32),
+}
+
+/// What the REGISTER requests of a `RegistrationManager` ask for.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Action {
+    Register,
+    Unregister,
+    UnregisterAll,
+}
 
 /// Handle's the SIP registration process.
 /// This structure is designed to handle the authentication
@@ -15,12 +49,8 @@ pub struct RegistrationManager {
     local_uri: Uri,
     /// Current REGISTER cseq count number.
     cseq_counter: u32,
-    /// The computed hash nonce count.
-    nonce_c: u32,
-    /// The CNonce value of the computer hash.
-    c_nonce: Option<String>,
-    /// The Finished computed auth header.
-    auth_header: Option<AuthHeader>,
+    /// The challenges received from the registrar and proxies.
+    challenges: AuthChallenges,
     /// The branch to use for registration.
     branch: String,
     /// The Call Id to use for register requests.
@@ -31,10 +61,30 @@ pub struct RegistrationManager {
     user: Option<String>,
     /// The password to use for login.
     pass: Option<String>,
-    /// Authentication realm
-    realm: Option<String>,
-    /// Authentication nonce
-    nonce: Option<String>,
+    /// What the next request will ask the registrar to do.
+    action: Action,
+    /// The interval granted in the last successful registration.
+    granted: Option<u32>,
+    /// When the current registration expires.
+    expires_at: Option<Instant>,
+    /// When the current registration should be refreshed.
+    refresh_at: Option<Instant>,
+    /// The number of refreshes that failed in a row.
+    failures: u32,
+    /// The bindings the registrar returned in it's last 200 response.
+    bindings: Vec<ContactHeader>,
+    /// The `+sip.instance` identifying this device.
+    instance: Option<String>,
+    /// The `reg-id` of the flow used for outbound registrations.
+    reg_id: Option<u32>,
+    /// Whether the registrar accepted the outbound registration.
+    outbound: bool,
+    /// The Flow-Timer returned by the registrar.
+    flow_timer: Option<u32>,
+    /// The public GRUU assigned by the registrar.
+    pub_gruu: Option<Uri>,
+    /// The temporary GRUU assigned by the registrar.
+    temp_gruu: Option<Uri>,
 }
 
 impl RegistrationManager {
@@ -46,16 +96,24 @@ impl RegistrationManager {
             account_uri,
             local_uri,
             cseq_counter: 444,
-            auth_header: None,
-            nonce_c: 1,
-            c_nonce: None,
-            branch: format!("{:x}", md5::compute(rand::random::<[u8; 16]>())),
+            challenges: AuthChallenges::new(),
+            branch: generate_branch(),
             call_id: format!("{:x}", md5::compute(rand::random::<[u8; 16]>())),
             expires_header: None,
             user: None,
             pass: None,
-            realm: None,
-            nonce: None,
+            action: Action::Register,
+            granted: None,
+            expires_at: None,
+            refresh_at: None,
+            failures: 0,
+            bindings: vec![],
+            instance: None,
+            reg_id: None,
+            outbound: false,
+            flow_timer: None,
+            pub_gruu: None,
+            temp_gruu: None,
         }
     }
 
@@ -69,35 +127,55 @@ impl RegistrationManager {
         self.pass = Some(p.into());
     }
 
+    /// The username and password used in the authentication process.
+    pub(crate) fn credentials(&self) -> Option<(&str, &str)> {
+        match (&self.user, &self.pass) {
+            (Some(user), Some(pass)) => Some((user, pass)),
+            _ => None,
+        }
+    }
+
+    /// Set the registration interval to ask the registrar for.
+    pub fn set_expires(&mut self, expires: u32) {
+        self.expires_header = Some(expires);
+    }
+
+    /// Set the `+sip.instance` of this device, usually a `urn:uuid:` that
+    /// stays the same across restarts. It is required to be assigned a GRUU
+    /// ([RFC5627: Section 4.1](https://tools.ietf.org/html/rfc5627#section-4.1)).
+    pub fn instance<S: Into<String>>(&mut self, urn: S) {
+        self.instance = Some(urn.into());
+    }
+
+    /// Register using SIP Outbound over the flow numbered `reg_id`, an
+    /// instance must be set as well
+    /// ([RFC5626: Section 4.2](https://tools.ietf.org/html/rfc5626#section-4.2)).
+    pub fn reg_id(&mut self, reg_id: u32) {
+        self.reg_id = Some(reg_id);
+    }
+
     /// Get the register request. if this method is called before `set_challenge`
     /// then no authentication header will be set, if called after `set_challenge`
-    /// then the Authorization header will be set.
+    /// then an Authorization or Proxy-Authorization header will be set for
+    /// each challenge received. After `get_unregister_request` this keeps
+    /// producing de-registrations until the registrar has accepted one.
     pub fn get_request(&mut self, cfg: &HeaderWriteConfig) -> IoResult<SipMessage> {
+        require_host(&self.account_uri)?;
+        self.check_local_uri()?;
         self.cseq_counter += 1;
-        self.nonce_c += 1;
+        self.branch = generate_branch();
         let to_header = self.account_uri.clone();
         let from_header = self.account_uri.clone();
-        let mut contact_header = self.local_uri.clone();
+        let request_uri = self.account_uri.clone().authless();
         let mut headers = vec![];
 
-        if let Some(name) = &self.user {
-            contact_header = contact_header.auth(UriAuth::new(name));
-            if let Some(auth_header) = &self.auth_header {
-                if let Some(pass) = &self.pass {
-                    let ctx = AuthContext {
-                        user: &name,
-                        pass,
-                        nc: self.nonce_c,
-                        uri: &self.account_uri,
-                    };
-                    headers.push(Header::Authorization(auth_header.authenticate(ctx)?));
-                }
-            }
-        }
         headers.push(Header::ContentLength(0));
         headers.push(Header::To(NamedHeader::new(to_header)));
         headers.push(Header::From(NamedHeader::new(from_header)));
-        headers.push(Header::Contact(ContactHeader::new(contact_header)));
+        headers.push(Header::Contact(match self.action {
+            Action::UnregisterAll => ContactHeader::wildcard(),
+            _ => self.register_contact(),
+        }));
         headers.push(Header::CSeq(self.cseq_counter, Method::Register));
         headers.push(Header::CallId(format!(
             "{}@{}",
@@ -106,30 +184,261 @@ impl RegistrationManager {
         )));
         headers.push(self.via_header());
         cfg.write_headers_vec(&mut headers);
+        self.add_supported(&mut headers);
 
-        if let Some(exp) = self.expires_header {
-            headers.push(Header::Expires(exp));
+        match self.action {
+            Action::Register => {
+                if let Some(exp) = self.expires_header {
+                    headers.push(Header::Expires(exp));
+                }
+            },
+            Action::Unregister | Action::UnregisterAll => headers.push(Header::Expires(0)),
         }
-        Ok(RequestGenerator::new()
+        let mut request = RequestGenerator::new()
             .method(Method::Register)
-            .uri(self.account_uri.clone().authless())
+            .uri(request_uri)
             .headers(headers)
-            .build()?)
+            .build()?;
+        if let (Some(user), Some(pass)) = (&self.user, &self.pass) {
+            if !self.challenges.is_empty() {
+                self.challenges.authorize(&mut request, user, pass)?;
+            }
+        }
+        Ok(request)
+    }
+
+    /// Get a request removing this device's binding, or every binding of
+    /// the account when `all` is set, as described in
+    /// [RFC3261: Section 10.2.2](https://tools.ietf.org/html/rfc3261#section-10.2.2).
+    pub fn get_unregister_request(
+        &mut self,
+        cfg: &HeaderWriteConfig,
+        all: bool,
+    ) -> IoResult<SipMessage> {
+        self.action = if all {
+            Action::UnregisterAll
+        } else {
+            Action::Unregister
+        };
+        self.get_request(cfg)
+    }
+
+    /// Process the final response to the last request received at `now`.
+    /// Challenges are stored for the next request, they fail the registration
+    /// when no credentials are set or the credentials were rejected. A
+    /// `423 Interval Too Brief` raises the requested interval to the
+    /// registrar's `Min-Expires` and a 200 response updates the bindings and
+    /// schedules the next refresh.
+    pub fn handle_response(
+        &mut self,
+        response: &SipMessage,
+        now: Instant,
+    ) -> IoResult<RegistrationStatus> {
+        match response.status_code()? {
+            200..=299 => Ok(self.handle_success(response, now)),
+            code @ 401 | code @ 407 => {
+                // The last request answered the challenges already received,
+                // a new challenge for the same realm means the credentials are
+                // wrong unless it only reports a stale nonce.
+                let rejected = self.challenges.rejected(response);
+                if self.challenges.set_challenge(response)? == 0
+                    || rejected
+                    || self.credentials().is_none()
+                {
+                    self.back_off(retry_after(response), now);
+                    return Ok(RegistrationStatus::Failed(code));
+                }
+                Ok(RegistrationStatus::Challenged)
+            },
+            423 => {
+                let min_expires = response
+                    .headers()
+                    .iter()
+                    .find_map(|header| match header {
+                        Header::MinExpires(min) => Some(*min),
+                        _ => None,
+                    })
+                    .ok_or_else(|| {
+                        IoError::new(
+                            IoErrorKind::InvalidData,
+                            "423 response without a Min-Expires header",
+                        )
+                    })?;
+                self.expires_header = Some(min_expires);
+                Ok(RegistrationStatus::IntervalTooBrief(min_expires))
+            },
+            code if code < 200 => Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "provisional responses do not complete a registration",
+            )),
+            code => {
+                self.back_off(retry_after(response), now);
+                Ok(RegistrationStatus::Failed(code))
+            },
+        }
+    }
+
+    /// The register request sent at `now` timed out, the next refresh
+    /// is delayed as for a failure response.
+    pub fn timed_out(&mut self, now: Instant) {
+        self.back_off(None, now);
+    }
+
+    /// Push a failed refresh back by the server's Retry-After, or
+    /// otherwise by the capped exponential delay of
+    /// [RFC5626: Section 4.5](https://tools.ietf.org/html/rfc5626#section-4.5)
+    /// so the registrar is not flooded. A registration that was never
+    /// accepted is not retried.
+    fn back_off(&mut self, retry_after: Option<u32>, now: Instant) {
+        if self.refresh_at.is_none() {
+            return;
+        }
+        self.failures = self.failures.saturating_add(1);
+        let seconds = match retry_after {
+            Some(seconds) => f64::from(seconds),
+            None => {
+                let wait = (BACKOFF_BASE << self.failures.min(6)).min(BACKOFF_MAX);
+                rand::thread_rng().gen_range(f64::from(wait) * 0.5, f64::from(wait))
+            },
+        };
+        self.refresh_at = Some(now + Duration::from_secs_f64(seconds));
+    }
+
+    fn handle_success(&mut self, response: &SipMessage, now: Instant) -> RegistrationStatus {
+        self.bindings = response
+            .headers()
+            .iter()
+            .filter_map(|header| match header {
+                Header::Contact(contact) => Some(contact.clone()),
+                _ => None,
+            })
+            .collect();
+        if self.action != Action::Register {
+            self.action = Action::Register;
+            self.granted = None;
+            self.expires_at = None;
+            self.refresh_at = None;
+            self.outbound = false;
+            self.pub_gruu = None;
+            self.temp_gruu = None;
+            return RegistrationStatus::Unregistered;
+        }
+        let binding = self.own_binding().cloned();
+        self.pub_gruu = binding.as_ref().and_then(ContactHeader::pub_gruu);
+        self.temp_gruu = binding.as_ref().and_then(ContactHeader::temp_gruu);
+        self.outbound = self.reg_id.is_some()
+            && response.headers().iter().any(|header| match header {
+                Header::Require(tags) => tags
+                    .split(',')
+                    .any(|tag| tag.trim().eq_ignore_ascii_case("outbound")),
+                _ => false,
+            });
+        self.flow_timer = response.headers().iter().find_map(|header| match header {
+            Header::Other(key, value) if key.eq_ignore_ascii_case("Flow-Timer") => {
+                value.trim().parse().ok().filter(|timer| *timer > 0)
+            },
+            _ => None,
+        });
+        let granted = binding
+            .as_ref()
+            .and_then(|binding| binding.parameters.get("expires"))
+            .and_then(|expires| expires.as_ref())
+            .and_then(|expires| expires.parse::<u32>().ok())
+            .or_else(|| response.expires().ok())
+            .unwrap_or_else(|| self.expires());
+        let interval = Duration::from_secs(granted as u64);
+        let ratio = rand::thread_rng().gen_range(0.5, 0.9);
+        self.failures = 0;
+        self.granted = Some(granted);
+        self.expires_at = Some(now + interval);
+        self.refresh_at = Some(now + interval.mul_f64(ratio));
+        RegistrationStatus::Registered(granted)
+    }
+
+    /// The interval granted by the registrar for the current registration.
+    pub fn granted_expires(&self) -> Option<u32> {
+        self.granted
+    }
+
+    /// When the current registration should be refreshed, a random point
+    /// between 50% and 90% of the granted interval so that many clients
+    /// do not refresh at once.
+    pub fn refresh_at(&self) -> Option<Instant> {
+        self.refresh_at
+    }
+
+    /// Determine if the registration should be refreshed at `now`.
+    pub fn needs_refresh(&self, now: Instant) -> bool {
+        self.refresh_at.map(|at| now >= at).unwrap_or(false)
+    }
+
+    /// Determine if the registration is still valid at `now`.
+    pub fn is_registered(&self, now: Instant) -> bool {
+        self.expires_at.map(|at| now < at).unwrap_or(false)
+    }
+
+    /// The bindings the registrar returned for the account in it's last
+    /// successful response, including those of other devices.
+    pub fn bindings(&self) -> &[ContactHeader] {
+        &self.bindings
+    }
+
+    /// Determine if the registrar accepted the outbound registration by
+    /// requiring the `outbound` option tag in it's response.
+    pub fn is_outbound(&self) -> bool {
+        self.outbound
+    }
+
+    /// The Flow-Timer the registrar asked keep-alives to be sent at,
+    /// pass it to `KeepAlive::new`.
+    pub fn flow_timer(&self) -> Option<u32> {
+        self.flow_timer
+    }
+
+    /// The public GRUU assigned to this device.
+    pub fn pub_gruu(&self) -> Option<&Uri> {
+        self.pub_gruu.as_ref()
+    }
+
+    /// The temporary GRUU assigned to this device by the last registration.
+    pub fn temp_gruu(&self) -> Option<&Uri> {
+        self.temp_gruu.as_ref()
+    }
+
+    /// The Contact to place in dialog forming requests and responses. This
+    /// is the public GRUU when one was assigned, otherwise the local uri
+    /// marked with the `ob` parameter when using outbound
+    /// ([RFC5626: Section 4.3](https://tools.ietf.org/html/rfc5626#section-4.3)).
+    pub fn dialog_contact(&self) -> ContactHeader {
+        if let Some(gruu) = &self.pub_gruu {
+            return ContactHeader::new(gruu.clone());
+        }
+        let mut uri = self.contact_uri();
+        if self.outbound {
+            uri = uri.parameter(UriParam::Ob);
+        }
+        ContactHeader::new(uri)
+    }
+
+    /// The flow the registration was sent over failed at `now`, a
+    /// keep-alive went unanswered for example. The registration must be
+    /// refreshed over a new flow straight away
+    /// ([RFC5626: Section 4.4.1](https://tools.ietf.org/html/rfc5626#section-4.4.1)).
+    pub fn flow_failed(&mut self, now: Instant) {
+        if self.refresh_at.is_some() {
+            self.refresh_at = Some(now);
+        }
     }
 
     /// After the first register request is sent. pass the received sip response
-    /// to this function to perform compute the hashed password.
+    /// to this function to perform compute the hashed password. Both 401 and
+    /// 407 responses are accepted and every challenge they contain is answered.
     pub fn set_challenge(&mut self, msg: SipMessage) -> IoResult<()> {
+        self.challenges.set_challenge(&msg)?;
         if let SipMessage::Response { headers, .. } = msg {
             for item in headers.into_iter() {
-                match item {
-                    Header::WwwAuthenticate(auth) => {
-                        self.auth_header = Some(auth);
-                    },
-                    Header::Expires(expire) => {
-                        self.expires_header = Some(expire);
-                    },
-                    _ => {},
+                if let Header::Expires(expire) = item {
+                    self.expires_header = Some(expire);
                 }
             }
             Ok(())
@@ -151,19 +460,108 @@ impl RegistrationManager {
         self.cseq_counter
     }
 
+    /// The Contact header of this device placed in REGISTER requests.
+    fn register_contact(&self) -> ContactHeader {
+        let mut contact = ContactHeader::new(self.contact_uri());
+        if let Some(instance) = &self.instance {
+            contact = contact.instance(instance.clone());
+            if let Some(reg_id) = self.reg_id {
+                contact = contact.reg_id(reg_id);
+            }
+        }
+        contact
+    }
+
+    /// The binding of this device among those returned by the registrar.
+    fn own_binding(&self) -> Option<&ContactHeader> {
+        let contact = self.contact_uri();
+        self.bindings.iter().find(|binding| match &self.instance {
+            Some(instance) if binding.sip_instance().is_some() => {
+                binding.sip_instance().as_ref() == Some(instance)
+                    && binding.registration_id() == self.reg_id
+            },
+            _ => binding.uri.host == contact.host && binding.uri.auth == contact.auth,
+        })
+    }
+
+    /// Advertise GRUU and outbound support. The option tags are merged
+    /// into a Supported header already written from the `HeaderWriteConfig`.
+    fn add_supported(&self, headers: &mut Vec<Header>) {
+        if self.instance.is_none() {
+            return;
+        }
+        let mut tags = vec!["gruu".to_string()];
+        if self.reg_id.is_some() {
+            tags.push("outbound".to_string());
+        }
+        let existing = headers.iter_mut().find_map(|header| match header {
+            Header::Supported(existing) => Some(existing),
+            _ => None,
+        });
+        match existing {
+            Some(existing) => {
+                for tag in tags {
+                    if !existing.iter().any(|item| item.eq_ignore_ascii_case(&tag)) {
+                        existing.push(tag);
+                    }
+                }
+            },
+            None => headers.push(Header::Supported(tags)),
+        }
+    }
+
+    /// The uri placed in the Contact header of this device.
+    fn contact_uri(&self) -> Uri {
+        match &self.user {
+            Some(name) => self.local_uri.clone().auth(UriAuth::new(name)),
+            None => self.local_uri.clone(),
+        }
+    }
+
+    /// Make sure the local uri has a host to build the Via header with,
+    /// a tel uri does not.
+    pub(crate) fn check_local_uri(&self) -> IoResult<()> {
+        require_host(&self.local_uri)
+    }
+
     /// Retreive the via header being used to represent the local
     /// listening socket.
     pub fn via_header(&self) -> Header {
-        let via_uri = self
+        let transport = self
             .local_uri
-            .clone()
-            .parameter(UriParam::Branch(self.branch.clone()))
-            .authless()
-            .schemaless();
+            .parameters
+            .iter()
+            .find_map(|param| match param {
+                UriParam::Transport(transport) => Some(*transport),
+                _ => None,
+            })
+            .unwrap_or(Transport::Udp);
         Header::Via(ViaHeader {
-            uri: via_uri,
-            version: Default::default(),
-            transport: Transport::Udp,
+            branch: Some(self.branch.clone()),
+            ..ViaHeader::new(self.local_uri.host.clone(), transport)
         })
     }
 }
+
+fn require_host(uri: &Uri) -> IoResult<()> {
+    match uri.host_domain() {
+        Some(_) => Ok(()),
+        None => Err(IoError::new(
+            IoErrorKind::InvalidInput,
+            format!("'{}' has no host", uri),
+        )),
+    }
+}
+
+/// The seconds given in a Retry-After header, ignoring any comment
+/// or parameters.
+fn retry_after(response: &SipMessage) -> Option<u32> {
+    response.headers().iter().find_map(|header| match header {
+        Header::RetryAfter(value) => value
+            .trim_start()
+            .split(|chr: char| !chr.is_ascii_digit())
+            .next()
+            .and_then(|seconds| seconds.parse().ok()),
+        _ => None,
+    })
+}
@@ -0,0 +1,158 @@
+use nom::error::VerboseError;
+
+use crate::{core::message::parse_message, Header, SipMessage};
+
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+
+/// The largest message `MessageDecoder` will buffer by default.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 65_535;
+
+/// One item framed by the `MessageDecoder`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Decoded {
+    /// A complete SIP message.
+    Message(SipMessage),
+    /// A double CRLF keep-alive ping,
+    /// [RFC5626: Section 4.4.1](https://tools.ietf.org/html/rfc5626#section-4.4.1).
+    Ping,
+    /// A single CRLF, the response to a keep-alive ping. Only decoded
+    /// once it can't be the start of a ping, see `MessageDecoder::pings`.
+    Pong,
+    /// The buffered data doesn't contain a complete message yet.
+    NeedMore,
+}
+
+/// Incremental decoder for SIP messages arriving over a stream transport
+/// such as TCP or TLS. Data is buffered until a complete message is
+/// available, using the Content-Length header to find the end of the body
+/// as required by [RFC3261: Section 18.3](https://tools.ietf.org/html/rfc3261#section-18.3).
+/// A missing Content-Length header is treated as an empty body.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MessageDecoder {
+    buffer: Vec<u8>,
+    max_size: usize,
+    pings: bool,
+}
+
+impl Default for MessageDecoder {
+    fn default() -> MessageDecoder {
+        MessageDecoder::new()
+    }
+}
+
+impl MessageDecoder {
+    /// Create a new decoder with an empty buffer.
+    pub fn new() -> MessageDecoder {
+        MessageDecoder {
+            buffer: vec![],
+            max_size: DEFAULT_MAX_MESSAGE_SIZE,
+            pings: true,
+        }
+    }
+
+    /// Set whether keep-alive pings can arrive on this stream. Pings are
+    /// only sent by the side that opened the connection, so a decoder for
+    /// a connection we opened should disable them. Every CRLF is then
+    /// decoded as a `Decoded::Pong` right away, instead of waiting for the
+    /// data needed to tell a pong from a ping that arrived in two parts.
+    pub fn pings(mut self, pings: bool) -> MessageDecoder {
+        self.pings = pings;
+        self
+    }
+
+    /// Set the largest message size, in bytes, this decoder will accept.
+    pub fn max_size(mut self, max_size: usize) -> MessageDecoder {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Retrieve the data that has been buffered but not decoded yet.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Add data read from the transport to the buffer.
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Remove the next item from the buffer. Returns `Decoded::NeedMore`
+    /// when the buffer does not contain a complete message. An error is
+    /// returned if the message can not be parsed or is larger than the
+    /// maximum size, after which the connection should be closed.
+    pub fn decode(&mut self) -> IoResult<Decoded> {
+        if self.pings && self.buffer.starts_with(b"\r\n\r\n") {
+            self.buffer.drain(..4);
+            return Ok(Decoded::Ping);
+        }
+        if self.buffer.starts_with(b"\r\n") {
+            // Wait for the rest of a possible ping.
+            if self.pings && b"\r\n\r\n".starts_with(&self.buffer) {
+                return Ok(Decoded::NeedMore);
+            }
+            self.buffer.drain(..2);
+            return Ok(Decoded::Pong);
+        }
+        let head_len = match find_header_end(&self.buffer) {
+            Some(index) => index + 4,
+            None if self.buffer.len() > self.max_size => return Err(too_large()),
+            None => return Ok(Decoded::NeedMore),
+        };
+        let mut msg = match parse_message::<VerboseError<&[u8]>>(&self.buffer[..head_len]) {
+            Ok((_, msg)) => msg,
+            Err(err) => {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidData,
+                    format!("Failed to parse SIP message: {:?}", err),
+                ))
+            },
+        };
+        let body_len = content_length(&msg);
+        let total = head_len + body_len;
+        if total > self.max_size {
+            return Err(too_large());
+        }
+        if self.buffer.len() < total {
+            return Ok(Decoded::NeedMore);
+        }
+        *msg.body_mut() = self.buffer[head_len..total].to_vec();
+        self.buffer.drain(..total);
+        Ok(Decoded::Message(msg))
+    }
+
+    /// Add `data` to the buffer and decode every complete item.
+    pub fn decode_all(&mut self, data: &[u8]) -> IoResult<Vec<Decoded>> {
+        self.extend(data);
+        let mut items = vec![];
+        loop {
+            match self.decode()? {
+                Decoded::NeedMore => return Ok(items),
+                item => items.push(item),
+            }
+        }
+    }
+}
+
+fn find_header_end(data: &[u8]) -> Option<usize> {
+    data.windows(4).position(|window| window == b"\r\n\r\n")
+}
+
+fn content_length(msg: &SipMessage) -> usize {
+    msg.headers()
+        .iter()
+        .find_map(|header| {
+            if let Header::ContentLength(len) = header {
+                Some(*len as usize)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(0)
+}
+
+fn too_large() -> IoError {
+    IoError::new(
+        IoErrorKind::InvalidData,
+        "SIP message exceeds the maximum message size",
+    )
+}
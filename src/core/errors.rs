@@ -1,3 +1,5 @@
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SipMessageError {
     MissingFromHeader,
@@ -20,3 +22,9 @@ pub enum SipMessageError {
     MissingContactExpires,
     MissingRegisterExpires,
 }
+
+impl From<SipMessageError> for IoError {
+    fn from(err: SipMessageError) -> IoError {
+        IoError::new(IoErrorKind::InvalidData, format!("{:?}", err))
+    }
+}
@@ -4,7 +4,7 @@ use std::fmt;
 
 use crate::{
     core::{code::error_code_to_str, method::parse_method, version::parse_version},
-    headers::parse_header,
+    headers::parse::parse_header_line,
     parse::{is_reserved, is_unreserved, parse_byte_vec, parse_u32},
     uri::parse_uri,
     SipMessageExt, *,
@@ -31,20 +31,12 @@ pub enum SipMessage {
 impl SipMessage {
     /// Determine if this is a SIP request.
     pub fn is_request(&self) -> bool {
-        if let SipMessage::Request { .. } = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, SipMessage::Request { .. })
     }
 
     /// Determine if this is a SIP response.
     pub fn is_response(&self) -> bool {
-        if let SipMessage::Response { .. } = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, SipMessage::Response { .. })
     }
 
     pub fn method(&self) -> Result<Method, SipMessageError> {
@@ -142,7 +134,7 @@ pub fn display_headers_and_body(
         writeln!(f, "{}\r", header)?;
     }
     writeln!(f, "\r")?;
-    f.write_str(&String::from_utf8_lossy(&body))?;
+    f.write_str(&String::from_utf8_lossy(body))?;
     Ok(())
 }
 
@@ -152,8 +144,8 @@ pub fn parse_headers<'a, E: ParseError<&'a [u8]>>(
 ) -> IResult<&'a [u8], Headers, E> {
     let mut headers = Headers(vec![]);
     let mut input = input;
-    while let Ok((data, value)) = parse_header::<E>(input) {
-        headers.push(value);
+    while let Ok((data, values)) = parse_header_line::<E>(input) {
+        headers.0.extend(values);
         input = data;
     }
     Ok((input, headers))
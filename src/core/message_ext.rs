@@ -1,5 +1,7 @@
+use super::generate_branch;
 use crate::{ContactHeader, Header, Method, NamedHeader, SipMessage, SipMessageError, ViaHeader};
 
+#[allow(clippy::wrong_self_convention)]
 pub trait SipMessageExt {
     fn from_header(&self) -> Result<&NamedHeader, SipMessageError>;
 
@@ -27,6 +29,17 @@ pub trait SipMessageExt {
 
     fn via_header_branch(&self) -> Result<&String, SipMessageError>;
 
+    /// Returns every Via header, the top one first.
+    fn via_headers(&self) -> Vec<&ViaHeader>;
+
+    /// Add `via` as the new top Via header, giving it a branch starting
+    /// with the RFC 3261 magic cookie if it has none.
+    fn push_via(&mut self, via: ViaHeader);
+
+    /// Remove and return the top Via header, as done by a proxy forwarding
+    /// a response ([RFC3261: Section 16.7](https://tools.ietf.org/html/rfc3261#section-16.7)).
+    fn pop_via(&mut self) -> Option<ViaHeader>;
+
     fn call_id(&self) -> Result<&String, SipMessageError>;
 
     fn call_id_mut(&mut self) -> Result<&mut String, SipMessageError>;
@@ -177,6 +190,39 @@ impl SipMessageExt for SipMessage {
         }
     }
 
+    fn via_headers(&self) -> Vec<&ViaHeader> {
+        self.headers()
+            .iter()
+            .filter_map(|header| match header {
+                Header::Via(via) => Some(via),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn push_via(&mut self, mut via: ViaHeader) {
+        if via.branch.is_none() {
+            via.branch = Some(generate_branch());
+        }
+        let headers = &mut self.headers_mut().0;
+        let index = headers
+            .iter()
+            .position(|header| matches!(header, Header::Via(_)))
+            .unwrap_or(0);
+        headers.insert(index, Header::Via(via));
+    }
+
+    fn pop_via(&mut self) -> Option<ViaHeader> {
+        let headers = &mut self.headers_mut().0;
+        let index = headers
+            .iter()
+            .position(|header| matches!(header, Header::Via(_)))?;
+        match headers.remove(index) {
+            Header::Via(via) => Some(via),
+            _ => None,
+        }
+    }
+
     fn call_id(&self) -> Result<&String, SipMessageError> {
         header!(
             self.headers().0.iter(),
@@ -1,8 +1,10 @@
 use nom::{branch::alt, bytes::complete::tag_no_case, combinator::map, error::ParseError, IResult};
+use serde::{Deserialize, Serialize};
+
 use std::fmt;
 
 /// SIP protocol methods.
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub enum Method {
     Invite,
     Ack,
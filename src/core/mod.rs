@@ -12,6 +12,9 @@ pub use self::version::{parse_version, Version};
 pub mod message;
 pub use self::message::{parse_message, parse_request, parse_response, SipMessage};
 
+mod decoder;
+pub use self::decoder::{Decoded, MessageDecoder, DEFAULT_MAX_MESSAGE_SIZE};
+
 mod message_ext;
 pub use self::message_ext::SipMessageExt;
 
@@ -29,3 +32,6 @@ pub use self::request_ext::RequestExt;
 
 mod errors;
 pub use self::errors::SipMessageError;
+
+mod random;
+pub use self::random::{generate_branch, generate_tag};
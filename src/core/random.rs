@@ -0,0 +1,9 @@
+/// Generate a new random value suitable for a From or To tag.
+pub fn generate_tag() -> String {
+    format!("{:x}", md5::compute(rand::random::<[u8; 16]>()))
+}
+
+/// Generate a new random Via branch, including the RFC 3261 magic cookie.
+pub fn generate_branch() -> String {
+    format!("z9hG4bK{:x}", md5::compute(rand::random::<[u8; 16]>()))
+}
@@ -4,15 +4,53 @@ use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// SIP protocol transport.
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default, Serialize, Deserialize)]
 pub enum Transport {
+    #[default]
     Udp,
     Tcp,
+    /// TCP secured with TLS, used for `sips:` uris.
+    Tls,
+    /// [RFC4168](https://tools.ietf.org/html/rfc4168)
+    Sctp,
+    /// WebSocket, [RFC7118](https://tools.ietf.org/html/rfc7118).
+    Ws,
+    /// Secure WebSocket, [RFC7118](https://tools.ietf.org/html/rfc7118).
+    Wss,
 }
 
-impl Default for Transport {
-    fn default() -> Transport {
-        Transport::Udp
+impl Transport {
+    /// Determine if this transport delivers messages reliably, in which
+    /// case SIP retransmission timers are not used.
+    pub fn is_reliable(&self) -> bool {
+        match self {
+            Transport::Udp => false,
+            Transport::Tcp | Transport::Tls | Transport::Sctp | Transport::Ws | Transport::Wss => {
+                true
+            },
+        }
+    }
+
+    /// Determine if this transport carries a byte stream, in which case
+    /// messages must be framed with a `MessageDecoder`. The other
+    /// transports deliver one message at a time.
+    pub fn is_stream(&self) -> bool {
+        matches!(self, Transport::Tcp | Transport::Tls)
+    }
+
+    /// Determine if this transport is encrypted.
+    pub fn is_secure(&self) -> bool {
+        matches!(self, Transport::Tls | Transport::Wss)
+    }
+
+    /// The port used when a uri or Via header doesn't specify one.
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Transport::Udp | Transport::Tcp | Transport::Sctp => 5060,
+            Transport::Tls => 5061,
+            Transport::Ws => 80,
+            Transport::Wss => 443,
+        }
     }
 }
 
@@ -21,6 +59,10 @@ impl fmt::Display for Transport {
         match self {
             Transport::Udp => write!(f, "UDP"),
             Transport::Tcp => write!(f, "TCP"),
+            Transport::Tls => write!(f, "TLS"),
+            Transport::Sctp => write!(f, "SCTP"),
+            Transport::Ws => write!(f, "WS"),
+            Transport::Wss => write!(f, "WSS"),
         }
     }
 }
@@ -32,5 +74,9 @@ pub fn parse_transport<'a, E: ParseError<&'a [u8]>>(
     alt::<_, _, E, _>((
         map(tag_no_case::<_, _, E>("TCP"), |_| Transport::Tcp),
         map(tag_no_case::<_, _, E>("UDP"), |_| Transport::Udp),
+        map(tag_no_case::<_, _, E>("TLS"), |_| Transport::Tls),
+        map(tag_no_case::<_, _, E>("SCTP"), |_| Transport::Sctp),
+        map(tag_no_case::<_, _, E>("WSS"), |_| Transport::Wss),
+        map(tag_no_case::<_, _, E>("WS"), |_| Transport::Ws),
     ))(input)
 }
@@ -0,0 +1,416 @@
+//! Dialog state as described in [RFC3261: Section 12](https://tools.ietf.org/html/rfc3261#section-12).
+
+use crate::{routing, *};
+
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+
+pub use crate::core::{generate_branch, generate_tag};
+
+/// The values identifying a dialog. Local and remote are
+/// always from the point of view of this user agent.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct DialogId {
+    pub call_id: String,
+    pub local_tag: String,
+    pub remote_tag: String,
+}
+
+impl DialogId {
+    /// The id of the dialog a request received from the remote side belongs to.
+    pub fn from_incoming_request(req: &SipMessage) -> Result<DialogId, SipMessageError> {
+        Ok(DialogId {
+            call_id: req.call_id()?.clone(),
+            local_tag: req.to_header_tag()?.clone(),
+            remote_tag: req.from_header_tag()?.clone(),
+        })
+    }
+
+    /// The id of the dialog a response to one of our requests belongs to.
+    pub fn from_incoming_response(res: &SipMessage) -> Result<DialogId, SipMessageError> {
+        Ok(DialogId {
+            call_id: res.call_id()?.clone(),
+            local_tag: res.from_header_tag()?.clone(),
+            remote_tag: res.to_header_tag()?.clone(),
+        })
+    }
+}
+
+/// The state of a dialog.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DialogState {
+    /// Created by a provisional response carrying a To tag.
+    Early,
+    /// Created or confirmed by a 2xx response.
+    Confirmed,
+    /// Ended by a BYE or a non-2xx final response to the initial request.
+    Terminated,
+}
+
+/// A peer-to-peer relationship between two user agents. The Dialog keeps
+/// track of the route set, the remote target and both CSeq counters and
+/// uses them to generate requests within the dialog.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Dialog {
+    id: DialogId,
+    state: DialogState,
+    /// The CSeq number of the last request sent within the dialog.
+    local_seq: u32,
+    /// The CSeq number of the last request received within the dialog.
+    remote_seq: Option<u32>,
+    /// Our From / To header value, including the local tag.
+    local: NamedHeader,
+    /// Their From / To header value, including the remote tag.
+    remote: NamedHeader,
+    /// Where requests within the dialog are sent.
+    remote_target: Uri,
+    /// The route set, in the order requests traverse it.
+    route_set: Vec<RouteHeader>,
+    /// Our Contact header, sent on target refresh requests.
+    local_contact: Option<ContactHeader>,
+    /// The Via header used for requests, a new branch is added for each one.
+    local_via: ViaHeader,
+}
+
+impl Dialog {
+    /// Create a dialog from the UAC side, using the request that was sent and
+    /// a 2xx or a provisional response with a To tag.
+    /// [RFC3261: Section 12.1.2](https://tools.ietf.org/html/rfc3261#section-12.1.2)
+    pub fn from_uac(request: &SipMessage, response: &SipMessage) -> IoResult<Dialog> {
+        let state = dialog_state_for(response)?;
+        let route_set = routing::dialog_route_set(response, true);
+        let (local_seq, _) = request.cseq()?;
+        Ok(Dialog {
+            id: DialogId::from_incoming_response(response)?,
+            state,
+            local_seq,
+            remote_seq: None,
+            local: request.from_header()?.clone(),
+            remote: response.to_header()?.clone(),
+            remote_target: response.contact_header()?.uri.clone(),
+            route_set,
+            local_contact: request.contact_header().ok().cloned(),
+            local_via: request.via_header()?.clone(),
+        })
+    }
+
+    /// Create a dialog from the UAS side, using the request that was received
+    /// and the 2xx or provisional response sent for it. The response must
+    /// carry a To tag.
+    /// [RFC3261: Section 12.1.1](https://tools.ietf.org/html/rfc3261#section-12.1.1)
+    pub fn from_uas(request: &SipMessage, response: &SipMessage) -> IoResult<Dialog> {
+        let state = dialog_state_for(response)?;
+        let (remote_seq, _) = request.cseq()?;
+        let local_contact = response.contact_header().ok().cloned();
+        let host = match (&local_contact, request) {
+            (Some(contact), _) => contact.uri.host_domain(),
+            (None, SipMessage::Request { uri, .. }) => uri.host_domain(),
+            (None, SipMessage::Response { .. }) => {
+                return Err(SipMessageError::MissingMethod.into())
+            },
+        };
+        let host = host.cloned().ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::InvalidInput,
+                "A tel uri has no host, the response needs a sip Contact header",
+            )
+        })?;
+        let local_via = ViaHeader::new(host, request.via_header()?.transport);
+        Ok(Dialog {
+            id: DialogId {
+                call_id: request.call_id()?.clone(),
+                local_tag: response.to_header_tag()?.clone(),
+                remote_tag: request.from_header_tag()?.clone(),
+            },
+            state,
+            local_seq: 0,
+            remote_seq: Some(remote_seq),
+            local: response.to_header()?.clone(),
+            remote: request.from_header()?.clone(),
+            remote_target: request.contact_header()?.uri.clone(),
+            route_set: routing::dialog_route_set(request, false),
+            local_contact,
+            local_via,
+        })
+    }
+
+    /// Retrieve the id of this dialog.
+    pub fn id(&self) -> &DialogId {
+        &self.id
+    }
+
+    /// Retrieve the current dialog state.
+    pub fn state(&self) -> DialogState {
+        self.state
+    }
+
+    /// The CSeq number of the last request sent within the dialog.
+    pub fn local_seq(&self) -> u32 {
+        self.local_seq
+    }
+
+    /// The CSeq number of the last request received within the dialog.
+    pub fn remote_seq(&self) -> Option<u32> {
+        self.remote_seq
+    }
+
+    /// Retrieve the uri requests within the dialog are sent to.
+    pub fn remote_target(&self) -> &Uri {
+        &self.remote_target
+    }
+
+    /// Retrieve the route set of this dialog.
+    pub fn route_set(&self) -> &[RouteHeader] {
+        &self.route_set
+    }
+
+    /// Mark the dialog as terminated.
+    pub fn terminate(&mut self) {
+        self.state = DialogState::Terminated;
+    }
+
+    /// Process a response to a request sent within this dialog, or to the
+    /// request that created it. A 2xx confirms an early dialog, a non-2xx
+    /// final response terminates it and target refresh responses update
+    /// the remote target. A provisional or 2xx response with another To tag
+    /// comes from a different fork and is rejected, it creates a dialog of
+    /// it's own with `Dialog::from_uac`
+    /// ([RFC3261: Section 13.2.2.4](https://tools.ietf.org/html/rfc3261#section-13.2.2.4)).
+    pub fn on_response(&mut self, response: &SipMessage) -> IoResult<()> {
+        let code = response.status_code()?;
+        let (_, method) = response.cseq()?;
+        if (101..=299).contains(&code) {
+            if let Ok(tag) = response.to_header_tag() {
+                if tag != &self.id.remote_tag {
+                    return Err(IoError::new(
+                        IoErrorKind::InvalidInput,
+                        "Response belongs to another dialog",
+                    ));
+                }
+            }
+        }
+        match code {
+            200..=299 => {
+                if self.state == DialogState::Early {
+                    self.state = DialogState::Confirmed;
+                    self.remote = response.to_header()?.clone();
+                    self.route_set = routing::dialog_route_set(response, true);
+                }
+                if is_target_refresh(method) {
+                    if let Ok(contact) = response.contact_header() {
+                        self.remote_target = contact.uri.clone();
+                    }
+                }
+            },
+            300..=699 if self.state == DialogState::Early => {
+                self.state = DialogState::Terminated;
+            },
+            // A 481 to any request means the remote side lost the dialog.
+            481 => self.state = DialogState::Terminated,
+            // A 408 to a request within the dialog, whether received or
+            // generated by the transaction layer, terminates it too.
+            // [RFC3261: Section 12.2.1.2](https://tools.ietf.org/html/rfc3261#section-12.2.1.2)
+            408 => self.state = DialogState::Terminated,
+            _ => {},
+        }
+        Ok(())
+    }
+
+    /// Process a request received within this dialog. Requests with
+    /// a CSeq lower than the last one received are rejected.
+    /// [RFC3261: Section 12.2.2](https://tools.ietf.org/html/rfc3261#section-12.2.2)
+    pub fn on_request(&mut self, request: &SipMessage) -> IoResult<()> {
+        let (cseq, method) = request.cseq()?;
+        if method != Method::Ack && method != Method::Cancel {
+            if let Some(remote_seq) = self.remote_seq {
+                if cseq < remote_seq {
+                    return Err(IoError::new(
+                        IoErrorKind::InvalidInput,
+                        "Request CSeq is lower than the remote sequence number",
+                    ));
+                }
+            }
+            self.remote_seq = Some(cseq);
+        }
+        match method {
+            Method::Bye => self.state = DialogState::Terminated,
+            method if is_target_refresh(method) => {
+                if let Ok(contact) = request.contact_header() {
+                    self.remote_target = contact.uri.clone();
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    /// Generate a request within this dialog. The local CSeq is incremented and
+    /// the request gets a new Via branch, a Request-URI and Route headers computed
+    /// from the route set and a Contact header for target refresh methods.
+    /// [RFC3261: Section 12.2.1.1](https://tools.ietf.org/html/rfc3261#section-12.2.1.1)
+    pub fn request(
+        &mut self,
+        method: Method,
+        body: Vec<u8>,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        if self.state == DialogState::Terminated {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "Dialog has been terminated",
+            ));
+        }
+        self.local_seq += 1;
+        self.build_request(method, self.local_seq, body, header_cfg)
+    }
+
+    /// Generate the ACK for a 2xx response to an INVITE sent within, or
+    /// creating, this dialog. The ACK uses the CSeq number of the INVITE,
+    /// a new Via branch, the dialog's route set and the remote target as
+    /// it's Request-URI.
+    /// [RFC3261: Section 13.2.2.4](https://tools.ietf.org/html/rfc3261#section-13.2.2.4)
+    pub fn ack(
+        &mut self,
+        response: &SipMessage,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        let code = response.status_code()?;
+        let (cseq, method) = response.cseq()?;
+        if method != Method::Invite || !(200..=299).contains(&code) {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "Only 2xx responses to an INVITE are acknowledged within a dialog",
+            ));
+        }
+        self.on_response(response)?;
+        self.build_request(Method::Ack, cseq, vec![], header_cfg)
+    }
+
+    /// Generate a BYE request ending this dialog.
+    pub fn bye(&mut self, header_cfg: &HeaderWriteConfig) -> IoResult<SipMessage> {
+        self.request(Method::Bye, vec![], header_cfg)
+    }
+
+    /// Generate a re-INVITE offering a new session description.
+    pub fn reinvite(
+        &mut self,
+        sdp: Vec<u8>,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        self.request_with_type(Method::Invite, ContentType::Sdp, sdp, header_cfg)
+    }
+
+    /// Generate an UPDATE request offering a new session description.
+    pub fn update(&mut self, sdp: Vec<u8>, header_cfg: &HeaderWriteConfig) -> IoResult<SipMessage> {
+        self.request_with_type(Method::Update, ContentType::Sdp, sdp, header_cfg)
+    }
+
+    /// Generate an INFO request carrying `body`.
+    pub fn info(
+        &mut self,
+        ty: ContentType,
+        body: Vec<u8>,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        self.request_with_type(Method::Info, ty, body, header_cfg)
+    }
+
+    /// Generate a REFER request asking the remote side to contact `target`.
+    pub fn refer(&mut self, target: Uri, header_cfg: &HeaderWriteConfig) -> IoResult<SipMessage> {
+        let mut req = self.request(Method::Refer, vec![], header_cfg)?;
+        req.headers_mut()
+            .push(Header::Other("Refer-To".into(), format!("<{}>", target)));
+        Ok(req)
+    }
+
+    /// Generate a NOTIFY request for the subscription identified by `event`.
+    pub fn notify(
+        &mut self,
+        event: String,
+        state: SubscriptionState,
+        ty: ContentType,
+        body: Vec<u8>,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        let mut req = self.request_with_type(Method::Notify, ty, body, header_cfg)?;
+        req.headers_mut().push(Header::Event(event));
+        req.headers_mut().push(Header::SubscriptionState(state));
+        Ok(req)
+    }
+
+    fn request_with_type(
+        &mut self,
+        method: Method,
+        ty: ContentType,
+        body: Vec<u8>,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        let with_body = !body.is_empty();
+        let mut req = self.request(method, body, header_cfg)?;
+        if with_body {
+            req.headers_mut().push(Header::ContentType(ty));
+        }
+        Ok(req)
+    }
+
+    fn build_request(
+        &self,
+        method: Method,
+        cseq: u32,
+        body: Vec<u8>,
+        header_cfg: &HeaderWriteConfig,
+    ) -> IoResult<SipMessage> {
+        let (uri, routes) = routing::request_target(&self.remote_target, &self.route_set);
+        let mut req = RequestGenerator::new()
+            .method(method)
+            .uri(uri)
+            .header(self.via_header())
+            .header(Header::MaxForwards(70));
+        for route in routes {
+            req = req.header(Header::Route(route));
+        }
+        req = req
+            .header(Header::To(self.remote.clone()))
+            .header(Header::From(self.local.clone()))
+            .header(Header::CallId(self.id.call_id.clone()))
+            .header(Header::CSeq(cseq, method));
+        if is_target_refresh(method) {
+            if let Some(contact) = &self.local_contact {
+                req = req.header(Header::Contact(contact.clone()));
+            }
+        }
+        header_cfg.write_headers(req.headers_ref_mut());
+        req.header(Header::ContentLength(body.len() as u32))
+            .body(body)
+            .build()
+    }
+
+    /// Generate the Via header for a new request within the dialog.
+    fn via_header(&self) -> Header {
+        let mut via = self.local_via.clone();
+        via.branch = Some(generate_branch());
+        via.received = None;
+        if via.rport.is_some() {
+            via.rport = Some(None);
+        }
+        Header::Via(via)
+    }
+}
+
+/// Methods that can update the remote target of a dialog.
+fn is_target_refresh(method: Method) -> bool {
+    matches!(
+        method,
+        Method::Invite | Method::Update | Method::Subscribe | Method::Notify | Method::Refer
+    )
+}
+
+fn dialog_state_for(response: &SipMessage) -> IoResult<DialogState> {
+    match response.status_code()? {
+        101..=199 => Ok(DialogState::Early),
+        200..=299 => Ok(DialogState::Confirmed),
+        _ => Err(IoError::new(
+            IoErrorKind::InvalidInput,
+            "Only 2xx and 101-199 responses can create a dialog",
+        )),
+    }
+}
@@ -1,12 +1,22 @@
 use sha::{
     sha256::Sha256,
-    sha512::Sha512,
+    sha512256::Sha512256,
     utils::{Digest, DigestExt},
 };
 
-use std::{collections::HashMap, fmt, io::Result as IoResult};
+use indexmap::IndexMap;
 
-use crate::Uri;
+use std::{
+    error::Error,
+    fmt,
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    str::FromStr,
+};
+
+use crate::{
+    parse::{self, quote},
+    Method, Uri,
+};
 
 /// The SIP Authentication schema.
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -22,189 +32,368 @@ impl fmt::Display for AuthSchema {
     }
 }
 
+/// Errors produced when answering an authentication challenge.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AuthError {
+    /// The challenge uses an algorithm this library doesn't implement.
+    UnsupportedAlgorithm(String),
+    /// None of the qop values offered by the challenge are supported.
+    UnsupportedQop(String),
+    /// A required parameter, named by the value, is missing from the challenge.
+    MissingParameter(&'static str),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthError::UnsupportedAlgorithm(alg) => {
+                write!(f, "unsupported auth algorithm: {}", alg)
+            },
+            AuthError::UnsupportedQop(qop) => write!(f, "unsupported auth qop: {}", qop),
+            AuthError::MissingParameter(name) => {
+                write!(f, "auth header does not contain a {} parameter", name)
+            },
+        }
+    }
+}
+
+impl Error for AuthError {}
+
+impl From<AuthError> for IoError {
+    fn from(err: AuthError) -> IoError {
+        IoError::new(IoErrorKind::InvalidData, err)
+    }
+}
+
+/// The hash algorithms of digest authentication,
+/// [RFC7616: Section 3.2](https://tools.ietf.org/html/rfc7616#section-3.2).
+/// The `-sess` variants include the nonce and client nonce in HA1.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DigestAlgorithm {
+    #[default]
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+    Sha512_256,
+    Sha512_256Sess,
+}
+
+impl DigestAlgorithm {
+    /// Determine if this is one of the `-sess` algorithms.
+    pub fn is_session(self) -> bool {
+        matches!(
+            self,
+            DigestAlgorithm::Md5Sess
+                | DigestAlgorithm::Sha256Sess
+                | DigestAlgorithm::Sha512_256Sess
+        )
+    }
+
+    /// Hash `data`, returning the lowercase hex encoded digest.
+    pub fn hash<D: AsRef<[u8]>>(self, data: D) -> String {
+        match self {
+            DigestAlgorithm::Md5 | DigestAlgorithm::Md5Sess => {
+                format!("{:x}", md5::compute(data))
+            },
+            DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => {
+                Sha256::default().digest(data.as_ref()).to_hex()
+            },
+            DigestAlgorithm::Sha512_256 | DigestAlgorithm::Sha512_256Sess => {
+                Sha512256::default().digest(data.as_ref()).to_hex()
+            },
+        }
+    }
+}
+
+impl fmt::Display for DigestAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DigestAlgorithm::Md5 => write!(f, "MD5"),
+            DigestAlgorithm::Md5Sess => write!(f, "MD5-sess"),
+            DigestAlgorithm::Sha256 => write!(f, "SHA-256"),
+            DigestAlgorithm::Sha256Sess => write!(f, "SHA-256-sess"),
+            DigestAlgorithm::Sha512_256 => write!(f, "SHA-512-256"),
+            DigestAlgorithm::Sha512_256Sess => write!(f, "SHA-512-256-sess"),
+        }
+    }
+}
+
+impl FromStr for DigestAlgorithm {
+    type Err = AuthError;
+
+    fn from_str(s: &str) -> Result<DigestAlgorithm, AuthError> {
+        match s.to_ascii_lowercase().as_ref() {
+            "md5" => Ok(DigestAlgorithm::Md5),
+            "md5-sess" => Ok(DigestAlgorithm::Md5Sess),
+            "sha-256" => Ok(DigestAlgorithm::Sha256),
+            "sha-256-sess" => Ok(DigestAlgorithm::Sha256Sess),
+            "sha-512-256" => Ok(DigestAlgorithm::Sha512_256),
+            "sha-512-256-sess" => Ok(DigestAlgorithm::Sha512_256Sess),
+            _ => Err(AuthError::UnsupportedAlgorithm(s.to_string())),
+        }
+    }
+}
+
+/// The parameters of an AuthHeader, kept in the order they were
+/// received or inserted.
+pub type AuthParams = IndexMap<String, String>;
+
 /// AuthHeader used for headers such as Authorization
 /// or WWWAuthenticate.
 #[derive(Debug, PartialEq, Clone)]
-pub struct AuthHeader(pub AuthSchema, pub HashMap<String, String>);
+pub struct AuthHeader(pub AuthSchema, pub AuthParams);
 
-impl fmt::Display for AuthHeader {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl AuthHeader {
+    /// Write this header's parameters following the quoting rules of
+    /// [RFC7616: Section 3.3](https://tools.ietf.org/html/rfc7616#section-3.3)
+    /// and [Section 3.4](https://tools.ietf.org/html/rfc7616#section-3.4).
+    /// The qop of a `challenge` is a quoted list of options while
+    /// credentials carry the chosen qop as a token.
+    pub(crate) fn write(&self, f: &mut fmt::Formatter, challenge: bool) -> fmt::Result {
         write!(f, "{}", self.0)?;
         for (index, (key, value)) in self.1.iter().enumerate() {
-            if index == 0 && (key == &"qop".to_string() || key == &"nc".to_string()) {
-                write!(f, " {}={}", key, value)?;
-            } else if index == 0 {
-                write!(f, " {}=\"{}\"", key, value)?;
-            } else if (key == &"qop".to_string()) || (key == &"nc".to_string()) {
-                write!(f, ", {}={}", key, value)?;
+            let separator = if index == 0 { " " } else { ", " };
+            if is_token_param(key, challenge) && is_token(value) {
+                write!(f, "{}{}={}", separator, key, value)?;
             } else {
-                write!(f, ", {}=\"{}\"", key, value)?;
+                write!(f, "{}{}={}", separator, key, quote(value))?;
             }
         }
         Ok(())
     }
 }
 
+/// Writes the header as credentials, `Header` writes WWW-Authenticate
+/// and Proxy-Authenticate headers as challenges.
+impl fmt::Display for AuthHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, false)
+    }
+}
+
+/// Determine if the value of parameter `key` is sent as a token rather
+/// than a quoted-string. Unknown parameters are always quoted.
+fn is_token_param(key: &str, challenge: bool) -> bool {
+    match key.to_ascii_lowercase().as_str() {
+        "algorithm" | "stale" | "nc" | "charset" | "userhash" => true,
+        "qop" => !challenge,
+        _ => false,
+    }
+}
+
+fn is_token(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(parse::is_token)
+}
+
 /// Context struct used when calculating the Auth Headers.
 pub struct AuthContext<'a> {
     pub user: &'a str,
     pub pass: &'a str,
     pub nc: u32,
+    /// The Request-URI of the request being authorized.
     pub uri: &'a Uri,
+    /// The method of the request being authorized.
+    pub method: Method,
+    /// The body of the request, hashed when using `qop=auth-int`.
+    pub body: Option<&'a [u8]>,
+    /// The client nonce to use, a random one is generated when `None`.
+    pub cnonce: Option<&'a str>,
 }
 
-impl AuthHeader {
-    /// Perform the authenticate action.
-    pub fn authenticate<'a>(&self, ctx: AuthContext<'a>) -> IoResult<AuthHeader> {
-        match self.0 {
-            AuthSchema::Digest => self.handle_digest_auth(ctx),
+impl<'a> AuthContext<'a> {
+    /// Create a context for authorizing a `method` request sent to `uri`.
+    pub fn new(user: &'a str, pass: &'a str, method: Method, uri: &'a Uri) -> AuthContext<'a> {
+        AuthContext {
+            user,
+            pass,
+            nc: 1,
+            uri,
+            method,
+            body: None,
+            cnonce: None,
         }
     }
 
-    /// Perform the Digest auth method.
-    fn handle_digest_auth<'a>(&self, ctx: AuthContext<'a>) -> IoResult<AuthHeader> {
-        let qop = self
-            .1
-            .get("qop")
-            .expect("Auth header does not contain a qop field");
-        match qop.as_ref() {
-            "auth" => self.handle_digest_qop_auth(ctx),
-            qop => panic!("unknown auth qop: {}", qop),
-        }
+    /// Set the nonce count.
+    pub fn nc(mut self, nc: u32) -> AuthContext<'a> {
+        self.nc = nc;
+        self
     }
 
-    /// Decides witch authorization algorythm to use.
-    fn handle_digest_qop_auth<'a>(&self, ctx: AuthContext<'a>) -> IoResult<AuthHeader> {
-        let alg = self
-            .1
-            .get("algorithm")
-            .map(|item| item.to_string())
-            .unwrap_or_else(|| "md5".to_string());
-        match alg.as_ref() {
-            "md5" | "MD5" => self.handle_md5_digest_auth(ctx),
-            "sha-256" | "SHA-256" => self.handle_sha256_digest_auth(ctx),
-            "sha-512-256" | "SHA-512-256" => self.handle_sha512_digest_auth(ctx),
-            alg => panic!("Unknown Auth alogirithm: {}", alg),
+    /// Set the body of the request.
+    pub fn body(mut self, body: &'a [u8]) -> AuthContext<'a> {
+        self.body = Some(body);
+        self
+    }
+
+    /// Use a fixed client nonce instead of a random one.
+    pub fn cnonce(mut self, cnonce: &'a str) -> AuthContext<'a> {
+        self.cnonce = Some(cnonce);
+        self
+    }
+}
+
+/// The values hashed into the `response` of a digest authorization,
+/// [RFC2617: Section 3.2.2](https://tools.ietf.org/html/rfc2617#section-3.2.2).
+/// Without a `qop` the response is calculated as described in
+/// [RFC2069](https://tools.ietf.org/html/rfc2069#section-2.1.2).
+#[derive(Debug, PartialEq, Clone)]
+pub struct DigestInput<'a> {
+    pub algorithm: DigestAlgorithm,
+    pub username: &'a str,
+    pub realm: &'a str,
+    pub password: &'a str,
+    pub nonce: &'a str,
+    pub method: &'a str,
+    pub uri: &'a str,
+    /// Either `auth` or `auth-int`.
+    pub qop: Option<&'a str>,
+    pub nc: u32,
+    pub cnonce: &'a str,
+    /// The entity body, only used with `qop=auth-int`.
+    pub body: &'a [u8],
+}
+
+impl<'a> DigestInput<'a> {
+    /// Calculate the hex encoded `response` value.
+    pub fn response(&self) -> String {
+        self.response_with_ha1(&self.ha1())
+    }
+
+    /// Hash of the username, realm and password. Servers can store
+    /// this value instead of the password, the `password` field
+    /// is not used when calculating the response from it.
+    pub fn ha1(&self) -> String {
+        self.algorithm.hash(format!(
+            "{}:{}:{}",
+            self.username, self.realm, self.password
+        ))
+    }
+
+    /// Calculate the hex encoded `response` value from a known `ha1`.
+    pub fn response_with_ha1(&self, ha1: &str) -> String {
+        let alg = self.algorithm;
+        let mut ha1 = ha1.to_string();
+        if alg.is_session() {
+            ha1 = alg.hash(format!("{}:{}:{}", ha1, self.nonce, self.cnonce));
+        }
+        let ha2 = match self.qop {
+            Some("auth-int") => {
+                let body = alg.hash(self.body);
+                alg.hash(format!("{}:{}:{}", self.method, self.uri, body))
+            },
+            _ => alg.hash(format!("{}:{}", self.method, self.uri)),
+        };
+        match self.qop {
+            Some(qop) => alg.hash(format!(
+                "{}:{}:{:08x}:{}:{}:{}",
+                ha1, self.nonce, self.nc, self.cnonce, qop, ha2
+            )),
+            None => alg.hash(format!("{}:{}:{}", ha1, self.nonce, ha2)),
         }
     }
 
-    /// Handle the MD5 digest auth method.
-    fn handle_md5_digest_auth<'a>(&self, ctx: AuthContext<'a>) -> IoResult<AuthHeader> {
-        let realm = self
-            .1
-            .get("realm")
-            .expect("Auth header does not contain a realm");
-        let nonce = self
-            .1
-            .get("nonce")
-            .expect("Auth header does not contain a nonce");
-        let mut map: HashMap<String, String> = HashMap::new();
-        let cnonce = self.generate_cnonce();
-        map.insert("username".into(), ctx.user.to_string());
-        map.insert("nonce".into(), nonce.to_string());
-        map.insert("realm".into(), realm.clone());
-        map.insert("uri".into(), format!("{}", ctx.uri));
-        map.insert("qop".into(), self.1.get("qop").unwrap().clone());
-        map.insert("algorithm".into(), "MD5".into());
-        map.insert("cnonce".into(), format!("{:x}", cnonce));
-        map.insert("nc".into(), format!("{:08}", ctx.nc));
-        let ha1 = md5::compute(&format!("{}:{}:{}", ctx.user, realm, ctx.pass));
-        let ha2 = md5::compute(format!("REGISTER:{}", ctx.uri));
-        let digest = format!(
-            "{:x}:{}:{:08}:{:x}:auth:{:x}",
-            ha1, nonce, ctx.nc, cnonce, ha2
-        );
-        let pass = md5::compute(digest);
-        map.insert("response".into(), format!("{:x}", pass));
-        Ok(AuthHeader(AuthSchema::Digest, map))
+    /// The hashed username sent when the challenge contains `userhash=true`,
+    /// [RFC7616: Section 3.4.4](https://tools.ietf.org/html/rfc7616#section-3.4.4).
+    pub fn username_hash(&self) -> String {
+        self.algorithm
+            .hash(format!("{}:{}", self.username, self.realm))
     }
+}
 
-    /// Handle sha256 Digest auth method.
-    fn handle_sha256_digest_auth<'a>(&self, ctx: AuthContext<'a>) -> IoResult<AuthHeader> {
-        let realm = self
-            .1
-            .get("realm")
-            .expect("Auth header does not contain a realm");
-        let nonce = self
-            .1
-            .get("nonce")
-            .expect("Auth header does not contain a nonce");
-        let opaque = self
-            .1
-            .get("opaque")
-            .expect("Auth header does not contain a opaque");
-        let mut map: HashMap<String, String> = HashMap::new();
-        let cnonce = self.generate_cnonce();
-        map.insert("username".into(), ctx.user.to_string());
-        map.insert("nonce".into(), nonce.to_string());
-        map.insert("realm".into(), realm.clone());
-        map.insert("uri".into(), format!("{}", ctx.uri));
-        map.insert("qop".into(), self.1.get("qop").unwrap().clone());
-        map.insert("algorithm".into(), "SHA-512-256".into());
-        map.insert("cnonce".into(), format!("{:x}", cnonce));
-        map.insert("nc".into(), format!("{:08}", ctx.nc));
-        map.insert("opaque".into(), opaque.into());
-        let mut ha1_hasher = Sha256::default();
-        let ha1 = ha1_hasher.digest(format!("{}:{}:{}", ctx.user, realm, ctx.pass).as_ref());
-        let mut ha2_hasher = Sha256::default();
-        let ha2 = ha2_hasher.digest(format!("REGISTER:{}", ctx.uri).as_ref());
-        let digest = format!(
-            "{}:{}:{:08}:{:x}:auth:{}",
-            ha1.to_hex(),
-            nonce,
-            ctx.nc,
-            cnonce,
-            ha2.to_hex()
-        );
-        let pass = md5::compute(digest);
-        map.insert("response".into(), format!("{:x}", pass));
-        Ok(AuthHeader(AuthSchema::Digest, map))
+impl AuthHeader {
+    /// Perform the authenticate action.
+    pub fn authenticate<'a>(&self, ctx: AuthContext<'a>) -> Result<AuthHeader, AuthError> {
+        match self.0 {
+            AuthSchema::Digest => self.handle_digest_auth(ctx),
+        }
     }
 
-    /// Handle sha512 auth method.
-    fn handle_sha512_digest_auth<'a>(&self, ctx: AuthContext<'a>) -> IoResult<AuthHeader> {
-        let realm = self
+    /// Perform the Digest auth method. The strongest qop offered by the
+    /// challenge is used, when no qop is offered the legacy RFC 2069
+    /// digest is used.
+    fn handle_digest_auth<'a>(&self, ctx: AuthContext<'a>) -> Result<AuthHeader, AuthError> {
+        let realm = self.param("realm")?;
+        let nonce = self.param("nonce")?;
+        let algorithm = match self.1.get("algorithm") {
+            Some(alg) => alg.parse::<DigestAlgorithm>()?,
+            None => DigestAlgorithm::default(),
+        };
+        let userhash = self
             .1
-            .get("realm")
-            .expect("Auth header does not contain a realm");
-        let nonce = self
-            .1
-            .get("nonce")
-            .expect("Auth header does not contain a nonce");
-        let opaque = self
-            .1
-            .get("opaque")
-            .expect("Auth header does not contain a opaque");
-        let mut map: HashMap<String, String> = HashMap::new();
-        let cnonce = self.generate_cnonce();
-        map.insert("username".into(), ctx.user.to_string());
-        map.insert("nonce".into(), nonce.to_string());
-        map.insert("realm".into(), realm.clone());
-        map.insert("uri".into(), format!("{}", ctx.uri));
-        map.insert("qop".into(), self.1.get("qop").unwrap().clone());
-        map.insert("algorithm".into(), "SHA-512".into());
-        map.insert("cnonce".into(), format!("{:x}", cnonce));
-        map.insert("nc".into(), format!("{:08}", ctx.nc));
-        map.insert("opaque".into(), opaque.into());
-        let mut ha1_hasher = Sha512::default();
-        let ha1 = ha1_hasher.digest(format!("{}:{}:{}", ctx.user, realm, ctx.pass).as_ref());
-        let mut ha2_hasher = Sha512::default();
-        let ha2 = ha2_hasher.digest(format!("REGISTER:{}", ctx.uri).as_ref());
-        let digest = format!(
-            "{}:{}:{:08}:{:x}:auth:{}",
-            ha1.to_hex(),
+            .get("userhash")
+            .map(|value| value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let qop = match self.1.get("qop") {
+            Some(qop) => Some(strongest_qop(qop)?),
+            None => None,
+        };
+        let cnonce = match ctx.cnonce {
+            Some(cnonce) => cnonce.to_string(),
+            None => self.generate_cnonce(),
+        };
+        let uri = format!("{}", ctx.uri);
+        let method = format!("{}", ctx.method);
+        let input = DigestInput {
+            algorithm,
+            username: ctx.user,
+            realm,
+            password: ctx.pass,
             nonce,
-            ctx.nc,
-            cnonce,
-            ha2.to_hex()
-        );
-        let pass = md5::compute(digest);
-        map.insert("response".into(), format!("{:x}", pass));
+            method: &method,
+            uri: &uri,
+            qop,
+            nc: ctx.nc,
+            cnonce: &cnonce,
+            body: ctx.body.unwrap_or_default(),
+        };
+        let response = input.response();
+
+        let mut map = AuthParams::new();
+        if userhash {
+            map.insert("username".into(), input.username_hash());
+            map.insert("userhash".into(), "true".into());
+        } else {
+            map.insert("username".into(), ctx.user.to_string());
+        }
+        map.insert("realm".into(), realm.clone());
+        map.insert("nonce".into(), nonce.clone());
+        map.insert("uri".into(), uri);
+        map.insert("response".into(), response);
+        map.insert("algorithm".into(), algorithm.to_string());
+        if let Some(opaque) = self.1.get("opaque") {
+            map.insert("opaque".into(), opaque.clone());
+        }
+        if let Some(qop) = qop {
+            map.insert("qop".into(), qop.into());
+            map.insert("nc".into(), format!("{:08x}", ctx.nc));
+            map.insert("cnonce".into(), cnonce);
+        }
         Ok(AuthHeader(AuthSchema::Digest, map))
     }
 
+    /// Retrieve a parameter the challenge must contain.
+    fn param(&self, name: &'static str) -> Result<&String, AuthError> {
+        self.1.get(name).ok_or(AuthError::MissingParameter(name))
+    }
+
     /// Generate the nonce used during authorization.
-    fn generate_cnonce(&self) -> md5::Digest {
-        md5::compute(rand::random::<[u8; 16]>())
+    fn generate_cnonce(&self) -> String {
+        format!("{:x}", md5::compute(rand::random::<[u8; 16]>()))
     }
 }
+
+/// Pick the strongest supported qop from a comma separated list.
+/// `auth-int` also protects the message body, so it is preferred over `auth`.
+fn strongest_qop(qop: &str) -> Result<&'static str, AuthError> {
+    let options: Vec<String> = qop
+        .split(',')
+        .map(|item| item.trim().to_ascii_lowercase())
+        .collect();
+    ["auth-int", "auth"]
+        .iter()
+        .find(|supported| options.iter().any(|item| item == *supported))
+        .copied()
+        .ok_or_else(|| AuthError::UnsupportedQop(qop.to_string()))
+}
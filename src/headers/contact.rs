@@ -1,6 +1,14 @@
-use nom::{error::ParseError, IResult};
+use nom::{
+    error::{ErrorKind, ParseError},
+    IResult,
+};
 
-use crate::{headers::parse::parse_generic_param_with_possibly_quoted_value, Uri};
+use crate::{
+    headers::{named::display_name, parse::parse_generic_param_with_possibly_quoted_value},
+    parse::{parse_quoted_string, quote},
+    uri::parse_uri,
+    Domain, Uri,
+};
 
 use std::{
     collections::{hash_map::Entry, HashMap},
@@ -24,29 +32,28 @@ pub enum GenValue {
 
 impl From<&str> for GenValue {
     fn from(string: &str) -> Self {
-        if string.starts_with("\"") && string.ends_with("\"") {
-            Self::QuotedString(string[1..=(string.len() - 2)].into())
-        } else {
-            Self::Token(string.into())
+        match parse_quoted_string::<(&[u8], ErrorKind)>(string.as_bytes()) {
+            Ok((b"", unquoted)) => Self::QuotedString(unquoted),
+            _ => Self::Token(string.into()),
         }
     }
 }
 
 impl From<String> for GenValue {
-    fn from(mut string: String) -> Self {
-        if string.starts_with("\"") && string.ends_with("\"") {
-            Self::QuotedString(string.drain(1..=(string.len() - 2)).collect())
+    fn from(string: String) -> Self {
+        if string.starts_with('"') {
+            Self::from(string.as_str())
         } else {
             Self::Token(string)
         }
     }
 }
 
-impl Into<String> for GenValue {
-    fn into(self) -> String {
-        match self {
-            Self::Token(token) => token,
-            Self::QuotedString(quoted_string) => format!("\"{}\"", quoted_string),
+impl From<GenValue> for String {
+    fn from(value: GenValue) -> String {
+        match value {
+            GenValue::Token(token) => token,
+            GenValue::QuotedString(quoted_string) => quote(&quoted_string),
         }
     }
 }
@@ -69,12 +76,77 @@ impl ContactHeader {
         }
     }
 
+    /// Create the `*` contact used to remove every binding of an address
+    /// of record ([RFC3261: Section 10.2.2](https://tools.ietf.org/html/rfc3261#section-10.2.2)).
+    pub fn wildcard() -> ContactHeader {
+        ContactHeader::new(Uri::new_schemaless(Domain::Domain("*".into(), None)))
+    }
+
+    /// Determine if this is the `*` contact.
+    pub fn is_wildcard(&self) -> bool {
+        self.uri.schema.is_none() && self.uri.host == Domain::Domain("*".into(), None)
+    }
+
     /// Sets `display_name` of this header
     pub fn name<S: Into<String>>(mut self, name: S) -> Self {
         self.display_name = Some(name.into());
         self
     }
 
+    /// Sets the `+sip.instance` parameter identifying this device, `urn` is
+    /// usually a `urn:uuid:` ([RFC5626: Section 4.1](https://tools.ietf.org/html/rfc5626#section-4.1)).
+    pub fn instance<S: Into<String>>(self, urn: S) -> Self {
+        self.param(
+            "+sip.instance",
+            Some(GenValue::QuotedString(format!("<{}>", urn.into()))),
+        )
+    }
+
+    /// Sets the `reg-id` parameter identifying the flow this contact is
+    /// registered over ([RFC5626: Section 4.2](https://tools.ietf.org/html/rfc5626#section-4.2)).
+    pub fn reg_id(self, id: u32) -> Self {
+        self.param("reg-id", Some(id.to_string()))
+    }
+
+    /// The instance id of the `+sip.instance` parameter, without it's
+    /// angle brackets.
+    pub fn sip_instance(&self) -> Option<String> {
+        let value = self.get_param("+sip.instance")?;
+        Some(value.trim_start_matches('<').trim_end_matches('>').into())
+    }
+
+    /// The value of the `reg-id` parameter.
+    pub fn registration_id(&self) -> Option<u32> {
+        self.get_param("reg-id")?.parse().ok()
+    }
+
+    /// The public GRUU assigned by the registrar
+    /// ([RFC5627: Section 5.2](https://tools.ietf.org/html/rfc5627#section-5.2)).
+    pub fn pub_gruu(&self) -> Option<Uri> {
+        self.get_uri_param("pub-gruu")
+    }
+
+    /// The temporary GRUU assigned by the registrar
+    /// ([RFC5627: Section 5.2](https://tools.ietf.org/html/rfc5627#section-5.2)).
+    pub fn temp_gruu(&self) -> Option<Uri> {
+        self.get_uri_param("temp-gruu")
+    }
+
+    fn get_param(&self, name: &str) -> Option<&str> {
+        match self.parameters.get(name)? {
+            Some(GenValue::Token(value)) | Some(GenValue::QuotedString(value)) => Some(value),
+            None => None,
+        }
+    }
+
+    fn get_uri_param(&self, name: &str) -> Option<Uri> {
+        let value = self.get_param(name)?;
+        match parse_uri::<(&[u8], ErrorKind)>(value.as_bytes()) {
+            Ok((b"", uri)) => Some(uri),
+            _ => None,
+        }
+    }
+
     /// Adds a parameter with a given name and a given value to `params`.
     ///
     /// If there is already a parameter with a given name, its value is changed because [RFC3261: Page 31, Header Field Format](https://tools.ietf.org/html/rfc3261#page-31) defines that "any given parameter-name MUST NOT appear more than once"
@@ -111,15 +183,11 @@ impl ContactHeader {
 impl fmt::Display for ContactHeader {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(name) = &self.display_name {
-            if name.contains(' ') {
-                write!(f, "\"{}\" <{}>", name, self.uri)?;
-            } else if name.is_empty() {
-                write!(f, "\"\" <{}>", self.uri)?;
-            } else {
-                write!(f, "{} <{}>", name, self.uri)?;
-            }
-        } else {
+            write!(f, "{} <{}>", display_name(name), self.uri)?;
+        } else if self.uri.parameters.is_empty() && self.uri.headers.is_empty() {
             write!(f, "{}", self.uri)?;
+        } else {
+            write!(f, "<{}>", self.uri)?;
         }
         for (key, value) in self.parameters.iter() {
             write!(f, ";{}", key)?;
@@ -135,7 +203,7 @@ impl fmt::Display for GenValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             GenValue::Token(string) => write!(f, "{}", string),
-            GenValue::QuotedString(string) => write!(f, "\"{}\"", string),
+            GenValue::QuotedString(string) => write!(f, "{}", quote(string)),
         }
     }
 }
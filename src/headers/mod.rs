@@ -4,16 +4,20 @@ mod content;
 mod language;
 mod named;
 pub mod parse;
+pub mod route;
 pub mod subscription_state;
 pub mod via;
 mod write;
 pub use self::{
-    auth::{AuthContext, AuthHeader, AuthSchema},
+    auth::{
+        AuthContext, AuthError, AuthHeader, AuthParams, AuthSchema, DigestAlgorithm, DigestInput,
+    },
     contact::{ContactHeader, GenValue},
     content::ContentType,
     language::Language,
     named::NamedHeader,
     parse::parse_header,
+    route::RouteHeader,
     subscription_state::SubscriptionState,
 };
 
@@ -186,15 +190,15 @@ pub enum Header {
     MinExpires(u32),
     MimeVersion(f32),
     Organization(String),
-    ProxyAuthenticate(String),
-    ProxyAuthorization(String),
+    ProxyAuthenticate(auth::AuthHeader),
+    ProxyAuthorization(auth::AuthHeader),
     ProxyRequire(String),
     Require(String),
     RetryAfter(String),
-    Route(String),
+    Route(RouteHeader),
     Subject(String),
     SubscriptionState(SubscriptionState),
-    RecordRoute(String),
+    RecordRoute(RouteHeader),
     Server(String),
     Supported(Vec<String>),
     Timestamp(u32),
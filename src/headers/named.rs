@@ -7,7 +7,12 @@ use nom::{
     IResult,
 };
 
-use crate::{headers::parse::parse_generic_param, parse::*, uri::parse_uri, Uri};
+use crate::{
+    headers::parse::parse_generic_param,
+    parse::*,
+    uri::{parse_addr_spec, parse_uri},
+    Uri,
+};
 
 use std::{
     collections::{hash_map::Entry, HashMap},
@@ -74,15 +79,13 @@ impl NamedHeader {
 impl fmt::Display for NamedHeader {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(name) = &self.display_name {
-            if name.contains(' ') {
-                write!(f, "\"{}\" <{}>", name, self.uri)?;
-            } else if name.is_empty() {
-                write!(f, "\"\" <{}>", self.uri)?;
-            } else {
-                write!(f, "{} <{}>", name, self.uri)?;
-            }
-        } else {
+            write!(f, "{} <{}>", display_name(name), self.uri)?;
+        } else if self.uri.parameters.is_empty() && self.uri.headers.is_empty() {
+            // Parameters or headers of an unbracketed uri would be read
+            // back as the header's own.
             write!(f, "{}", self.uri)?;
+        } else {
+            write!(f, "<{}>", self.uri)?;
         }
         for (key, value) in self.parameters.iter() {
             write!(f, ";{}", key)?;
@@ -94,31 +97,43 @@ impl fmt::Display for NamedHeader {
     }
 }
 
+/// Format a display-name, quoting it unless it is a single token
+/// ([RFC3261: Section 25.1, "display-name"](https://tools.ietf.org/html/rfc3261#section-25.1)).
+pub fn display_name(name: &str) -> String {
+    if !name.is_empty() && name.bytes().all(is_token) {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
 /// Parse the name part of the NamedHeader.
 pub fn parse_name<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], String, E> {
-    Ok(alt::<_, _, E, _>((
-        parse_quoted_string::<E>,
-        parse_unquoted_string::<E>,
-    ))(input)?)
+    alt::<_, _, E, _>((parse_quoted_string::<E>, parse_unquoted_string::<E>))(input)
 }
 
-/// Parse a stream of text that is not quoted. This will stop
+/// Parse a token that is not quoted. This will stop
 /// at the first ' ' char the input contains.
 pub fn parse_unquoted_string<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], String, E> {
-    let (input, string_data) = map_res(take_while(is_alphabetic), slice_to_string::<E>)(input)?;
+    let (input, string_data) = map_res(take_while(is_token), slice_to_string::<E>)(input)?;
     let (input, _) = char(' ')(input)?;
     Ok((input, string_data))
 }
 
-/// Parse a single NamedHeader value.
+/// Parse a single NamedHeader value. A uri without angle brackets is an
+/// addr-spec, the parameters following it belong to the header.
 pub fn parse_named_field_value<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], (Option<String>, Uri), E> {
     let (input, name) = opt(parse_name)(input)?;
     let (input, _) = opt(take_while(is_space))(input)?;
-    let (input, _) = opt(char('<'))(input)?;
+    let (input, bracket) = opt(char('<'))(input)?;
+    if bracket.is_none() {
+        let (input, value) = parse_addr_spec(input)?;
+        return Ok((input, (name, value)));
+    }
     let (input, value) = parse_uri(input)?;
     let (input, _) = opt(char('>'))(input)?;
     Ok((input, (name, value)))
@@ -1,23 +1,18 @@
 use super::{
-    contact::*, content::*, language::*, named::*,
+    contact::*, content::*, language::*, named::*, route::parse_route_value,
     subscription_state::parse_subscription_state_header, *,
 };
-use crate::{
-    core::{parse_method, parse_transport, parse_version},
-    parse::*,
-    uri::parse_uri,
-};
+use crate::{core::parse_method, parse::*};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, tag_no_case, take_until, take_while},
+    bytes::complete::{tag, tag_no_case, take_until, take_while, take_while1},
     character::{complete::char, *},
     combinator::{map, map_res, opt},
-    error::ParseError,
+    error::{ErrorKind, ParseError},
     multi::separated_list0,
     sequence::pair,
     IResult,
 };
-use std::collections::HashMap;
 
 pub fn parse_header<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Header> {
     let (input, _) = opt(take_while(is_space))(input)?;
@@ -25,6 +20,174 @@ pub fn parse_header<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a
     Ok((input, header))
 }
 
+/// Parse one logical header line, which may be folded over several lines
+/// and use a compact header name, into the headers it contains. Via, Contact,
+/// Route and Record-Route values separated by commas become separate headers.
+/// A value that can't be parsed is kept as a `Header::Other` with it's raw text.
+/// ([RFC3261: Section 7.3](https://tools.ietf.org/html/rfc3261#section-7.3))
+pub fn parse_header_line<'a, E: ParseError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], Vec<Header>, E> {
+    let error = || nom::Err::Error(E::from_error_kind(input, ErrorKind::Tag));
+    let end = find_line_end(input).ok_or_else(error)?;
+    let (line, remains) = (&input[..end], &input[end + 2..]);
+    let colon = line.iter().position(|c| *c == b':').ok_or_else(error)?;
+    let name = trim_lws(&line[..colon]);
+    if name.is_empty() || !name.iter().all(|c| is_token(*c)) {
+        return Err(error());
+    }
+    let name = expand_compact_name(name);
+    let value = unfold(trim_lws(&line[colon + 1..]));
+    let values = if is_comma_separated(name) {
+        split_header_values(&value)
+    } else {
+        vec![&value[..]]
+    };
+    let mut headers = vec![];
+    for value in values {
+        let mut data = name.to_vec();
+        data.extend_from_slice(b": ");
+        data.extend_from_slice(value);
+        data.extend_from_slice(b"\r\n");
+        let mut data = &data[..];
+        let mut parsed = false;
+        while !data.is_empty() && data != b"\r\n" {
+            match _parse_header(data) {
+                Ok((rest, header)) => {
+                    headers.push(header);
+                    data = rest;
+                    parsed = true;
+                },
+                Err(_) => {
+                    // Whatever follows a parsed header is part of the same value.
+                    let raw = if parsed {
+                        trim_lws(data.strip_suffix(b"\r\n").unwrap_or(data))
+                    } else {
+                        trim_lws(value)
+                    };
+                    headers.push(Header::Other(
+                        String::from_utf8_lossy(name).into(),
+                        String::from_utf8_lossy(raw).into(),
+                    ));
+                    break;
+                },
+            }
+        }
+    }
+    Ok((remains, headers))
+}
+
+/// Find the CRLF ending a header line, skipping over folded line breaks.
+fn find_line_end(input: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    loop {
+        let index = start + input[start..].windows(2).position(|w| w == b"\r\n")?;
+        match input.get(index + 2) {
+            Some(b' ') | Some(b'\t') => start = index + 2,
+            _ => return Some(index),
+        }
+    }
+}
+
+fn trim_lws(mut input: &[u8]) -> &[u8] {
+    while let Some(c) = input.first() {
+        if !is_space(*c) {
+            break;
+        }
+        input = &input[1..];
+    }
+    while let Some(c) = input.last() {
+        if !is_space(*c) {
+            break;
+        }
+        input = &input[..input.len() - 1];
+    }
+    input
+}
+
+/// Replace every CRLF followed by whitespace with a single space.
+fn unfold(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut index = 0;
+    while index < input.len() {
+        if input[index..].starts_with(b"\r\n") {
+            while out.last().map(|c| is_space(*c)).unwrap_or(false) {
+                out.pop();
+            }
+            index += 2;
+            while index < input.len() && is_space(input[index]) {
+                index += 1;
+            }
+            out.push(b' ');
+        } else {
+            out.push(input[index]);
+            index += 1;
+        }
+    }
+    out
+}
+
+/// Map a compact header name to it's long form.
+/// ([RFC3261: Section 7.3.3](https://tools.ietf.org/html/rfc3261#section-7.3.3))
+pub fn expand_compact_name(name: &[u8]) -> &[u8] {
+    if name.len() != 1 {
+        return name;
+    }
+    match name[0].to_ascii_lowercase() {
+        b'v' => b"Via",
+        b'f' => b"From",
+        b't' => b"To",
+        b'i' => b"Call-ID",
+        b'm' => b"Contact",
+        b'l' => b"Content-Length",
+        b'c' => b"Content-Type",
+        b'k' => b"Supported",
+        b's' => b"Subject",
+        b'e' => b"Content-Encoding",
+        b'o' => b"Event",
+        b'u' => b"Allow-Events",
+        b'r' => b"Refer-To",
+        b'b' => b"Referred-By",
+        _ => name,
+    }
+}
+
+fn is_comma_separated(name: &[u8]) -> bool {
+    [&b"Via"[..], b"Contact", b"Route", b"Record-Route"]
+        .iter()
+        .any(|item| item.eq_ignore_ascii_case(name))
+}
+
+/// Split a header value on the commas that aren't inside
+/// a quoted string or angle brackets.
+fn split_header_values(input: &[u8]) -> Vec<&[u8]> {
+    let mut values = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut bracketed = false;
+    for (index, c) in input.iter().enumerate() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            b'\\' if quoted => escaped = true,
+            b'"' => quoted = !quoted,
+            b'<' if !quoted => bracketed = true,
+            b'>' if !quoted => bracketed = false,
+            b',' if !quoted && !bracketed => {
+                values.push(trim_lws(&input[start..index]));
+                start = index + 1;
+            },
+            _ => {},
+        }
+    }
+    values.push(trim_lws(&input[start..]));
+    values.retain(|value| !value.is_empty());
+    values
+}
+
 named!(pub _parse_header<Header>, alt!(
     parse_accept_encoding_header |
     parse_accept_header |
@@ -153,6 +316,38 @@ macro_rules! impl_named_parser {
     };
 }
 
+macro_rules! impl_route_parser {
+    ($name:tt, $tag:tt, $variant:ident) => {
+        pub fn $name<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Header, E> {
+            let (input, _) = tag_no_case($tag)(input)?;
+            let (input, _) = opt(take_while(is_space))(input)?;
+            let (input, _) = char(':')(input)?;
+            let (input, _) = opt(take_while(is_space))(input)?;
+            let (input, route) = parse_route_value(input)?;
+            let (input, _) = tag("\r\n")(input)?;
+            Ok((input, Header::$variant(route)))
+        }
+    };
+}
+
+macro_rules! impl_auth_parser {
+    ($name:tt, $tag:tt, $variant:ident) => {
+        pub fn $name<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Header, E> {
+            let (input, _) = opt(tag("\r\n"))(input)?;
+            let (input, _) = tag_no_case($tag)(input)?;
+            let (input, _) = opt(take_while(is_space))(input)?;
+            let (input, _) = char(':')(input)?;
+            let (input, _) = opt(take_while(is_space))(input)?;
+            let (input, schema) = parse_auth_schema::<E>(input)?;
+            let (input, _) = char(' ')(input)?;
+            let (input, res) = parse_auth_header_vars(input)?;
+            let (input, _) = opt(char(' '))(input)?;
+            let (input, _) = tag("\r\n")(input)?;
+            Ok((input, Header::$variant(auth::AuthHeader(schema, res))))
+        }
+    };
+}
+
 macro_rules! impl_type_parser {
     ($name:tt, $tag:tt, $variant:ident) => {
         pub fn $name<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Header, E> {
@@ -205,12 +400,12 @@ impl_string_parser!(
 );
 impl_string_parser!(parse_date_header, "Date", Date);
 impl_string_parser!(parse_organization_header, "Organization", Organization);
-impl_string_parser!(
+impl_auth_parser!(
     parse_proxy_authenticate_header,
     "Proxy-Authenticate",
     ProxyAuthenticate
 );
-impl_string_parser!(
+impl_auth_parser!(
     parse_proxy_authorization_header,
     "Proxy-Authorization",
     ProxyAuthorization
@@ -218,9 +413,9 @@ impl_string_parser!(
 impl_string_parser!(parse_proxy_require_header, "Proxy-Require", ProxyRequire);
 impl_string_parser!(parse_require_header, "Require", Require);
 impl_string_parser!(parse_retry_after_header, "Retry-After", RetryAfter);
-impl_string_parser!(parse_route_header, "Route", Route);
+impl_route_parser!(parse_route_header, "Route", Route);
 impl_string_parser!(parse_subject_header, "Subject", Subject);
-impl_string_parser!(parse_record_route_header, "Record-Route", RecordRoute);
+impl_route_parser!(parse_record_route_header, "Record-Route", RecordRoute);
 impl_string_parser!(parse_server_header, "Server", Server);
 impl_string_parser!(parse_unsupported_header, "Unsupported", Unsupported);
 impl_string_parser!(parse_warning_header, "Warning", Warning);
@@ -261,8 +456,8 @@ impl_lang_parser!(
 
 fn parse_auth_header_vars<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
-) -> IResult<&'a [u8], HashMap<String, String>, E> {
-    let mut map = HashMap::new();
+) -> IResult<&'a [u8], auth::AuthParams, E> {
+    let mut map = auth::AuthParams::new();
     let mut data = input;
     while let Ok((remains, (key, value))) = parse_key_value_pair::<E>(data) {
         map.insert(key, value);
@@ -307,6 +502,9 @@ pub fn parse_contact_header<'a, E: ParseError<&'a [u8]>>(
     let (input, _) = opt(take_while(is_space))(input)?;
     let (input, _) = char(':')(input)?;
     let (input, _) = opt(take_while(is_space))(input)?;
+    if let Ok((input, _)) = pair::<_, _, _, E, _, _>(char('*'), tag("\r\n"))(input) {
+        return Ok((input, Header::Contact(ContactHeader::wildcard())));
+    }
     let (input, out) = parse_named_field_value(input)?;
     let (input, params) = parse_contact_field_params(input)?;
     let (input, _) = tag("\r\n")(input)?;
@@ -327,74 +525,36 @@ pub fn parse_via_header<'a, E: ParseError<&'a [u8]>>(
     let (input, _) = opt(take_while(is_space))(input)?;
     let (input, _) = char(':')(input)?;
     let (input, _) = opt(take_while(is_space))(input)?;
-    let (input, version) = parse_version(input)?;
-    let (input, _) = char('/')(input)?;
-    let (input, transport) = parse_transport(input)?;
-    let (input, _) = opt(take_while(is_space))(input)?;
-    let (input, uri) = parse_uri(input)?;
-    let (input, _) = tag("\r\n")(input)?;
-    Ok((
-        input,
-        Header::Via(via::ViaHeader {
-            version,
-            transport,
-            uri,
-        }),
-    ))
-}
-
-pub fn parse_www_authenticate_header<'a, E: ParseError<&'a [u8]>>(
-    input: &'a [u8],
-) -> IResult<&'a [u8], Header, E> {
-    let (input, _) = opt(tag("\r\n"))(input)?;
-    let (input, _) = tag_no_case("WWW-Authenticate")(input)?;
-    let (input, _) = opt(take_while(is_space))(input)?;
-    let (input, _) = char(':')(input)?;
-    let (input, _) = opt(take_while(is_space))(input)?;
-    let (input, schema) = parse_auth_schema::<E>(input)?;
-    let (input, _) = char(' ')(input)?;
-    let (input, res) = parse_auth_header_vars(input)?;
-    let (input, _) = opt(char(' '))(input)?;
+    let (input, via) = via::parse_via_value(input)?;
     let (input, _) = tag("\r\n")(input)?;
-    Ok((
-        input,
-        Header::WwwAuthenticate(auth::AuthHeader(schema, res)),
-    ))
+    Ok((input, Header::Via(via)))
 }
 
-pub fn parse_authorization_header<'a, E: ParseError<&'a [u8]>>(
-    input: &'a [u8],
-) -> IResult<&'a [u8], Header, E> {
-    let (input, _) = opt(tag("\r\n"))(input)?;
-    let (input, _) = tag_no_case("Authorization")(input)?;
-    let (input, _) = opt(take_while(is_space))(input)?;
-    let (input, _) = char(':')(input)?;
-    let (input, _) = opt(take_while(is_space))(input)?;
-    let (input, schema) = parse_auth_schema(input)?;
-    let (input, _) = char(' ')(input)?;
-    let (input, res) = parse_auth_header_vars(input)?;
-    let (input, _) = opt(char(' '))(input)?;
-    let (input, _) = tag("\r\n")(input)?;
-    Ok((input, Header::Authorization(auth::AuthHeader(schema, res))))
-}
+impl_auth_parser!(
+    parse_www_authenticate_header,
+    "WWW-Authenticate",
+    WwwAuthenticate
+);
+impl_auth_parser!(parse_authorization_header, "Authorization", Authorization);
 
 pub fn parse_key_value_pair<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], (String, String), E> {
     let (input, _) = opt(char(','))(input)?;
-    let (input, _) = opt(char(' '))(input)?;
-    let (input, key) = map_res(take_while(is_alphanumeric), slice_to_string::<E>)(input)?;
+    let (input, _) = opt(take_while(is_space))(input)?;
+    let (input, key) = map_res(take_while1(is_token), slice_to_string::<E>)(input)?;
     let (input, _) = opt(char('='))(input)?;
-    let (input, value) = parse_possibly_quoted_string(input)?;
+    let (input, value) = alt((
+        parse_quoted_string,
+        map_res(take_while1(is_token), slice_to_string::<E>),
+    ))(input)?;
     Ok((input, (key, value)))
 }
 
 pub fn parse_auth_schema<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], auth::AuthSchema, E> {
-    Ok(map(tag_no_case("Digest"), |_| auth::AuthSchema::Digest)(
-        input,
-    )?)
+    map(tag_no_case("Digest"), |_| auth::AuthSchema::Digest)(input)
 }
 
 /// Parses "generic-param" ([RFC3261: Page 227, "generic-param"](https://tools.ietf.org/html/rfc3261#page-227))
@@ -0,0 +1,126 @@
+use nom::{
+    bytes::complete::take_while,
+    character::{complete::char, *},
+    combinator::opt,
+    error::ParseError,
+    IResult,
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    headers::{
+        named::{display_name, parse_name},
+        parse::parse_generic_param,
+    },
+    parse::{is_token, quote},
+    uri::parse_uri,
+    Uri, UriParam,
+};
+
+use std::fmt;
+
+/// Value used in the Route and Record-Route Headers,
+/// [RFC3261: Section 20.34](https://tools.ietf.org/html/rfc3261#section-20.34).
+/// Unlike `NamedHeader` the uri is always written inside angle brackets
+/// and the parameters keep the order they were received in, so a proxy
+/// or UAS echoing the header writes it back unchanged.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RouteHeader {
+    pub display_name: Option<String>,
+    pub uri: Uri,
+    pub parameters: IndexMap<String, Option<String>>,
+}
+
+impl RouteHeader {
+    pub fn new(uri: Uri) -> RouteHeader {
+        RouteHeader {
+            display_name: None,
+            parameters: IndexMap::new(),
+            uri,
+        }
+    }
+
+    /// Sets `display_name` of this header
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.display_name = Some(name.into());
+        self
+    }
+
+    /// Adds a parameter with a given name and a given value to `params`.
+    pub fn param<N, V>(mut self, name: N, value: Option<V>) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.parameters.insert(name.into(), value.map(Into::into));
+        self
+    }
+
+    /// Determine if the element this route points to is a loose router,
+    /// signalled by the `lr` uri parameter.
+    pub fn is_loose(&self) -> bool {
+        is_loose_router(&self.uri)
+    }
+}
+
+impl From<Uri> for RouteHeader {
+    fn from(uri: Uri) -> RouteHeader {
+        RouteHeader::new(uri)
+    }
+}
+
+impl fmt::Display for RouteHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = &self.display_name {
+            write!(f, "{} ", display_name(name))?;
+        }
+        write!(f, "<{}>", self.uri)?;
+        for (key, value) in self.parameters.iter() {
+            write!(f, ";{}", key)?;
+            match value {
+                Some(value) if value.bytes().all(is_token) => write!(f, "={}", value)?,
+                Some(value) => write!(f, "={}", quote(value))?,
+                None => {},
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Determine if `uri` contains the `lr` parameter.
+pub fn is_loose_router(uri: &Uri) -> bool {
+    uri.parameters.iter().any(UriParam::is_lr)
+}
+
+/// Parse a single name-addr value of a Route or Record-Route header.
+pub fn parse_route_value<'a, E: ParseError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], RouteHeader, E> {
+    let (input, display_name) = opt(parse_name)(input)?;
+    let (input, _) = opt(take_while(is_space))(input)?;
+    let (input, _) = char('<')(input)?;
+    let (input, uri) = parse_uri(input)?;
+    let (input, _) = char('>')(input)?;
+    let (input, parameters) = parse_route_params(input)?;
+    Ok((
+        input,
+        RouteHeader {
+            display_name,
+            uri,
+            parameters,
+        },
+    ))
+}
+
+/// Parse the header parameters of a route value, in order.
+fn parse_route_params<'a, E: ParseError<&'a [u8]>>(
+    mut input: &'a [u8],
+) -> IResult<&'a [u8], IndexMap<String, Option<String>>, E> {
+    let mut map = IndexMap::new();
+    while let Ok((data, (key, value))) = parse_generic_param::<E>(input) {
+        map.insert(key, value);
+        input = data;
+    }
+    Ok((input, map))
+}
@@ -1,37 +1,267 @@
-use crate::*;
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while, take_while1},
+    character::{complete::char, is_digit, is_space},
+    combinator::{map, opt, recognize},
+    error::{ErrorKind, ParseError},
+    IResult,
+};
 
-use std::fmt;
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+};
 
-/// Value used in the Via Header.
+use crate::{
+    core::{parse_transport, parse_version},
+    parse::{is_token, parse_quoted_string, slice_to_string},
+    uri::domain::{parse_domain, parse_ipv6_address},
+    *,
+};
+
+/// Value used in the Via Header
+/// ([RFC3261: Section 20.42](https://tools.ietf.org/html/rfc3261#section-20.42)).
 #[derive(Debug, PartialEq, Clone)]
 pub struct ViaHeader {
     pub version: Version,
     pub transport: Transport,
-    pub uri: Uri,
+    /// The host, and optionally port, the request was sent from.
+    pub sent_by: Domain,
+    pub branch: Option<String>,
+    /// The source address of the request, added by the server that
+    /// received it.
+    pub received: Option<Domain>,
+    /// The `rport` parameter of [RFC3581](https://tools.ietf.org/html/rfc3581),
+    /// `Some(None)` when a client requests it and `Some(Some(port))` once
+    /// a server has filled in the source port.
+    pub rport: Option<Option<u16>>,
+    pub maddr: Option<Domain>,
+    pub ttl: Option<u8>,
+    /// Any other parameters, in the order they appeared.
+    pub parameters: Vec<(String, Option<String>)>,
+}
+
+/// Where a response should be sent, as described in
+/// [RFC3261: Section 18.2.2](https://tools.ietf.org/html/rfc3261#section-18.2.2).
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResponseTarget {
+    pub transport: Transport,
+    /// The address and port to send the response to. When it holds a
+    /// domain name it must be resolved as described in
+    /// [RFC3263: Section 5](https://tools.ietf.org/html/rfc3263#section-5).
+    pub host: Domain,
+    /// The time to live to use when sending to a multicast `maddr`.
+    pub ttl: Option<u8>,
+    /// Whether the connection the request arrived on should be used,
+    /// `host` is only used when that connection has closed.
+    pub reuse_connection: bool,
 }
 
 impl ViaHeader {
-    pub fn new(uri: Uri, transport: Transport) -> ViaHeader {
+    pub fn new(sent_by: Domain, transport: Transport) -> ViaHeader {
         ViaHeader {
-            transport,
-            uri,
             version: Version::default(),
+            transport,
+            sent_by,
+            branch: None,
+            received: None,
+            rport: None,
+            maddr: None,
+            ttl: None,
+            parameters: vec![],
         }
     }
 
     pub fn branch(&self) -> Option<&String> {
-        self.uri.parameters.iter().find_map(|p| {
-            if let UriParam::Branch(branch) = p {
-                Some(branch)
-            } else {
-                None
-            }
-        })
+        self.branch.as_ref()
+    }
+
+    /// Determine if the branch starts with the magic cookie `z9hG4bK`
+    /// marking it as unique ([RFC3261: Section 8.1.1.7](https://tools.ietf.org/html/rfc3261#section-8.1.1.7)).
+    pub fn is_rfc3261_branch(&self) -> bool {
+        self.branch
+            .as_ref()
+            .map(|branch| branch.starts_with("z9hG4bK"))
+            .unwrap_or(false)
+    }
+
+    /// Add a parameter.
+    pub fn parameter<N: Into<String>>(mut self, name: N, value: Option<String>) -> ViaHeader {
+        self.parameters.push((name.into(), value));
+        self
+    }
+
+    /// Record that the request carrying this Via arrived from `source`.
+    /// The `received` parameter is added when `source` differs from the
+    /// sent-by host ([RFC3261: Section 18.2.1](https://tools.ietf.org/html/rfc3261#section-18.2.1))
+    /// and a requested `rport` is set to the source port
+    /// ([RFC3581: Section 4](https://tools.ietf.org/html/rfc3581#section-4)).
+    pub fn received_from(&mut self, source: SocketAddr) {
+        let address = match source.ip() {
+            IpAddr::V4(addr) => Domain::Ipv4(addr, None),
+            IpAddr::V6(addr) => Domain::Ipv6(addr, None),
+        };
+        if self.rport.is_some() {
+            self.rport = Some(Some(source.port()));
+            self.received = Some(address);
+        } else if with_port(&self.sent_by, None) != address {
+            self.received = Some(address);
+        }
+    }
+
+    /// Compute where the response to the request carrying this Via should
+    /// be sent, following [RFC3261: Section 18.2.2](https://tools.ietf.org/html/rfc3261#section-18.2.2)
+    /// and [RFC3581: Section 4](https://tools.ietf.org/html/rfc3581#section-4).
+    pub fn response_target(&self) -> ResponseTarget {
+        let port = self
+            .sent_by
+            .port()
+            .unwrap_or_else(|| self.transport.default_port());
+        let received_port = match self.rport {
+            Some(Some(rport)) => rport,
+            _ => port,
+        };
+        let reuse_connection = self.transport.is_reliable();
+        let (host, ttl) = match (&self.maddr, &self.received) {
+            (Some(maddr), _) if !reuse_connection => (with_port(maddr, Some(port)), self.ttl),
+            (_, Some(received)) => (with_port(received, Some(received_port)), None),
+            (_, None) => (with_port(&self.sent_by, Some(port)), None),
+        };
+        ResponseTarget {
+            transport: self.transport,
+            host,
+            ttl,
+            reuse_connection,
+        }
+    }
+}
+
+fn with_port(domain: &Domain, port: Option<u16>) -> Domain {
+    match domain {
+        Domain::Ipv4(addr, _) => Domain::Ipv4(*addr, port),
+        Domain::Ipv6(addr, _) => Domain::Ipv6(*addr, port),
+        Domain::Domain(host, _) => Domain::Domain(host.clone(), port),
     }
 }
 
 impl fmt::Display for ViaHeader {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Via: {}/{} {}", self.version, self.transport, self.uri)
+        write!(
+            f,
+            "Via: {}/{} {}",
+            self.version, self.transport, self.sent_by
+        )?;
+        if let Some(branch) = &self.branch {
+            write!(f, ";branch={}", branch)?;
+        }
+        if let Some(received) = &self.received {
+            write!(f, ";received={}", received)?;
+        }
+        match self.rport {
+            Some(Some(rport)) => write!(f, ";rport={}", rport)?,
+            Some(None) => write!(f, ";rport")?,
+            None => {},
+        }
+        if let Some(maddr) = &self.maddr {
+            write!(f, ";maddr={}", maddr)?;
+        }
+        if let Some(ttl) = &self.ttl {
+            write!(f, ";ttl={}", ttl)?;
+        }
+        for (key, value) in &self.parameters {
+            write!(f, ";{}", key)?;
+            if let Some(value) = value {
+                write!(f, "={}", value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse the value of a Via header, `SIP/2.0/UDP host:port;params`.
+pub fn parse_via_value<'a, E: ParseError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], ViaHeader, E> {
+    let (input, version) = parse_version(input)?;
+    let (input, _) = char('/')(input)?;
+    let (input, transport) = parse_transport(input)?;
+    let (input, _) = take_while(is_space)(input)?;
+    let (mut input, sent_by) = parse_domain(input)?;
+    let mut via = ViaHeader {
+        version,
+        ..ViaHeader::new(sent_by, transport)
+    };
+    while let Ok((data, _)) = tag::<_, _, E>(";")(input) {
+        let (data, key) = map(take_while1(is_token), |key: &[u8]| {
+            String::from_utf8_lossy(key).to_string()
+        })(data)?;
+        let (data, equals) = opt(char('='))(data)?;
+        input = match (key.to_ascii_lowercase().as_str(), equals) {
+            ("branch", Some(_)) => {
+                let (data, branch) = take_while1(is_token)(data)?;
+                via.branch = Some(slice_to_string::<E>(branch).map_err(nom::Err::Failure)?);
+                data
+            },
+            ("received", Some(_)) => {
+                let (data, received) = alt((
+                    map(parse_ipv6_address, |addr| Domain::Ipv6(addr, None)),
+                    parse_domain,
+                ))(data)
+                .map_err(failure)?;
+                via.received = Some(received);
+                data
+            },
+            ("rport", Some(_)) => {
+                let (data, port) = take_while1(is_digit)(data).map_err(failure)?;
+                let port = parse_number(port)?;
+                via.rport = Some(Some(port));
+                data
+            },
+            ("rport", None) => {
+                via.rport = Some(None);
+                data
+            },
+            ("maddr", Some(_)) => {
+                let (data, maddr) = parse_domain(data).map_err(failure)?;
+                via.maddr = Some(maddr);
+                data
+            },
+            ("ttl", Some(_)) => {
+                let (data, ttl) = take_while1(is_digit)(data).map_err(failure)?;
+                via.ttl = Some(parse_number(ttl)?);
+                data
+            },
+            (_, Some(_)) => {
+                let (data, value) = alt((
+                    recognize(parse_quoted_string),
+                    take_while1(|chr| is_token(chr) || b":[]".contains(&chr)),
+                ))(data)?;
+                via.parameters
+                    .push((key, Some(String::from_utf8_lossy(value).into())));
+                data
+            },
+            (_, None) => {
+                via.parameters.push((key, None));
+                data
+            },
+        };
+    }
+    Ok((input, via))
+}
+
+fn parse_number<'a, N: std::str::FromStr, E: ParseError<&'a [u8]>>(
+    input: &'a [u8],
+) -> Result<N, nom::Err<E>> {
+    ::std::str::from_utf8(input)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| nom::Err::Failure(E::from_error_kind(input, ErrorKind::Digit)))
+}
+
+/// Invalid values of the known parameters fail the whole header.
+fn failure<E>(err: nom::Err<E>) -> nom::Err<E> {
+    match err {
+        nom::Err::Error(err) => nom::Err::Failure(err),
+        err => err,
     }
 }
@@ -26,7 +26,7 @@ impl fmt::Display for Header {
             Header::AlertInfo(data) => write_simple_field("Alert-Info", data, f),
             Header::ErrorInfo(data) => write_simple_field("Error-Info", data, f),
             Header::AuthenticationInfo(data) => write_simple_field("Authentication-Info", data, f),
-            Header::Authorization(data) => write_auth_header("Authorization", data, f),
+            Header::Authorization(data) => write_auth_header("Authorization", data, false, f),
             Header::CallInfo(data) => write_simple_field("Call-Info", data, f),
             Header::InReplyTo(data) => write_simple_field("In-Reply-To", data, f),
             Header::ContentDisposition(data) => write_simple_field("Content-Disposition", data, f),
@@ -34,8 +34,12 @@ impl fmt::Display for Header {
             Header::MinExpires(exp) => write_simple_field("Min-Expires", exp, f),
             Header::MimeVersion(exp) => write_simple_field("MIME-Version", exp, f),
             Header::Organization(org) => write_simple_field("Organization", org, f),
-            Header::ProxyAuthenticate(data) => write_simple_field("Proxy-Authenticate", data, f),
-            Header::ProxyAuthorization(data) => write_simple_field("Proxy-Authorization", data, f),
+            Header::ProxyAuthenticate(data) => {
+                write_auth_header("Proxy-Authenticate", data, true, f)
+            },
+            Header::ProxyAuthorization(data) => {
+                write_auth_header("Proxy-Authorization", data, false, f)
+            },
             Header::ProxyRequire(data) => write_simple_field("Proxy-Require", data, f),
             Header::Require(data) => write_simple_field("Require", data, f),
             Header::RetryAfter(data) => write_simple_field("Retry-After", data, f),
@@ -50,7 +54,7 @@ impl fmt::Display for Header {
             Header::Warning(data) => write_simple_field("Warning", data, f),
             Header::Via(data) => write!(f, "{}", data),
             Header::Priority(data) => write_simple_field("Priority", data, f),
-            Header::WwwAuthenticate(data) => write_auth_header("WWW-Authenticate", data, f),
+            Header::WwwAuthenticate(data) => write_auth_header("WWW-Authenticate", data, true, f),
             Header::XFsSendingMessage(data) => write_simple_field("X-FS-Sending-Message", data, f),
             Header::Other(key, value) => write!(f, "{}: {}", key, value),
         }
@@ -83,12 +87,14 @@ fn write_simple_field<D: fmt::Display>(
     write!(f, "{}: {}", header, data)
 }
 
-fn write_auth_header<D: fmt::Display>(
+fn write_auth_header(
     header: &str,
-    data: D,
+    data: &AuthHeader,
+    challenge: bool,
     f: &mut fmt::Formatter,
 ) -> fmt::Result {
-    write!(f, "{}: {}", header, data)
+    write!(f, "{}: ", header)?;
+    data.write(f, challenge)
 }
 
 /// Writes an optional parameter if it has any value, prepending it with ';'.
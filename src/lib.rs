@@ -17,6 +17,9 @@
 //! let output = libsip::parse_message::<VerboseError<&[u8]>>(packet.as_ref()).unwrap();
 //! ```
 //!
+//! Messages received over a stream transport like TCP must be framed first,
+//! the `MessageDecoder` buffers partial reads and yields each complete message.
+//!
 //! ### Creating Messages
 //! This crate provides 2 abstraction's to aid in building sip messages.
 //! The `ResponseGenerator` is used to create sip response's and the
@@ -45,11 +48,39 @@
 //!  ```
 //!
 //! ### Registration
-//! The registration manager is used to generate REGISTER requests. Once
-//! that is sent to the server you must wait for the Challange response pass
-//! it to the `set_challenge` method of the RegistrationManager.
+//! The registration manager is used to generate REGISTER requests. Each
+//! response received is passed to it's `handle_response` method, which
+//! answers challenges, adjusts the interval after a `423 Interval Too Brief`
+//! and keeps track of when the registration must be refreshed.
 //! reqpeatedly calling the `get_request` method will cause the c_nonce
 //! counter to be incremented and a new hash computed.
+//!
+//! ### Authentication
+//! Servers challenge requests with the `DigestVerifier`, which signs it's
+//! nonces so no state is needed between the challenge and the credentials.
+//! The passwords, or their hashes, are looked up through a `CredentialStore`.
+//! Authenticated REGISTER requests are then passed to a `Registrar`, which
+//! keeps the bindings of each address of record in a `LocationService`.
+//!
+//! ### Transactions
+//! The `TransactionLayer` drives the RFC 3261 client and server transaction
+//! state machines. It performs no IO, every call takes the current `Instant`
+//! and returns the messages that should be sent or passed up to the caller.
+//!
+//! ### Transport
+//! The `TransportLayer` sits below the transactions. It keeps a table of
+//! open connections keyed by transport and remote address, frames stream
+//! transports, stamps `received` and `rport` on incoming requests and moves
+//! large UDP requests over to TCP. Like the transactions it performs no IO,
+//! the `Loopback` network connects transport layers in memory for tests.
+//! The `Resolver` turns a uri into the ordered list of servers to try,
+//! querying DNS through the `DnsResolver` trait.
+//!
+//! ### Async User Agent
+//! With the `tokio` feature enabled the `UserAgent` runs a `SoftPhone` over
+//! a UDP socket. It keeps the registration refreshed, answers incoming
+//! requests through a `RequestHandler` and returns a future for the final
+//! response of every request it sends.
 
 #[macro_use]
 extern crate nom;
@@ -60,27 +91,50 @@ mod macros;
 
 mod client;
 pub mod core;
+pub mod dialog;
 pub mod headers;
 pub mod parse;
 mod request;
 mod response;
+pub mod routing;
+pub mod server;
+pub mod transaction;
+pub mod transport;
 pub mod uri;
 
 pub use crate::{
     client::{
-        HeaderWriteConfig, InviteHelper, MessageHelper, MessageWriter, RegistrationManager,
-        SoftPhone,
+        AuthChallenges, HeaderWriteConfig, InviteHelper, InviteWriter, KeepAlive, MessageHelper,
+        MessageWriter, RegistrationManager, RegistrationStatus, SoftPhone,
     },
     core::{
-        parse_message, parse_request, parse_response, parse_version, EventRequestExt, Method,
-        RegisterRequestExt, RequestExt, SipMessage, SipMessageError, SipMessageExt, Transport,
-        Version,
+        parse_message, parse_request, parse_response, parse_version, Decoded, EventRequestExt,
+        MessageDecoder, Method, RegisterRequestExt, RequestExt, SipMessage, SipMessageError,
+        SipMessageExt, Transport, Version,
     },
+    dialog::{Dialog, DialogId, DialogState},
     headers::{
-        parse_header, via::ViaHeader, AuthContext, AuthHeader, AuthSchema, ContactHeader,
-        ContentType, Header, Headers, Language, NamedHeader, SubscriptionState,
+        parse_header,
+        via::{ResponseTarget, ViaHeader},
+        AuthContext, AuthError, AuthHeader, AuthParams, AuthSchema, ContactHeader, ContentType,
+        DigestAlgorithm, DigestInput, Header, Headers, Language, NamedHeader, RouteHeader,
+        SubscriptionState,
     },
     request::RequestGenerator,
     response::ResponseGenerator,
-    uri::{parse_uri, Domain, Uri, UriAuth, UriParam, UriSchema},
+    server::{
+        Binding, Credential, CredentialStore, DigestVerifier, LocationService, Registrar,
+        Verification,
+    },
+    transaction::{
+        Transaction, TransactionEvent, TransactionKey, TransactionLayer, TransactionState,
+    },
+    transport::{
+        ConnectionKey, DnsResolver, Loopback, NaptrRecord, Resolver, SrvRecord, StaticZone,
+        TransportEvent, TransportLayer,
+    },
+    uri::{parse_uri, Domain, TelUri, Uri, UriAuth, UriParam, UriSchema},
 };
+
+#[cfg(feature = "tokio")]
+pub use crate::client::{RequestHandler, ResponseFuture, UserAgent};
@@ -1,9 +1,9 @@
 use crate::headers::GenValue;
 use nom::{
     branch::alt,
-    bytes::complete::{take_until, take_while},
+    bytes::complete::take_while,
     character::{complete::char as parse_char, is_alphanumeric, *},
-    combinator::map_res,
+    combinator::{map, map_res},
     error::{ErrorKind, ParseError},
     IResult,
 };
@@ -18,14 +18,13 @@ pub fn slice_to_string<'a, E: ParseError<&'a [u8]>>(slice: &'a [u8]) -> Result<S
     if slice.is_empty() {
         Err(E::from_error_kind(slice, ErrorKind::Eof))
     } else {
-        Ok(String::from_utf8(Vec::from(slice))
-            .map_err(|_| E::from_error_kind(slice, ErrorKind::IsNot))?)
+        String::from_utf8(Vec::from(slice)).map_err(|_| E::from_error_kind(slice, ErrorKind::IsNot))
     }
 }
 
 pub fn slice_to_string_nullable(slice: &[u8]) -> Result<String, IoError> {
-    Ok(String::from_utf8(Vec::from(slice))
-        .map_err(|_| IoError::new(IoErrorKind::InvalidInput, "Failed to parse utf8 string"))?)
+    String::from_utf8(Vec::from(slice))
+        .map_err(|_| IoError::new(IoErrorKind::InvalidInput, "Failed to parse utf8 string"))
 }
 
 pub fn slice_to_gen_value_nullable(slice: &[u8]) -> Result<GenValue, IoError> {
@@ -37,15 +36,15 @@ pub fn slice_to_gen_value_nullable(slice: &[u8]) -> Result<GenValue, IoError> {
 
 /// Parse unsigned 16 bit integer using `Parse::parse`.
 pub fn parse_u16<'a, E: ParseError<&'a [u8]>>(slice: &'a [u8]) -> Result<u16, E> {
-    Ok(::std::str::from_utf8(slice)
+    ::std::str::from_utf8(slice)
         .map_err(|_| E::from_error_kind(slice, ErrorKind::IsNot))?
         .parse()
-        .map_err(|_| E::from_error_kind(slice, ErrorKind::IsNot))?)
+        .map_err(|_| E::from_error_kind(slice, ErrorKind::IsNot))
 }
 
 /// Parse unsigned 8 bit integer using `Parse::parse`.
 pub fn parse_u8(slice: &[u8]) -> Result<u8, IoError> {
-    Ok(::std::str::from_utf8(slice)
+    ::std::str::from_utf8(slice)
         .map_err(|_| {
             IoError::new(
                 IoErrorKind::InvalidInput,
@@ -53,12 +52,12 @@ pub fn parse_u8(slice: &[u8]) -> Result<u8, IoError> {
             )
         })?
         .parse()
-        .map_err(|_| IoError::new(IoErrorKind::InvalidInput, "Failed to parse u8 integer"))?)
+        .map_err(|_| IoError::new(IoErrorKind::InvalidInput, "Failed to parse u8 integer"))
 }
 
 /// Parse unsigned 32 bit integer using `Parse::parse`.
 pub fn parse_u32(slice: &[u8]) -> Result<u32, IoError> {
-    Ok(::std::str::from_utf8(slice)
+    ::std::str::from_utf8(slice)
         .map_err(|_| {
             IoError::new(
                 IoErrorKind::InvalidInput,
@@ -66,15 +65,15 @@ pub fn parse_u32(slice: &[u8]) -> Result<u32, IoError> {
             )
         })?
         .parse()
-        .map_err(|_| IoError::new(IoErrorKind::InvalidInput, "Failed to parse u32 integer"))?)
+        .map_err(|_| IoError::new(IoErrorKind::InvalidInput, "Failed to parse u32 integer"))
 }
 
 /// Parse input as an f32 using `Parse::parse`.
 pub fn parse_f32(slice: &[u8]) -> Result<f32, IoError> {
-    Ok(::std::str::from_utf8(slice)
+    ::std::str::from_utf8(slice)
         .map_err(|_| IoError::new(IoErrorKind::InvalidInput, "Failed to parse utf8 f32"))?
         .parse()
-        .map_err(|_| IoError::new(IoErrorKind::InvalidInput, "Failed to parse f32"))?)
+        .map_err(|_| IoError::new(IoErrorKind::InvalidInput, "Failed to parse f32"))
 }
 
 /// Parse Input as a vector of bytes.
@@ -86,7 +85,7 @@ pub fn parse_byte_vec<'a, E: ParseError<&'a [u8]>>(
 
 pub fn parse_ip_address<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
-) -> IResult<&[u8], Ipv4Addr, E> {
+) -> IResult<&'a [u8], Ipv4Addr, E> {
     let (input, byte1) = map_res(take_while(is_digit), parse_u8)(input)?;
     let (input, _) = parse_char('.')(input)?;
     let (input, byte2) = map_res(take_while(is_digit), parse_u8)(input)?;
@@ -108,22 +107,67 @@ pub fn parse_possibly_quoted_string<'a, E: ParseError<&'a [u8]>>(
     alt::<_, _, E, _>(alts)(input)
 }
 
+/// Parse a quoted-string, unescaping any quoted-pairs.
+/// ([RFC3261: Section 25.1](https://tools.ietf.org/html/rfc3261#section-25.1))
+/// # Examples
+///
+/// ```
+/// use libsip::parse::parse_quoted_string;
+/// use nom::error::VerboseError;
+/// assert_eq!(
+///     parse_quoted_string::<VerboseError<&[u8]>>(br#""a \"b\" \\c""#),
+///     Ok((&b""[..], "a \"b\" \\c".to_string()))
+/// );
+/// ```
 pub fn parse_quoted_string<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], String, E> {
     let (input, _) = parse_char('"')(input)?;
-    let (input, out) = map_res(take_until("\""), slice_to_string_nullable)(input)?;
-    let (input, _) = parse_char('"')(input)?;
-    Ok((input, out))
+    let mut out = vec![];
+    let mut index = 0;
+    loop {
+        match (input.get(index), input.get(index + 1)) {
+            (Some(b'"'), _) => break,
+            (Some(b'\\'), Some(byte)) => {
+                out.push(*byte);
+                index += 2;
+            },
+            (Some(byte), _) => {
+                out.push(*byte);
+                index += 1;
+            },
+            (None, _) => return Err(nom::Err::Error(E::from_error_kind(input, ErrorKind::Char))),
+        }
+    }
+    let out = slice_to_string_nullable(&out)
+        .map_err(|_| nom::Err::Error(E::from_error_kind(input, ErrorKind::MapRes)))?;
+    Ok((&input[index + 1..], out))
 }
 
 pub fn parse_quoted_string_as_gen_value<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], GenValue, E> {
-    let (input, _) = parse_char('"')(input)?;
-    let (input, out) = map_res(take_until("\""), slice_to_string_nullable)(input)?;
-    let (input, _) = parse_char('"')(input)?;
-    Ok((input, GenValue::QuotedString(out)))
+    map(parse_quoted_string, GenValue::QuotedString)(input)
+}
+
+/// Wrap `input` in double quotes, escaping any `"` or `\\` as a quoted-pair.
+/// # Examples
+///
+/// ```
+/// use libsip::parse::quote;
+/// assert_eq!(quote("a \"b\""), r#""a \"b\"""#);
+/// ```
+pub fn quote(input: &str) -> String {
+    let mut out = String::with_capacity(input.len() + 2);
+    out.push('"');
+    for chr in input.chars() {
+        if chr == '"' || chr == '\\' {
+            out.push('\\');
+        }
+        out.push(chr);
+    }
+    out.push('"');
+    out
 }
 
 /// [RFC3261: Page 220, "reserved"](https://tools.ietf.org/html/rfc3261#page-220)
@@ -190,3 +234,86 @@ pub fn is_mark(chr: u8) -> bool {
 pub fn is_token(chr: u8) -> bool {
     is_alphanumeric(chr) || "-.!%*_+`'~".contains(char::from(chr))
 }
+
+/// Characters allowed unescaped in the user part of a uri
+/// ([RFC3261: Page 222, "user"](https://tools.ietf.org/html/rfc3261#page-222))
+pub fn is_user_char(chr: u8) -> bool {
+    is_unreserved(chr) || "&=+$,;?/".contains(char::from(chr))
+}
+
+/// Characters allowed unescaped in the password part of a uri
+/// ([RFC3261: Page 222, "password"](https://tools.ietf.org/html/rfc3261#page-222))
+pub fn is_password_char(chr: u8) -> bool {
+    is_unreserved(chr) || "&=+$,".contains(char::from(chr))
+}
+
+/// Characters allowed unescaped in a uri parameter name or value
+/// ([RFC3261: Page 222, "paramchar"](https://tools.ietf.org/html/rfc3261#page-222))
+pub fn is_param_char(chr: u8) -> bool {
+    is_unreserved(chr) || "[]/:&+$".contains(char::from(chr))
+}
+
+/// Characters allowed unescaped in a uri header name or value
+/// ([RFC3261: Page 222, "hnv-unreserved"](https://tools.ietf.org/html/rfc3261#page-222))
+pub fn is_header_char(chr: u8) -> bool {
+    is_unreserved(chr) || "[]/?:+$".contains(char::from(chr))
+}
+
+/// Decode the `%HH` escape sequences in `input`. Invalid
+/// sequences are kept as they are.
+/// # Examples
+///
+/// ```
+/// use libsip::parse::unescape;
+/// assert_eq!(unescape(b"alice%40home"), "alice@home");
+/// ```
+pub fn unescape(input: &[u8]) -> String {
+    let mut out = Vec::with_capacity(input.len());
+    let mut index = 0;
+    while index < input.len() {
+        let hex = input
+            .get(index + 1..index + 3)
+            .and_then(|hex| ::std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (input[index], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                index += 3;
+            },
+            (byte, _) => {
+                out.push(byte);
+                index += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&out).into()
+}
+
+/// Escape every byte of `input` that `allowed` rejects as `%HH`.
+/// # Examples
+///
+/// ```
+/// use libsip::parse::{escape, is_user_char};
+/// assert_eq!(escape("alice@home", is_user_char), "alice%40home");
+/// ```
+pub fn escape(input: &str, allowed: fn(u8) -> bool) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        if allowed(byte) {
+            out.push(char::from(byte));
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+/// Parse a run of characters accepted by `allowed` or `%HH` escape
+/// sequences, returning the decoded value.
+pub fn parse_escaped<'a, E: ParseError<&'a [u8]>>(
+    input: &'a [u8],
+    allowed: fn(u8) -> bool,
+) -> IResult<&'a [u8], String, E> {
+    let (input, value) = take_while(|chr| allowed(chr) || chr == b'%')(input)?;
+    Ok((input, unescape(value)))
+}
@@ -25,6 +25,24 @@ impl ResponseGenerator {
         }
     }
 
+    /// Start a response to `request` by copying it's Via, From, To, Call-ID
+    /// and CSeq headers as described in
+    /// [RFC3261: Section 8.2.6.2](https://tools.ietf.org/html/rfc3261#section-8.2.6.2).
+    pub fn from_request(request: &SipMessage) -> ResponseGenerator {
+        let mut res = ResponseGenerator::new();
+        for header in request.headers().iter() {
+            match header {
+                Header::Via(_)
+                | Header::From(_)
+                | Header::To(_)
+                | Header::CallId(_)
+                | Header::CSeq(_, _) => res = res.header(header.clone()),
+                _ => {},
+            }
+        }
+        res
+    }
+
     /// Set the response status code.
     pub fn code(mut self, code: u32) -> ResponseGenerator {
         self.code = Some(code);
@@ -0,0 +1,141 @@
+//! Helpers for the route set carried in Route and Record-Route headers,
+//! covering both loose routers ([RFC3261: Section 16.12](https://tools.ietf.org/html/rfc3261#section-16.12))
+//! and the strict routers of [RFC2543](https://tools.ietf.org/html/rfc2543).
+
+pub use crate::headers::route::is_loose_router;
+
+use crate::{Header, RouteHeader, SipMessage, Uri, UriParam};
+
+/// The Route headers of `msg` in the order they appear.
+pub fn routes(msg: &SipMessage) -> Vec<RouteHeader> {
+    msg.headers()
+        .iter()
+        .filter_map(|header| match header {
+            Header::Route(route) => Some(route.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The Record-Route headers of `msg` in the order they appear.
+pub fn record_routes(msg: &SipMessage) -> Vec<RouteHeader> {
+    msg.headers()
+        .iter()
+        .filter_map(|header| match header {
+            Header::RecordRoute(route) => Some(route.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Compute the route set of a dialog from the Record-Route headers of the
+/// message that created it. The UAS uses them in order, the UAC reverses them.
+/// [RFC3261: Section 12.1](https://tools.ietf.org/html/rfc3261#section-12.1)
+pub fn dialog_route_set(msg: &SipMessage, uac: bool) -> Vec<RouteHeader> {
+    let mut route_set = record_routes(msg);
+    if uac {
+        route_set.reverse();
+    }
+    route_set
+}
+
+/// Compute the Request-URI and Route headers of a request sent to
+/// `remote_target` over `route_set`. When the first route is a loose router
+/// the Request-URI is the remote target, otherwise the first route becomes
+/// the Request-URI and the remote target is appended to the Route headers.
+/// [RFC3261: Section 12.2.1.1](https://tools.ietf.org/html/rfc3261#section-12.2.1.1)
+pub fn request_target(remote_target: &Uri, route_set: &[RouteHeader]) -> (Uri, Vec<RouteHeader>) {
+    match route_set.first() {
+        None => (remote_target.clone(), vec![]),
+        Some(first) if first.is_loose() => (remote_target.clone(), route_set.to_vec()),
+        Some(first) => {
+            let mut routes = route_set[1..].to_vec();
+            routes.push(RouteHeader::new(remote_target.clone()));
+            (request_uri(first.uri.clone()), routes)
+        },
+    }
+}
+
+/// Set the Request-URI and Route headers of `request` as described by `request_target`.
+/// Any Route headers already present are replaced.
+pub fn apply_route_set(request: &mut SipMessage, remote_target: &Uri, route_set: &[RouteHeader]) {
+    let (target, routes) = request_target(remote_target, route_set);
+    set_routes(request, routes);
+    if let SipMessage::Request { uri, .. } = request {
+        *uri = target;
+    }
+}
+
+/// The uri the request should be sent to, the top Route
+/// header when present otherwise the Request-URI. A tel uri
+/// has no host to send to, it has to be turned into a sip uri
+/// with `TelUri::to_sip_uri` first.
+pub fn next_hop(request: &SipMessage) -> Option<Uri> {
+    let hop = match (routes(request).first(), request) {
+        (Some(route), _) => route.uri.clone(),
+        (None, SipMessage::Request { uri, .. }) => uri.clone(),
+        (None, SipMessage::Response { .. }) => return None,
+    };
+    hop.host_domain()?;
+    Some(hop)
+}
+
+/// Route information preprocessing done by a proxy on a received request.
+/// If the Request-URI is one this proxy put in a Record-Route header, the
+/// previous hop was a strict router and the original Request-URI is restored
+/// from the last Route header. A top Route header pointing at this proxy is
+/// then removed. `is_local` determines if a uri refers to this proxy.
+/// [RFC3261: Section 16.4](https://tools.ietf.org/html/rfc3261#section-16.4)
+pub fn preprocess_routes<F: Fn(&Uri) -> bool>(request: &mut SipMessage, is_local: F) {
+    let mut routes = routes(request);
+    if let SipMessage::Request { uri, .. } = request {
+        if is_local(uri) {
+            if let Some(last) = routes.pop() {
+                *uri = last.uri;
+            }
+        }
+    }
+    if routes.first().map(|route| is_local(&route.uri)) == Some(true) {
+        routes.remove(0);
+    }
+    set_routes(request, routes);
+}
+
+/// Prepare a request a proxy is about to forward. If the top Route header
+/// is a strict router, the Request-URI is appended to the Route headers
+/// and the first Route header becomes the Request-URI.
+/// [RFC3261: Section 16.6](https://tools.ietf.org/html/rfc3261#section-16.6)
+pub fn postprocess_routes(request: &mut SipMessage) {
+    let mut routes = routes(request);
+    match routes.first() {
+        Some(first) if !first.is_loose() => {},
+        _ => return,
+    }
+    let first = routes.remove(0);
+    if let SipMessage::Request { uri, .. } = request {
+        let previous = std::mem::replace(uri, request_uri(first.uri));
+        routes.push(RouteHeader::new(previous));
+    }
+    set_routes(request, routes);
+}
+
+/// Replace the Route headers of `msg` with `routes`, keeping
+/// them at the position of the first Route header.
+fn set_routes(msg: &mut SipMessage, routes: Vec<RouteHeader>) {
+    let headers = &mut msg.headers_mut().0;
+    let index = headers
+        .iter()
+        .position(|header| matches!(header, Header::Route(_)))
+        .unwrap_or(headers.len());
+    headers.retain(|header| !matches!(header, Header::Route(_)));
+    let index = index.min(headers.len());
+    headers.splice(index..index, routes.into_iter().map(Header::Route));
+}
+
+/// Strip the uri parameters that are not allowed in a Request-URI.
+/// [RFC3261: Section 19.1.1](https://tools.ietf.org/html/rfc3261#section-19.1.1)
+fn request_uri(mut uri: Uri) -> Uri {
+    uri.parameters
+        .retain(|param| !matches!(param, UriParam::Method(_)));
+    uri
+}
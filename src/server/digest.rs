@@ -0,0 +1,353 @@
+use sha::{
+    sha256::Sha256,
+    utils::{Digest, DigestExt},
+};
+
+use std::{
+    collections::HashMap,
+    io::Result as IoResult,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{core::generate_tag, *};
+
+/// The credentials a `CredentialStore` holds for a user.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Credential {
+    /// The plaintext password.
+    Password(String),
+    /// The precomputed hash of `username:realm:password`, it must use the
+    /// same algorithm as the `DigestVerifier`.
+    Ha1(String),
+}
+
+/// Used by `DigestVerifier` to look up the credentials of a user.
+pub trait CredentialStore {
+    /// Find the credentials of `username` within `realm`.
+    fn lookup(&self, username: &str, realm: &str) -> Option<Credential>;
+}
+
+/// A map from username to credentials, ignoring the realm.
+impl CredentialStore for HashMap<String, Credential> {
+    fn lookup(&self, username: &str, _realm: &str) -> Option<Credential> {
+        self.get(username).cloned()
+    }
+}
+
+/// The outcome of verifying the credentials of a request.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Verification {
+    /// The credentials are valid, holds the authenticated username.
+    Authorized(String),
+    /// The credentials were valid for a nonce that has expired. The
+    /// request should be challenged again with `stale=true`.
+    Stale,
+    /// The nonce count was used before, or the nonce was used before
+    /// without qop, the request is a replay.
+    Replay,
+    /// No credentials for this realm, or the credentials are wrong.
+    Unauthorized,
+}
+
+/// Server side digest authentication as described in
+/// [RFC3261: Section 22.4](https://tools.ietf.org/html/rfc3261#section-22.4)
+/// and [RFC7616](https://tools.ietf.org/html/rfc7616).
+///
+/// Nonces are stateless: each one carries the time it was issued and a
+/// random salt, signed with HMAC-SHA-256 using a secret only this verifier
+/// knows. The only state kept is the highest nonce count seen for each
+/// nonce, used to reject replayed requests. A nonce used without qop has
+/// no count, so it is only accepted once.
+pub struct DigestVerifier<S: CredentialStore> {
+    realm: String,
+    store: S,
+    secret: Vec<u8>,
+    algorithm: DigestAlgorithm,
+    qop: Vec<String>,
+    nonce_lifetime: Duration,
+    proxy: bool,
+    counts: HashMap<String, u32>,
+}
+
+impl<S: CredentialStore> DigestVerifier<S> {
+    /// Create a verifier for `realm` with a random secret, offering
+    /// `qop=auth` and nonces valid for five minutes.
+    pub fn new<R: Into<String>>(realm: R, store: S) -> DigestVerifier<S> {
+        DigestVerifier {
+            realm: realm.into(),
+            store,
+            secret: rand::random::<[u8; 32]>().to_vec(),
+            algorithm: DigestAlgorithm::default(),
+            qop: vec!["auth".into()],
+            nonce_lifetime: Duration::from_secs(300),
+            proxy: false,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Set the secret used to sign nonces. Servers sharing a secret
+    /// accept each other's nonces.
+    pub fn secret(mut self, secret: &[u8]) -> DigestVerifier<S> {
+        self.secret = secret.to_vec();
+        self
+    }
+
+    /// Set the algorithm offered in challenges.
+    pub fn algorithm(mut self, algorithm: DigestAlgorithm) -> DigestVerifier<S> {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Set the qop values offered in challenges, an empty list
+    /// offers the RFC 2069 digest without qop.
+    pub fn qop(mut self, qop: Vec<String>) -> DigestVerifier<S> {
+        self.qop = qop;
+        self
+    }
+
+    /// Set how long a nonce is valid for.
+    pub fn nonce_lifetime(mut self, lifetime: Duration) -> DigestVerifier<S> {
+        self.nonce_lifetime = lifetime;
+        self
+    }
+
+    /// Challenge with Proxy-Authenticate and verify Proxy-Authorization
+    /// headers, as a proxy does, instead of the WWW variants.
+    pub fn proxy(mut self, proxy: bool) -> DigestVerifier<S> {
+        self.proxy = proxy;
+        self
+    }
+
+    /// Retrieve the realm of this verifier.
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    /// Retrieve the credential store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Retrieve a mutable reference to the credential store.
+    pub fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    /// Generate a new nonce issued at `now`.
+    pub fn nonce(&self, now: SystemTime) -> String {
+        let timestamp = unix_time(now);
+        let salt = rand::random::<u64>();
+        let data = format!("{:016x}{:016x}", timestamp, salt);
+        let signature = hmac_sha256(&self.secret, data.as_bytes());
+        format!("{}{}", data, signature)
+    }
+
+    /// Generate a challenge header with a fresh nonce. `stale`
+    /// signals the client to retry with the new nonce without
+    /// asking the user for new credentials.
+    pub fn challenge(&self, now: SystemTime, stale: bool) -> Header {
+        let mut map = AuthParams::new();
+        map.insert("realm".to_string(), self.realm.clone());
+        map.insert("nonce".to_string(), self.nonce(now));
+        map.insert("algorithm".to_string(), self.algorithm.to_string());
+        if !self.qop.is_empty() {
+            map.insert("qop".to_string(), self.qop.join(","));
+        }
+        if stale {
+            map.insert("stale".to_string(), "true".to_string());
+        }
+        let auth = AuthHeader(AuthSchema::Digest, map);
+        if self.proxy {
+            Header::ProxyAuthenticate(auth)
+        } else {
+            Header::WwwAuthenticate(auth)
+        }
+    }
+
+    /// Generate the 401, or 407 for a proxy, response challenging `request`.
+    pub fn challenge_response(
+        &self,
+        request: &SipMessage,
+        now: SystemTime,
+        stale: bool,
+    ) -> IoResult<SipMessage> {
+        let code = if self.proxy { 407 } else { 401 };
+        let mut res = ResponseGenerator::from_request(request)
+            .code(code)
+            .header(self.challenge(now, stale))
+            .header(Header::ContentLength(0))
+            .build()?;
+        if let Ok(to) = res.to_header_mut() {
+            if !to.parameters.contains_key("tag") {
+                to.set_param("tag", Some(generate_tag()));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Verify the Authorization, or Proxy-Authorization for a proxy,
+    /// header of `request` that matches the realm of this verifier.
+    pub fn verify(&mut self, request: &SipMessage, now: SystemTime) -> Verification {
+        let (method, request_uri) = match request {
+            SipMessage::Request { method, uri, .. } => (method, uri),
+            SipMessage::Response { .. } => return Verification::Unauthorized,
+        };
+        let params = match self.credentials(request) {
+            Some(params) => params,
+            None => return Verification::Unauthorized,
+        };
+        let get = |key: &str| params.get(key).map(String::as_str);
+        let (username, nonce, uri, response) =
+            match (get("username"), get("nonce"), get("uri"), get("response")) {
+                (Some(username), Some(nonce), Some(uri), Some(response)) => {
+                    (username, nonce, uri, response)
+                },
+                _ => return Verification::Unauthorized,
+            };
+        if !same_uri(uri, request_uri) {
+            return Verification::Unauthorized;
+        }
+        let algorithm = match get("algorithm").map(str::parse::<DigestAlgorithm>) {
+            None => DigestAlgorithm::default(),
+            Some(Ok(alg)) if alg == self.algorithm => alg,
+            Some(_) => return Verification::Unauthorized,
+        };
+        let qop = get("qop");
+        if qop.is_some() && !self.qop.iter().any(|item| Some(item.as_str()) == qop) {
+            return Verification::Unauthorized;
+        }
+        let nc = match (qop, get("nc").map(|nc| u32::from_str_radix(nc, 16))) {
+            (None, _) => 0,
+            (Some(_), Some(Ok(nc))) => nc,
+            (Some(_), _) => return Verification::Unauthorized,
+        };
+        let issued = match self.nonce_timestamp(nonce) {
+            Some(issued) => issued,
+            None => return Verification::Unauthorized,
+        };
+        let method = format!("{}", method);
+        let input = DigestInput {
+            algorithm,
+            username,
+            realm: &self.realm,
+            password: "",
+            nonce,
+            method: &method,
+            uri,
+            qop,
+            nc,
+            cnonce: get("cnonce").unwrap_or_default(),
+            body: request.body(),
+        };
+        let ha1 = match self.store.lookup(username, &self.realm) {
+            Some(Credential::Password(password)) => DigestInput {
+                password: &password,
+                ..input.clone()
+            }
+            .ha1(),
+            Some(Credential::Ha1(ha1)) => ha1,
+            None => return Verification::Unauthorized,
+        };
+        if !constant_time_eq(
+            input.response_with_ha1(&ha1).as_bytes(),
+            response.as_bytes(),
+        ) {
+            return Verification::Unauthorized;
+        }
+        if unix_time(now).saturating_sub(issued) > self.nonce_lifetime.as_secs() {
+            self.counts.remove(nonce);
+            return Verification::Stale;
+        }
+        // Without qop there is no nonce count, so each nonce is only
+        // accepted once.
+        let nc = if qop.is_some() { nc } else { u32::MAX };
+        let last = self.counts.entry(nonce.to_string()).or_insert(0);
+        if nc <= *last {
+            return Verification::Replay;
+        }
+        *last = nc;
+        Verification::Authorized(username.to_string())
+    }
+
+    /// Forget the nonce counts of nonces that have expired at `now`.
+    pub fn purge(&mut self, now: SystemTime) {
+        let now = unix_time(now);
+        let lifetime = self.nonce_lifetime.as_secs();
+        let secret = &self.secret;
+        self.counts
+            .retain(|nonce, _| match verify_nonce(secret, nonce) {
+                Some(issued) => now.saturating_sub(issued) <= lifetime,
+                None => false,
+            });
+    }
+
+    /// The parameters of the credentials for this realm.
+    fn credentials<'a>(&self, request: &'a SipMessage) -> Option<&'a AuthParams> {
+        request.headers().iter().find_map(|header| {
+            let auth = match (header, self.proxy) {
+                (Header::Authorization(auth), false) => auth,
+                (Header::ProxyAuthorization(auth), true) => auth,
+                _ => return None,
+            };
+            match auth.1.get("realm") {
+                Some(realm) if realm == &self.realm => Some(&auth.1),
+                _ => None,
+            }
+        })
+    }
+
+    /// The time `nonce` was issued, if it was signed by this verifier.
+    fn nonce_timestamp(&self, nonce: &str) -> Option<u64> {
+        verify_nonce(&self.secret, nonce)
+    }
+}
+
+/// Compare the `uri` of the credentials with the Request-URI, they must
+/// name the same resource as described in
+/// [RFC2617: Section 3.2.2.5](https://tools.ietf.org/html/rfc2617#section-3.2.2.5).
+fn same_uri(uri: &str, request_uri: &Uri) -> bool {
+    match parse_uri::<(&[u8], nom::error::ErrorKind)>(uri.as_bytes()) {
+        Ok((b"", uri)) => &uri == request_uri,
+        _ => uri == request_uri.to_string(),
+    }
+}
+
+fn verify_nonce(secret: &[u8], nonce: &str) -> Option<u64> {
+    if nonce.len() != 96 || !nonce.is_ascii() {
+        return None;
+    }
+    let (data, signature) = nonce.split_at(32);
+    if !constant_time_eq(
+        hmac_sha256(secret, data.as_bytes()).as_bytes(),
+        signature.as_bytes(),
+    ) {
+        return None;
+    }
+    u64::from_str_radix(&data[..16], 16).ok()
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// HMAC-SHA-256 of `data`, [RFC2104](https://tools.ietf.org/html/rfc2104).
+fn hmac_sha256(key: &[u8], data: &[u8]) -> String {
+    const BLOCK_SIZE: usize = 64;
+    let mut key = if key.len() > BLOCK_SIZE {
+        Sha256::default().digest(key).to_bytes()
+    } else {
+        key.to_vec()
+    };
+    key.resize(BLOCK_SIZE, 0);
+    let mut inner: Vec<u8> = key.iter().map(|byte| byte ^ 0x36).collect();
+    inner.extend_from_slice(data);
+    let mut outer: Vec<u8> = key.iter().map(|byte| byte ^ 0x5c).collect();
+    outer.extend(Sha256::default().digest(&inner).to_bytes());
+    Sha256::default().digest(&outer).to_hex()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
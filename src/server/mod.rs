@@ -0,0 +1,8 @@
+//! The server module contains the code used by SIP servers
+//! such as registrars, proxies and back-to-back user agents.
+
+mod digest;
+pub use self::digest::{Credential, CredentialStore, DigestVerifier, Verification};
+
+mod registrar;
+pub use self::registrar::{address_of_record, Binding, LocationService, Registrar};
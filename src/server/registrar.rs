@@ -0,0 +1,256 @@
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    time::{Duration, Instant},
+};
+
+use crate::{core::generate_tag, *};
+
+/// A contact address bound to an address of record.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Binding {
+    /// The contact as it was registered, without it's `expires` parameter.
+    pub contact: ContactHeader,
+    /// The Call-ID of the REGISTER request that last updated this binding.
+    pub call_id: String,
+    /// The CSeq of the REGISTER request that last updated this binding.
+    pub cseq: u32,
+    /// When this binding expires.
+    pub expires_at: Instant,
+}
+
+impl Binding {
+    /// The preference of this contact, bindings without a `q` parameter
+    /// are treated as having the highest preference.
+    pub fn q(&self) -> f32 {
+        self.contact
+            .parameters
+            .get("q")
+            .and_then(|q| q.as_ref())
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0)
+    }
+
+    /// The number of seconds until this binding expires.
+    pub fn remaining(&self, now: Instant) -> u32 {
+        self.expires_at.saturating_duration_since(now).as_secs() as u32
+    }
+
+    /// Determine if this binding has expired at `now`.
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at <= now
+    }
+}
+
+/// The location service used by a `Registrar` to store bindings, as
+/// described in [RFC3261: Section 10.3](https://tools.ietf.org/html/rfc3261#section-10.3).
+pub trait LocationService {
+    /// The bindings stored for the address of record `aor`.
+    fn bindings(&self, aor: &str) -> Vec<Binding>;
+
+    /// Replace the bindings of `aor`, an empty list removes the address
+    /// of record.
+    fn set_bindings(&mut self, aor: &str, bindings: Vec<Binding>);
+}
+
+/// A location service held in memory.
+impl LocationService for HashMap<String, Vec<Binding>> {
+    fn bindings(&self, aor: &str) -> Vec<Binding> {
+        self.get(aor).cloned().unwrap_or_default()
+    }
+
+    fn set_bindings(&mut self, aor: &str, bindings: Vec<Binding>) {
+        if bindings.is_empty() {
+            self.remove(aor);
+        } else {
+            self.insert(aor.to_string(), bindings);
+        }
+    }
+}
+
+/// Processes REGISTER requests as described in
+/// [RFC3261: Section 10.3](https://tools.ietf.org/html/rfc3261#section-10.3).
+/// Requests should be authenticated, with a `DigestVerifier` for example,
+/// before they are passed to the registrar.
+pub struct Registrar<L: LocationService> {
+    location: L,
+    min_expires: u32,
+    max_expires: u32,
+    default_expires: u32,
+}
+
+impl<L: LocationService> Registrar<L> {
+    /// Create a registrar storing it's bindings in `location`.
+    pub fn new(location: L) -> Registrar<L> {
+        Registrar {
+            location,
+            min_expires: 60,
+            max_expires: 86400,
+            default_expires: 3600,
+        }
+    }
+
+    /// Set the shortest registration interval accepted, shorter ones
+    /// are rejected with `423 Interval Too Brief`.
+    pub fn min_expires(mut self, seconds: u32) -> Registrar<L> {
+        self.min_expires = seconds;
+        self
+    }
+
+    /// Set the longest registration interval granted, longer ones are
+    /// shortened to it.
+    pub fn max_expires(mut self, seconds: u32) -> Registrar<L> {
+        self.max_expires = seconds;
+        self
+    }
+
+    /// Set the interval granted when a request doesn't ask for one.
+    pub fn default_expires(mut self, seconds: u32) -> Registrar<L> {
+        self.default_expires = seconds;
+        self
+    }
+
+    /// Retrieve the location service.
+    pub fn location(&self) -> &L {
+        &self.location
+    }
+
+    /// Retrieve the location service mutably.
+    pub fn location_mut(&mut self) -> &mut L {
+        &mut self.location
+    }
+
+    /// The unexpired bindings of `aor`, most preferred first.
+    pub fn lookup(&self, aor: &Uri, now: Instant) -> Vec<Binding> {
+        let mut bindings: Vec<Binding> = self
+            .location
+            .bindings(&address_of_record(aor))
+            .into_iter()
+            .filter(|binding| !binding.is_expired(now))
+            .collect();
+        bindings.sort_by(|a, b| {
+            b.q()
+                .partial_cmp(&a.q())
+                .unwrap_or(::std::cmp::Ordering::Equal)
+        });
+        bindings
+    }
+
+    /// Apply the REGISTER `request` received at `now` and build the
+    /// response to send. A 200 response lists every binding of the
+    /// address of record with the seconds it has left, a request that
+    /// is malformed, out of order or asks for too short an interval
+    /// leaves the bindings unchanged and is rejected.
+    pub fn process(&mut self, request: &SipMessage, now: Instant) -> IoResult<SipMessage> {
+        if request.method()? != Method::Register {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "registrars only process REGISTER requests",
+            ));
+        }
+        let aor = address_of_record(&request.to_header()?.uri);
+        let call_id = request.call_id()?.clone();
+        let (cseq, _) = request.cseq()?;
+        let expires = request.expires_header().ok();
+        let contacts: Vec<&ContactHeader> = request
+            .headers()
+            .iter()
+            .filter_map(|header| match header {
+                Header::Contact(contact) => Some(contact),
+                _ => None,
+            })
+            .collect();
+
+        let mut bindings: Vec<Binding> = self
+            .location
+            .bindings(&aor)
+            .into_iter()
+            .filter(|binding| !binding.is_expired(now))
+            .collect();
+
+        if contacts.iter().any(|contact| contact.is_wildcard()) {
+            if contacts.len() != 1 || expires != Some(0) {
+                return response(request, 400, vec![]);
+            }
+            for binding in &bindings {
+                if binding.call_id == call_id && binding.cseq >= cseq {
+                    return response(request, 500, vec![]);
+                }
+            }
+            bindings.clear();
+        }
+
+        for contact in contacts.iter().filter(|contact| !contact.is_wildcard()) {
+            let requested = match contact.parameters.get("expires") {
+                Some(Some(value)) => match value.parse::<u32>() {
+                    Ok(value) => value,
+                    Err(_) => return response(request, 400, vec![]),
+                },
+                _ => expires.unwrap_or(self.default_expires),
+            };
+            if requested != 0 && requested < self.min_expires {
+                let min_expires = Header::MinExpires(self.min_expires);
+                return response(request, 423, vec![min_expires]);
+            }
+            let existing = bindings
+                .iter()
+                .position(|binding| binding.contact.uri == contact.uri);
+            if let Some(index) = existing {
+                let binding = &bindings[index];
+                if binding.call_id == call_id && binding.cseq >= cseq {
+                    return response(request, 500, vec![]);
+                }
+                bindings.remove(index);
+            }
+            if requested == 0 {
+                continue;
+            }
+            let mut contact = (*contact).clone();
+            contact.parameters.remove("expires");
+            let granted = requested.min(self.max_expires);
+            bindings.push(Binding {
+                contact,
+                call_id: call_id.clone(),
+                cseq,
+                expires_at: now + Duration::from_secs(granted as u64),
+            });
+        }
+
+        let headers = bindings
+            .iter()
+            .map(|binding| {
+                let contact = binding
+                    .contact
+                    .clone()
+                    .param("expires", Some(binding.remaining(now).to_string()));
+                Header::Contact(contact)
+            })
+            .collect();
+        self.location.set_bindings(&aor, bindings);
+        response(request, 200, headers)
+    }
+}
+
+/// The address of record of `uri` in it's canonical form, the user and
+/// host without any parameters or headers.
+pub fn address_of_record(uri: &Uri) -> String {
+    let mut aor = Uri::new(uri.schema.unwrap_or(UriSchema::Sip), uri.host.clone());
+    if let Some(auth) = &uri.auth {
+        aor = aor.auth(UriAuth::new(auth.username.clone()));
+    }
+    format!("{}", aor)
+}
+
+fn response(request: &SipMessage, code: u32, headers: Vec<Header>) -> IoResult<SipMessage> {
+    let mut res = ResponseGenerator::from_request(request)
+        .code(code)
+        .headers(headers)
+        .header(Header::ContentLength(0))
+        .build()?;
+    if let Ok(to) = res.to_header_mut() {
+        if !to.parameters.contains_key("tag") {
+            to.set_param("tag", Some(generate_tag()));
+        }
+    }
+    Ok(res)
+}
@@ -0,0 +1,285 @@
+use crate::{
+    transaction::{Timer, TimerConfig, Timers, TransactionEvent, TransactionKey, TransactionState},
+    Header, Method, RequestGenerator, SipMessage, SipMessageError, SipMessageExt,
+};
+
+use std::{
+    io::Result as IoResult,
+    time::{Duration, Instant},
+};
+
+/// Generate the ACK for a non-2xx final response to an INVITE as described in
+/// [RFC3261: Section 17.1.1.3](https://tools.ietf.org/html/rfc3261#section-17.1.1.3).
+/// The ACK reuses the INVITE's Request-URI, top Via (and so it's branch), Call-ID,
+/// From and Route headers, and takes the To header from the response.
+pub fn non_2xx_ack(invite: &SipMessage, response: &SipMessage) -> IoResult<SipMessage> {
+    let uri = match invite {
+        SipMessage::Request { uri, .. } => uri.clone(),
+        SipMessage::Response { .. } => return Err(SipMessageError::MissingMethod.into()),
+    };
+    let (cseq, _) = invite.cseq()?;
+    let mut req = RequestGenerator::new()
+        .method(Method::Ack)
+        .uri(uri)
+        .header(Header::Via(invite.via_header()?.clone()))
+        .header(Header::From(invite.from_header()?.clone()))
+        .header(Header::To(response.to_header()?.clone()))
+        .header(Header::CallId(invite.call_id()?.clone()))
+        .header(Header::CSeq(cseq, Method::Ack));
+    for header in invite.headers().iter() {
+        if let Header::Route(_) = header {
+            req = req.header(header.clone());
+        }
+    }
+    req.header(Header::MaxForwards(70))
+        .header(Header::ContentLength(0))
+        .build()
+}
+
+fn is_reliable(request: &SipMessage) -> IoResult<bool> {
+    Ok(request.via_header()?.transport.is_reliable())
+}
+
+/// The INVITE client transaction state machine,
+/// [RFC3261: Section 17.1.1](https://tools.ietf.org/html/rfc3261#section-17.1.1).
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClientInviteTransaction {
+    key: TransactionKey,
+    state: TransactionState,
+    request: SipMessage,
+    ack: Option<SipMessage>,
+    reliable: bool,
+    config: TimerConfig,
+    timers: Timers,
+    interval: Duration,
+}
+
+impl ClientInviteTransaction {
+    /// Create a new transaction in the Calling state for an INVITE that is
+    /// being sent at `now`. Sending the request the first time is the
+    /// caller's responsibility.
+    pub fn new(
+        request: SipMessage,
+        config: TimerConfig,
+        now: Instant,
+    ) -> IoResult<ClientInviteTransaction> {
+        let key = TransactionKey::from_message(&request)?;
+        let reliable = is_reliable(&request)?;
+        let mut timers = Timers::default();
+        if !reliable {
+            timers.start(Timer::A, now + config.t1);
+        }
+        timers.start(Timer::B, now + config.transaction_timeout());
+        Ok(ClientInviteTransaction {
+            key,
+            state: TransactionState::Calling,
+            request,
+            ack: None,
+            reliable,
+            config,
+            timers,
+            interval: config.t1,
+        })
+    }
+
+    /// Retrieve the key identifying this transaction.
+    pub fn key(&self) -> &TransactionKey {
+        &self.key
+    }
+
+    /// Retrieve the current state of this transaction.
+    pub fn state(&self) -> TransactionState {
+        self.state
+    }
+
+    /// Retrieve the INVITE request this transaction was created for.
+    pub fn request(&self) -> &SipMessage {
+        &self.request
+    }
+
+    /// The instant `on_timer` should next be called.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        self.timers.next()
+    }
+
+    /// Process a response matching this transaction.
+    pub fn on_response(&mut self, response: SipMessage, now: Instant) -> Vec<TransactionEvent> {
+        let code = match response.status_code() {
+            Ok(code) => code,
+            Err(_) => return vec![],
+        };
+        match (self.state, code) {
+            (TransactionState::Calling, 100..=199) | (TransactionState::Proceeding, 100..=199) => {
+                self.timers.cancel(Timer::A);
+                self.state = TransactionState::Proceeding;
+                vec![TransactionEvent::Deliver(response)]
+            },
+            (TransactionState::Calling, 200..=299) | (TransactionState::Proceeding, 200..=299) => {
+                let mut events = vec![TransactionEvent::Deliver(response)];
+                events.extend(self.terminate());
+                events
+            },
+            (TransactionState::Calling, _) | (TransactionState::Proceeding, _) => {
+                let ack = match non_2xx_ack(&self.request, &response) {
+                    Ok(ack) => ack,
+                    Err(_) => return vec![],
+                };
+                self.timers.clear();
+                self.timers
+                    .start(Timer::D, now + self.config.timer_d(self.reliable));
+                self.state = TransactionState::Completed;
+                self.ack = Some(ack.clone());
+                vec![
+                    TransactionEvent::Send(ack),
+                    TransactionEvent::Deliver(response),
+                ]
+            },
+            (TransactionState::Completed, 300..=699) => match &self.ack {
+                Some(ack) => vec![TransactionEvent::Send(ack.clone())],
+                None => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Fire every timer that expired at or before `now`.
+    pub fn on_timer(&mut self, now: Instant) -> Vec<TransactionEvent> {
+        let mut events = vec![];
+        while let Some((timer, deadline)) = self.timers.pop_expired(now) {
+            match timer {
+                Timer::A => {
+                    self.interval *= 2;
+                    self.timers.start(Timer::A, deadline + self.interval);
+                    events.push(TransactionEvent::Send(self.request.clone()));
+                },
+                Timer::B => {
+                    events.push(TransactionEvent::Timeout(self.key.clone()));
+                    events.extend(self.terminate());
+                },
+                Timer::D => events.extend(self.terminate()),
+                _ => {},
+            }
+        }
+        events
+    }
+
+    fn terminate(&mut self) -> Vec<TransactionEvent> {
+        self.timers.clear();
+        self.state = TransactionState::Terminated;
+        vec![TransactionEvent::Terminated(self.key.clone())]
+    }
+}
+
+/// The non-INVITE client transaction state machine,
+/// [RFC3261: Section 17.1.2](https://tools.ietf.org/html/rfc3261#section-17.1.2).
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClientNonInviteTransaction {
+    key: TransactionKey,
+    state: TransactionState,
+    request: SipMessage,
+    reliable: bool,
+    config: TimerConfig,
+    timers: Timers,
+    interval: Duration,
+}
+
+impl ClientNonInviteTransaction {
+    /// Create a new transaction in the Trying state for a request that is
+    /// being sent at `now`. Sending the request the first time is the
+    /// caller's responsibility.
+    pub fn new(
+        request: SipMessage,
+        config: TimerConfig,
+        now: Instant,
+    ) -> IoResult<ClientNonInviteTransaction> {
+        let key = TransactionKey::from_message(&request)?;
+        let reliable = is_reliable(&request)?;
+        let mut timers = Timers::default();
+        if !reliable {
+            timers.start(Timer::E, now + config.t1);
+        }
+        timers.start(Timer::F, now + config.transaction_timeout());
+        Ok(ClientNonInviteTransaction {
+            key,
+            state: TransactionState::Trying,
+            request,
+            reliable,
+            config,
+            timers,
+            interval: config.t1,
+        })
+    }
+
+    /// Retrieve the key identifying this transaction.
+    pub fn key(&self) -> &TransactionKey {
+        &self.key
+    }
+
+    /// Retrieve the current state of this transaction.
+    pub fn state(&self) -> TransactionState {
+        self.state
+    }
+
+    /// Retrieve the request this transaction was created for.
+    pub fn request(&self) -> &SipMessage {
+        &self.request
+    }
+
+    /// The instant `on_timer` should next be called.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        self.timers.next()
+    }
+
+    /// Process a response matching this transaction.
+    pub fn on_response(&mut self, response: SipMessage, now: Instant) -> Vec<TransactionEvent> {
+        let code = match response.status_code() {
+            Ok(code) => code,
+            Err(_) => return vec![],
+        };
+        match (self.state, code) {
+            (TransactionState::Trying, 100..=199) | (TransactionState::Proceeding, 100..=199) => {
+                self.state = TransactionState::Proceeding;
+                vec![TransactionEvent::Deliver(response)]
+            },
+            (TransactionState::Trying, _) | (TransactionState::Proceeding, _) => {
+                self.timers.clear();
+                self.timers
+                    .start(Timer::K, now + self.config.timer_t4(self.reliable));
+                self.state = TransactionState::Completed;
+                vec![TransactionEvent::Deliver(response)]
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Fire every timer that expired at or before `now`.
+    pub fn on_timer(&mut self, now: Instant) -> Vec<TransactionEvent> {
+        let mut events = vec![];
+        while let Some((timer, deadline)) = self.timers.pop_expired(now) {
+            match timer {
+                Timer::E => {
+                    self.interval = if self.state == TransactionState::Proceeding {
+                        self.config.t2
+                    } else {
+                        (self.interval * 2).min(self.config.t2)
+                    };
+                    self.timers.start(Timer::E, deadline + self.interval);
+                    events.push(TransactionEvent::Send(self.request.clone()));
+                },
+                Timer::F => {
+                    events.push(TransactionEvent::Timeout(self.key.clone()));
+                    events.extend(self.terminate());
+                },
+                Timer::K => events.extend(self.terminate()),
+                _ => {},
+            }
+        }
+        events
+    }
+
+    fn terminate(&mut self) -> Vec<TransactionEvent> {
+        self.timers.clear();
+        self.state = TransactionState::Terminated;
+        vec![TransactionEvent::Terminated(self.key.clone())]
+    }
+}
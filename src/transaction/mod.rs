@@ -0,0 +1,332 @@
+//! The transaction module implements the four transaction state machines
+//! described in [RFC3261: Section 17](https://tools.ietf.org/html/rfc3261#section-17).
+//!
+//! Transactions never touch the network or the system clock. Every method
+//! takes the current `Instant` and returns a list of `TransactionEvent`'s
+//! describing the messages to send and the messages to pass up to the
+//! transaction user. Call `on_timer` whenever `next_timeout` has elapsed.
+
+mod timer;
+pub(crate) use self::timer::Timers;
+pub use self::timer::{Timer, TimerConfig};
+
+mod client;
+pub use self::client::{non_2xx_ack, ClientInviteTransaction, ClientNonInviteTransaction};
+
+mod server;
+pub use self::server::{trying_response, ServerInviteTransaction, ServerNonInviteTransaction};
+
+use crate::{Domain, Method, SipMessage, SipMessageError, SipMessageExt};
+
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    time::Instant,
+};
+
+/// Identifies a transaction, made up of the branch parameter of the top
+/// Via header and the CSeq method. ACK requests are mapped to the INVITE
+/// transaction they acknowledge.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct TransactionKey {
+    pub branch: String,
+    pub method: Method,
+}
+
+impl TransactionKey {
+    /// Create a new key from it's parts.
+    pub fn new<S: Into<String>>(branch: S, method: Method) -> TransactionKey {
+        let method = if method == Method::Ack {
+            Method::Invite
+        } else {
+            method
+        };
+        TransactionKey {
+            branch: branch.into(),
+            method,
+        }
+    }
+
+    /// Compute the key a request or response belongs to.
+    pub fn from_message(msg: &SipMessage) -> Result<TransactionKey, SipMessageError> {
+        let branch = msg.via_header_branch()?;
+        let (_, method) = msg.cseq()?;
+        Ok(TransactionKey::new(branch.clone(), method))
+    }
+}
+
+/// Server transactions are also matched on the sent-by value of the top
+/// Via header, so two clients picking the same branch don't collide
+/// ([RFC3261: Section 17.2.3](https://tools.ietf.org/html/rfc3261#section-17.2.3)).
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct ServerKey {
+    key: TransactionKey,
+    sent_by: Domain,
+}
+
+impl ServerKey {
+    fn from_message(msg: &SipMessage) -> Result<ServerKey, SipMessageError> {
+        Ok(ServerKey {
+            key: TransactionKey::from_message(msg)?,
+            sent_by: msg.via_header()?.sent_by.clone(),
+        })
+    }
+}
+
+/// The states a transaction can be in. Not every
+/// state is used by every kind of transaction.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TransactionState {
+    Calling,
+    Trying,
+    Proceeding,
+    Completed,
+    Confirmed,
+    Terminated,
+}
+
+/// Output of the transaction state machines.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TransactionEvent {
+    /// This message must be handed to the transport layer.
+    Send(SipMessage),
+    /// This message must be passed up to the transaction user.
+    Deliver(SipMessage),
+    /// Timer B, F or H fired before the transaction completed.
+    Timeout(TransactionKey),
+    /// The transaction reached the Terminated state and can be dropped.
+    Terminated(TransactionKey),
+}
+
+/// Any one of the four transaction kinds.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Transaction {
+    ClientInvite(ClientInviteTransaction),
+    ClientNonInvite(ClientNonInviteTransaction),
+    ServerInvite(ServerInviteTransaction),
+    ServerNonInvite(ServerNonInviteTransaction),
+}
+
+macro_rules! dispatch {
+    ($self:expr, $tx:ident => $expr:expr) => {
+        match $self {
+            Transaction::ClientInvite($tx) => $expr,
+            Transaction::ClientNonInvite($tx) => $expr,
+            Transaction::ServerInvite($tx) => $expr,
+            Transaction::ServerNonInvite($tx) => $expr,
+        }
+    };
+}
+
+impl Transaction {
+    /// Retrieve the key identifying this transaction.
+    pub fn key(&self) -> &TransactionKey {
+        dispatch!(self, tx => tx.key())
+    }
+
+    /// Retrieve the current state of this transaction.
+    pub fn state(&self) -> TransactionState {
+        dispatch!(self, tx => tx.state())
+    }
+
+    /// The instant `on_timer` should next be called.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        dispatch!(self, tx => tx.next_timeout())
+    }
+
+    /// Fire every timer that expired at or before `now`.
+    pub fn on_timer(&mut self, now: Instant) -> Vec<TransactionEvent> {
+        dispatch!(self, tx => tx.on_timer(now))
+    }
+
+    /// Determine if this is a client transaction.
+    pub fn is_client(&self) -> bool {
+        matches!(
+            self,
+            Transaction::ClientInvite(_) | Transaction::ClientNonInvite(_)
+        )
+    }
+}
+
+/// Owns every running transaction and routes messages between
+/// the transport and the transaction user. Client and server transactions
+/// are kept apart, a request reusing the branch of a request we sent
+/// starts a new server transaction.
+#[derive(Debug, Default)]
+pub struct TransactionLayer {
+    config: TimerConfig,
+    client: HashMap<TransactionKey, Transaction>,
+    server: HashMap<ServerKey, Transaction>,
+}
+
+impl TransactionLayer {
+    /// Create a new TransactionLayer using the given timer values.
+    pub fn new(config: TimerConfig) -> TransactionLayer {
+        TransactionLayer {
+            config,
+            client: HashMap::new(),
+            server: HashMap::new(),
+        }
+    }
+
+    /// Retrieve the timer configuration.
+    pub fn config(&self) -> &TimerConfig {
+        &self.config
+    }
+
+    /// Look up a running transaction, client transactions are
+    /// searched first.
+    pub fn get(&self, key: &TransactionKey) -> Option<&Transaction> {
+        self.client.get(key).or_else(|| {
+            self.server
+                .iter()
+                .find(|(server, _)| &server.key == key)
+                .map(|(_, tx)| tx)
+        })
+    }
+
+    /// Number of transactions currently running.
+    pub fn len(&self) -> usize {
+        self.client.len() + self.server.len()
+    }
+
+    /// Determine if there are no running transactions.
+    pub fn is_empty(&self) -> bool {
+        self.client.is_empty() && self.server.is_empty()
+    }
+
+    /// Start a client transaction for `request`. The returned events
+    /// contain the initial transmission of the request.
+    pub fn send_request(
+        &mut self,
+        request: SipMessage,
+        now: Instant,
+    ) -> IoResult<Vec<TransactionEvent>> {
+        let method = request.method()?;
+        let tx = match method {
+            Method::Invite => Transaction::ClientInvite(ClientInviteTransaction::new(
+                request.clone(),
+                self.config,
+                now,
+            )?),
+            Method::Ack => {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidInput,
+                    "ACK requests are sent outside of a client transaction",
+                ))
+            },
+            _ => Transaction::ClientNonInvite(ClientNonInviteTransaction::new(
+                request.clone(),
+                self.config,
+                now,
+            )?),
+        };
+        self.client.insert(tx.key().clone(), tx);
+        Ok(vec![TransactionEvent::Send(request)])
+    }
+
+    /// Pass a message received from the transport layer to the transaction
+    /// it belongs to. Requests that don't match a transaction create a new
+    /// server transaction; responses that don't match are passed straight up
+    /// so the transaction user can handle retransmitted 2xx responses.
+    pub fn receive(&mut self, msg: SipMessage, now: Instant) -> IoResult<Vec<TransactionEvent>> {
+        let events = if msg.is_request() {
+            let key = ServerKey::from_message(&msg)?;
+            match self.server.get_mut(&key) {
+                Some(Transaction::ServerInvite(tx)) => tx.on_request(msg, now),
+                Some(Transaction::ServerNonInvite(tx)) => tx.on_request(msg, now),
+                _ => self.create_server_transaction(key, msg, now)?,
+            }
+        } else {
+            let key = TransactionKey::from_message(&msg)?;
+            match self.client.get_mut(&key) {
+                Some(Transaction::ClientInvite(tx)) => tx.on_response(msg, now),
+                Some(Transaction::ClientNonInvite(tx)) => tx.on_response(msg, now),
+                _ => vec![TransactionEvent::Deliver(msg)],
+            }
+        };
+        self.remove_terminated(&events);
+        Ok(events)
+    }
+
+    /// Send a response from the transaction user through the
+    /// server transaction it belongs to.
+    pub fn send_response(
+        &mut self,
+        response: SipMessage,
+        now: Instant,
+    ) -> IoResult<Vec<TransactionEvent>> {
+        let key = ServerKey::from_message(&response)?;
+        let events = match self.server.get_mut(&key) {
+            Some(Transaction::ServerInvite(tx)) => tx.send_response(response, now)?,
+            Some(Transaction::ServerNonInvite(tx)) => tx.send_response(response, now)?,
+            _ => {
+                return Err(IoError::new(
+                    IoErrorKind::NotFound,
+                    "No server transaction matches the response",
+                ))
+            },
+        };
+        self.remove_terminated(&events);
+        Ok(events)
+    }
+
+    /// The instant `on_timer` should next be called.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        self.client
+            .values()
+            .chain(self.server.values())
+            .filter_map(Transaction::next_timeout)
+            .min()
+    }
+
+    /// Fire every timer that expired at or before `now`.
+    pub fn on_timer(&mut self, now: Instant) -> Vec<TransactionEvent> {
+        let mut events = vec![];
+        for tx in self.client.values_mut().chain(self.server.values_mut()) {
+            events.extend(tx.on_timer(now));
+        }
+        self.remove_terminated(&events);
+        events
+    }
+
+    fn create_server_transaction(
+        &mut self,
+        key: ServerKey,
+        request: SipMessage,
+        now: Instant,
+    ) -> IoResult<Vec<TransactionEvent>> {
+        match request.method()? {
+            // An ACK that matches no transaction acknowledges a 2xx
+            // response and belongs to the transaction user.
+            Method::Ack => Ok(vec![TransactionEvent::Deliver(request)]),
+            Method::Invite => {
+                let mut tx = ServerInviteTransaction::new(request.clone(), self.config, now)?;
+                let mut events = tx.send_response(trying_response(&request)?, now)?;
+                events.push(TransactionEvent::Deliver(request));
+                self.server.insert(key, Transaction::ServerInvite(tx));
+                Ok(events)
+            },
+            _ => {
+                let tx = ServerNonInviteTransaction::new(request.clone(), self.config, now)?;
+                self.server.insert(key, Transaction::ServerNonInvite(tx));
+                Ok(vec![TransactionEvent::Deliver(request)])
+            },
+        }
+    }
+
+    fn remove_terminated(&mut self, events: &[TransactionEvent]) {
+        for event in events {
+            if let TransactionEvent::Terminated(key) = event {
+                if self.client.get(key).map(Transaction::state)
+                    == Some(TransactionState::Terminated)
+                {
+                    self.client.remove(key);
+                }
+                self.server.retain(|server, tx| {
+                    &server.key != key || tx.state() != TransactionState::Terminated
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{
+    transaction::{Timer, TimerConfig, Timers, TransactionEvent, TransactionKey, TransactionState},
+    Header, Method, ResponseGenerator, SipMessage, SipMessageError, SipMessageExt,
+};
+
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    time::{Duration, Instant},
+};
+
+/// Generate the 100 Trying response an INVITE server transaction sends
+/// on the transaction user's behalf. Via, From, To, Call-ID and CSeq
+/// are copied from the request along with any Timestamp
+/// ([RFC3261: Section 8.2.6.1](https://tools.ietf.org/html/rfc3261#section-8.2.6.1)).
+pub fn trying_response(request: &SipMessage) -> IoResult<SipMessage> {
+    if !request.is_request() {
+        return Err(SipMessageError::MissingMethod.into());
+    }
+    let mut res = ResponseGenerator::from_request(request).code(100);
+    for header in request.headers().iter() {
+        if let Header::Timestamp(_) = header {
+            res = res.header(header.clone());
+        }
+    }
+    res.header(Header::ContentLength(0)).build()
+}
+
+fn is_reliable(request: &SipMessage) -> IoResult<bool> {
+    Ok(request.via_header()?.transport.is_reliable())
+}
+
+fn invalid_state(state: TransactionState) -> IoError {
+    IoError::new(
+        IoErrorKind::InvalidInput,
+        format!("Cannot send a response in the {:?} state", state),
+    )
+}
+
+/// The INVITE server transaction state machine,
+/// [RFC3261: Section 17.2.1](https://tools.ietf.org/html/rfc3261#section-17.2.1).
+#[derive(Debug, PartialEq, Clone)]
+pub struct ServerInviteTransaction {
+    key: TransactionKey,
+    state: TransactionState,
+    request: SipMessage,
+    response: Option<SipMessage>,
+    reliable: bool,
+    config: TimerConfig,
+    timers: Timers,
+    interval: Duration,
+}
+
+impl ServerInviteTransaction {
+    /// Create a new transaction in the Proceeding state for
+    /// an INVITE received at `now`.
+    pub fn new(
+        request: SipMessage,
+        config: TimerConfig,
+        _now: Instant,
+    ) -> IoResult<ServerInviteTransaction> {
+        let key = TransactionKey::from_message(&request)?;
+        let reliable = is_reliable(&request)?;
+        Ok(ServerInviteTransaction {
+            key,
+            state: TransactionState::Proceeding,
+            request,
+            response: None,
+            reliable,
+            config,
+            timers: Timers::default(),
+            interval: config.t1,
+        })
+    }
+
+    /// Retrieve the key identifying this transaction.
+    pub fn key(&self) -> &TransactionKey {
+        &self.key
+    }
+
+    /// Retrieve the current state of this transaction.
+    pub fn state(&self) -> TransactionState {
+        self.state
+    }
+
+    /// Retrieve the INVITE request this transaction was created for.
+    pub fn request(&self) -> &SipMessage {
+        &self.request
+    }
+
+    /// The instant `on_timer` should next be called.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        self.timers.next()
+    }
+
+    /// Process a retransmitted INVITE or an ACK matching this transaction.
+    pub fn on_request(&mut self, request: SipMessage, now: Instant) -> Vec<TransactionEvent> {
+        match (self.state, request.method()) {
+            (TransactionState::Proceeding, Ok(Method::Invite))
+            | (TransactionState::Completed, Ok(Method::Invite)) => match &self.response {
+                Some(response) => vec![TransactionEvent::Send(response.clone())],
+                None => vec![],
+            },
+            (TransactionState::Completed, Ok(Method::Ack)) => {
+                self.timers.clear();
+                self.timers
+                    .start(Timer::I, now + self.config.timer_t4(self.reliable));
+                self.state = TransactionState::Confirmed;
+                vec![]
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Send a response from the transaction user.
+    pub fn send_response(
+        &mut self,
+        response: SipMessage,
+        now: Instant,
+    ) -> IoResult<Vec<TransactionEvent>> {
+        if self.state != TransactionState::Proceeding {
+            return Err(invalid_state(self.state));
+        }
+        let code = response.status_code()?;
+        self.response = Some(response.clone());
+        let mut events = vec![TransactionEvent::Send(response)];
+        match code {
+            100..=199 => {},
+            200..=299 => events.extend(self.terminate()),
+            _ => {
+                if !self.reliable {
+                    self.timers.start(Timer::G, now + self.config.t1);
+                }
+                self.timers
+                    .start(Timer::H, now + self.config.transaction_timeout());
+                self.state = TransactionState::Completed;
+            },
+        }
+        Ok(events)
+    }
+
+    /// Fire every timer that expired at or before `now`.
+    pub fn on_timer(&mut self, now: Instant) -> Vec<TransactionEvent> {
+        let mut events = vec![];
+        while let Some((timer, deadline)) = self.timers.pop_expired(now) {
+            match timer {
+                Timer::G => {
+                    self.interval = (self.interval * 2).min(self.config.t2);
+                    self.timers.start(Timer::G, deadline + self.interval);
+                    if let Some(response) = &self.response {
+                        events.push(TransactionEvent::Send(response.clone()));
+                    }
+                },
+                Timer::H => {
+                    events.push(TransactionEvent::Timeout(self.key.clone()));
+                    events.extend(self.terminate());
+                },
+                Timer::I => events.extend(self.terminate()),
+                _ => {},
+            }
+        }
+        events
+    }
+
+    fn terminate(&mut self) -> Vec<TransactionEvent> {
+        self.timers.clear();
+        self.state = TransactionState::Terminated;
+        vec![TransactionEvent::Terminated(self.key.clone())]
+    }
+}
+
+/// The non-INVITE server transaction state machine,
+/// [RFC3261: Section 17.2.2](https://tools.ietf.org/html/rfc3261#section-17.2.2).
+#[derive(Debug, PartialEq, Clone)]
+pub struct ServerNonInviteTransaction {
+    key: TransactionKey,
+    state: TransactionState,
+    request: SipMessage,
+    response: Option<SipMessage>,
+    reliable: bool,
+    config: TimerConfig,
+    timers: Timers,
+}
+
+impl ServerNonInviteTransaction {
+    /// Create a new transaction in the Trying state for
+    /// a request received at `now`.
+    pub fn new(
+        request: SipMessage,
+        config: TimerConfig,
+        _now: Instant,
+    ) -> IoResult<ServerNonInviteTransaction> {
+        let key = TransactionKey::from_message(&request)?;
+        let reliable = is_reliable(&request)?;
+        Ok(ServerNonInviteTransaction {
+            key,
+            state: TransactionState::Trying,
+            request,
+            response: None,
+            reliable,
+            config,
+            timers: Timers::default(),
+        })
+    }
+
+    /// Retrieve the key identifying this transaction.
+    pub fn key(&self) -> &TransactionKey {
+        &self.key
+    }
+
+    /// Retrieve the current state of this transaction.
+    pub fn state(&self) -> TransactionState {
+        self.state
+    }
+
+    /// Retrieve the request this transaction was created for.
+    pub fn request(&self) -> &SipMessage {
+        &self.request
+    }
+
+    /// The instant `on_timer` should next be called.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        self.timers.next()
+    }
+
+    /// Process a retransmitted request matching this transaction.
+    pub fn on_request(&mut self, _request: SipMessage, _now: Instant) -> Vec<TransactionEvent> {
+        match (self.state, &self.response) {
+            (TransactionState::Proceeding, Some(response))
+            | (TransactionState::Completed, Some(response)) => {
+                vec![TransactionEvent::Send(response.clone())]
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Send a response from the transaction user.
+    pub fn send_response(
+        &mut self,
+        response: SipMessage,
+        now: Instant,
+    ) -> IoResult<Vec<TransactionEvent>> {
+        if self.state != TransactionState::Trying && self.state != TransactionState::Proceeding {
+            return Err(invalid_state(self.state));
+        }
+        let code = response.status_code()?;
+        self.response = Some(response.clone());
+        if code < 200 {
+            self.state = TransactionState::Proceeding;
+        } else {
+            self.timers
+                .start(Timer::J, now + self.config.timer_j(self.reliable));
+            self.state = TransactionState::Completed;
+        }
+        Ok(vec![TransactionEvent::Send(response)])
+    }
+
+    /// Fire every timer that expired at or before `now`.
+    pub fn on_timer(&mut self, now: Instant) -> Vec<TransactionEvent> {
+        let mut events = vec![];
+        while let Some((timer, _)) = self.timers.pop_expired(now) {
+            if timer == Timer::J {
+                self.timers.clear();
+                self.state = TransactionState::Terminated;
+                events.push(TransactionEvent::Terminated(self.key.clone()));
+            }
+        }
+        events
+    }
+}
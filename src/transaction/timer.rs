@@ -0,0 +1,126 @@
+use std::time::{Duration, Instant};
+
+/// The transaction timers defined in
+/// [RFC3261: Page 265, Table 4](https://tools.ietf.org/html/rfc3261#page-265).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Timer {
+    /// INVITE request retransmit interval, UDP only.
+    A,
+    /// INVITE transaction timeout.
+    B,
+    /// Wait time for response retransmits.
+    D,
+    /// Non-INVITE request retransmit interval, UDP only.
+    E,
+    /// Non-INVITE transaction timeout.
+    F,
+    /// INVITE response retransmit interval.
+    G,
+    /// Wait time for ACK receipt.
+    H,
+    /// Wait time for ACK retransmits.
+    I,
+    /// Wait time for non-INVITE request retransmits.
+    J,
+    /// Wait time for response retransmits.
+    K,
+}
+
+/// The base timer values every transaction timer is derived from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TimerConfig {
+    /// RTT Estimate, defaults to 500ms.
+    pub t1: Duration,
+    /// The maximum retransmit interval for non-INVITE
+    /// requests and INVITE responses, defaults to 4s.
+    pub t2: Duration,
+    /// Maximum duration a message will remain in the
+    /// network, defaults to 5s.
+    pub t4: Duration,
+}
+
+impl Default for TimerConfig {
+    fn default() -> TimerConfig {
+        TimerConfig {
+            t1: Duration::from_millis(500),
+            t2: Duration::from_secs(4),
+            t4: Duration::from_secs(5),
+        }
+    }
+}
+
+impl TimerConfig {
+    /// Timers B, F, H and J all fire after 64*T1.
+    pub fn transaction_timeout(&self) -> Duration {
+        self.t1 * 64
+    }
+
+    /// Timer D is at least 32 seconds for unreliable transports
+    /// and zero for reliable ones.
+    pub fn timer_d(&self, reliable: bool) -> Duration {
+        if reliable {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs(32).max(self.transaction_timeout())
+        }
+    }
+
+    /// Timers I and K are T4 for unreliable transports and
+    /// zero for reliable ones.
+    pub fn timer_t4(&self, reliable: bool) -> Duration {
+        if reliable {
+            Duration::from_secs(0)
+        } else {
+            self.t4
+        }
+    }
+
+    /// Timer J is 64*T1 for unreliable transports and
+    /// zero for reliable ones.
+    pub fn timer_j(&self, reliable: bool) -> Duration {
+        if reliable {
+            Duration::from_secs(0)
+        } else {
+            self.transaction_timeout()
+        }
+    }
+}
+
+/// The set of timers currently running for one transaction.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub(crate) struct Timers(Vec<(Timer, Instant)>);
+
+impl Timers {
+    /// Start `timer`, replacing it if it is already running.
+    pub fn start(&mut self, timer: Timer, deadline: Instant) {
+        self.cancel(timer);
+        self.0.push((timer, deadline));
+    }
+
+    /// Stop `timer` if it is running.
+    pub fn cancel(&mut self, timer: Timer) {
+        self.0.retain(|(item, _)| *item != timer);
+    }
+
+    /// Stop every running timer.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// The deadline of the timer that will fire next.
+    pub fn next(&self) -> Option<Instant> {
+        self.0.iter().map(|(_, deadline)| *deadline).min()
+    }
+
+    /// Remove and return the earliest timer whose deadline is at or before `now`.
+    pub fn pop_expired(&mut self, now: Instant) -> Option<(Timer, Instant)> {
+        let index = self
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .min_by_key(|(_, (_, deadline))| *deadline)
+            .map(|(index, _)| index)?;
+        Some(self.0.remove(index))
+    }
+}
@@ -0,0 +1,67 @@
+use super::{ConnectionKey, TransportEvent};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+};
+
+/// An in-memory network connecting any number of `TransportLayer`'s,
+/// each one known by it's local address. The network events produced by
+/// a transport layer are handed to `deliver` and the data each address
+/// received is read back with `recv`, ready to be passed to
+/// `TransportLayer::receive`. Meant for tests and examples.
+#[derive(Debug, Default, Clone)]
+pub struct Loopback {
+    queues: HashMap<SocketAddr, VecDeque<(ConnectionKey, Vec<u8>)>>,
+    closed: HashMap<SocketAddr, Vec<ConnectionKey>>,
+}
+
+impl Loopback {
+    /// Create a new network with nothing in flight.
+    pub fn new() -> Loopback {
+        Loopback::default()
+    }
+
+    /// Carry out the events produced by the transport layer at `from`.
+    /// Data is queued for the remote address and closed connections are
+    /// reported to the remote side, connecting always succeeds. The events
+    /// that aren't network operations are returned.
+    pub fn deliver(
+        &mut self,
+        from: SocketAddr,
+        events: Vec<TransportEvent>,
+    ) -> Vec<TransportEvent> {
+        let mut remaining = vec![];
+        for event in events {
+            match event {
+                TransportEvent::Connect(_) => {},
+                TransportEvent::Send(key, data) => {
+                    self.queues
+                        .entry(key.remote)
+                        .or_default()
+                        .push_back((ConnectionKey::new(key.transport, from), data));
+                },
+                TransportEvent::Close(key) => {
+                    self.closed
+                        .entry(key.remote)
+                        .or_default()
+                        .push(ConnectionKey::new(key.transport, from));
+                },
+                event => remaining.push(event),
+            }
+        }
+        remaining
+    }
+
+    /// Take the oldest data sent to `to`, with the key of the connection
+    /// it arrived on as seen from `to`.
+    pub fn recv(&mut self, to: SocketAddr) -> Option<(ConnectionKey, Vec<u8>)> {
+        self.queues.get_mut(&to)?.pop_front()
+    }
+
+    /// Take the connections to `to` that were closed by the remote side,
+    /// each should be passed to `TransportLayer::closed`.
+    pub fn closed(&mut self, to: SocketAddr) -> Vec<ConnectionKey> {
+        self.closed.remove(&to).unwrap_or_default()
+    }
+}
@@ -0,0 +1,339 @@
+//! The transport module implements the transport layer of
+//! [RFC3261: Section 18](https://tools.ietf.org/html/rfc3261#section-18).
+//!
+//! Like the transaction layer the `TransportLayer` never touches the
+//! network or the system clock. Outgoing messages are turned into
+//! `TransportEvent`'s describing the connections to open and the bytes to
+//! write, and the bytes read from the network are passed to `receive`
+//! which returns the messages to hand to the transaction layer. Stream
+//! transports (TCP and TLS) are framed with a `MessageDecoder`, the other
+//! transports deliver one message per datagram, chunk or WebSocket frame
+//! ([RFC7118](https://tools.ietf.org/html/rfc7118)). Handling the TLS and
+//! WebSocket handshakes is left to the IO code.
+//!
+//! The `Resolver` finds the address and transport a request should be sent
+//! to, using the DNS procedures of [RFC3263](https://tools.ietf.org/html/rfc3263).
+
+mod loopback;
+pub use self::loopback::Loopback;
+
+mod resolver;
+pub use self::resolver::{DnsResolver, NaptrRecord, Resolver, SrvRecord, StaticZone};
+
+use nom::error::VerboseError;
+
+use crate::{
+    core::parse_message, Decoded, Domain, KeepAlive, MessageDecoder, Method, SipMessage,
+    SipMessageExt, Transport,
+};
+
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+/// The path MTU assumed when sending requests over UDP.
+pub const DEFAULT_MTU: usize = 1500;
+
+/// How long a connection may go without traffic before it is closed.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long the connection a request arrived on is remembered when no
+/// final response is sent, 64*T1 with the default T1 of 500ms.
+const REQUEST_LIFETIME: Duration = Duration::from_secs(32);
+
+/// Identifies a connection, or a flow for UDP, by it's transport
+/// and the address of the remote side.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct ConnectionKey {
+    pub transport: Transport,
+    pub remote: SocketAddr,
+}
+
+impl ConnectionKey {
+    /// Create a new key from it's parts.
+    pub fn new(transport: Transport, remote: SocketAddr) -> ConnectionKey {
+        ConnectionKey { transport, remote }
+    }
+}
+
+/// Output of the `TransportLayer`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TransportEvent {
+    /// A new connection must be opened, it is followed by the data to
+    /// write once it is established.
+    Connect(ConnectionKey),
+    /// This data must be written to the connection.
+    Send(ConnectionKey, Vec<u8>),
+    /// The connection must be closed.
+    Close(ConnectionKey),
+    /// A keep-alive pong arrived on the connection, see `KeepAlive::pong`.
+    Pong(ConnectionKey),
+    /// This message must be passed up to the transaction layer.
+    Message(ConnectionKey, SipMessage),
+}
+
+#[derive(Debug)]
+struct Connection {
+    decoder: Option<MessageDecoder>,
+    last_activity: Instant,
+}
+
+impl Connection {
+    /// Create a connection, `outbound` if it was opened by this side in
+    /// which case keep-alive pings can't arrive on it.
+    fn new(transport: Transport, outbound: bool, now: Instant) -> Connection {
+        Connection {
+            decoder: if transport.is_stream() {
+                Some(MessageDecoder::new().pings(!outbound))
+            } else {
+                None
+            },
+            last_activity: now,
+        }
+    }
+}
+
+/// Owns the connection table and frames the messages sent over it.
+/// Connections are opened for every transport except UDP and are reused
+/// for any message sent to the same address over the same transport.
+#[derive(Debug)]
+pub struct TransportLayer {
+    mtu: usize,
+    idle_timeout: Duration,
+    connections: HashMap<ConnectionKey, Connection>,
+    /// The connection each request arrived on, by Via branch, so the
+    /// response can be sent back over it. Entries are removed when the
+    /// final response is sent or expire at the given instant.
+    requests: HashMap<String, (ConnectionKey, Instant)>,
+}
+
+impl Default for TransportLayer {
+    fn default() -> TransportLayer {
+        TransportLayer::new()
+    }
+}
+
+impl TransportLayer {
+    /// Create a new transport layer without any connections.
+    pub fn new() -> TransportLayer {
+        TransportLayer {
+            mtu: DEFAULT_MTU,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            connections: HashMap::new(),
+            requests: HashMap::new(),
+        }
+    }
+
+    /// Set the path MTU used to decide when a UDP request must be sent
+    /// over TCP instead.
+    pub fn mtu(mut self, mtu: usize) -> TransportLayer {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Set how long a connection may go without traffic before `poll`
+    /// closes it.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> TransportLayer {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Retrieve the key of every open connection.
+    pub fn connections(&self) -> Vec<&ConnectionKey> {
+        self.connections.keys().collect()
+    }
+
+    /// Determine if a connection to this key is open.
+    pub fn is_connected(&self, key: &ConnectionKey) -> bool {
+        self.connections.contains_key(key)
+    }
+
+    /// Choose the transport `request` is sent over, it must be called before
+    /// the client transaction is created since the transaction picks it's
+    /// timers from the top Via header. A request that would come within 200
+    /// bytes of the MTU is sent over TCP instead of UDP, with the top Via
+    /// header updated to match
+    /// ([RFC3261: Section 18.1.1](https://tools.ietf.org/html/rfc3261#section-18.1.1)).
+    pub fn select_transport(&self, request: &mut SipMessage, transport: Transport) -> Transport {
+        if transport != Transport::Udp
+            || !request.is_request()
+            || format!("{}", request).len() + 200 <= self.mtu
+        {
+            return transport;
+        }
+        if let Ok(via) = request.via_header_mut() {
+            via.transport = Transport::Tcp;
+        }
+        Transport::Tcp
+    }
+
+    /// Send `msg` to `remote` over `transport`. Requests should be given
+    /// the transport returned by `select_transport`.
+    pub fn send(
+        &mut self,
+        msg: &SipMessage,
+        transport: Transport,
+        remote: SocketAddr,
+        now: Instant,
+    ) -> Vec<TransportEvent> {
+        let key = ConnectionKey::new(transport, remote);
+        self.write(key, format!("{}", msg).into_bytes(), now)
+    }
+
+    /// Send the response `msg` back to where the request came from. If the
+    /// request arrived over a connection that is still open the response is
+    /// sent over it, otherwise the top Via header decides
+    /// ([RFC3261: Section 18.2.2](https://tools.ietf.org/html/rfc3261#section-18.2.2)).
+    /// An error is returned if the Via header names a host that must be
+    /// resolved first.
+    pub fn send_response(
+        &mut self,
+        msg: &SipMessage,
+        now: Instant,
+    ) -> IoResult<Vec<TransportEvent>> {
+        let via = msg
+            .via_header()
+            .map_err(|err| IoError::new(IoErrorKind::InvalidInput, format!("{:?}", err)))?;
+        let target = via.response_target();
+        let request = match via.branch() {
+            Some(branch) if matches!(msg.status_code(), Ok(code) if code >= 200) => {
+                self.requests.remove(branch)
+            },
+            Some(branch) => self.requests.get(branch).cloned(),
+            None => None,
+        };
+        if target.reuse_connection {
+            let key = request
+                .map(|(key, _)| key)
+                .filter(|key| self.connections.contains_key(key));
+            if let Some(key) = key {
+                return Ok(self.write(key, format!("{}", msg).into_bytes(), now));
+            }
+        }
+        let remote = match target.host {
+            Domain::Ipv4(addr, port) => SocketAddr::new(IpAddr::V4(addr), port.unwrap_or(0)),
+            Domain::Ipv6(addr, port) => SocketAddr::new(IpAddr::V6(addr), port.unwrap_or(0)),
+            Domain::Domain(host, _) => {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidInput,
+                    format!("Via host '{}' must be resolved", host),
+                ))
+            },
+        };
+        let key = ConnectionKey::new(target.transport, remote);
+        Ok(self.write(key, format!("{}", msg).into_bytes(), now))
+    }
+
+    fn write(&mut self, key: ConnectionKey, data: Vec<u8>, now: Instant) -> Vec<TransportEvent> {
+        let mut events = vec![];
+        if key.transport.is_reliable() {
+            let connection = self.connections.entry(key).or_insert_with(|| {
+                events.push(TransportEvent::Connect(key));
+                Connection::new(key.transport, true, now)
+            });
+            connection.last_activity = now;
+        }
+        events.push(TransportEvent::Send(key, data));
+        events
+    }
+
+    /// Pass the data read from the connection `key` at `now`. Requests get
+    /// the `received` and `rport` parameters of their top Via header set
+    /// from the source address. Datagrams that can't be parsed are dropped,
+    /// a stream that can't be framed is closed.
+    pub fn receive(
+        &mut self,
+        key: ConnectionKey,
+        data: &[u8],
+        now: Instant,
+    ) -> Vec<TransportEvent> {
+        if !key.transport.is_reliable() {
+            return match parse_message::<VerboseError<&[u8]>>(data) {
+                Ok((_, msg)) => vec![self.message(key, msg, now)],
+                Err(_) => vec![],
+            };
+        }
+        let connection = self
+            .connections
+            .entry(key)
+            .or_insert_with(|| Connection::new(key.transport, false, now));
+        connection.last_activity = now;
+        let items = match &mut connection.decoder {
+            Some(decoder) => match decoder.decode_all(data) {
+                Ok(items) => items,
+                Err(_) => {
+                    self.closed(&key);
+                    return vec![TransportEvent::Close(key)];
+                },
+            },
+            None => match parse_message::<VerboseError<&[u8]>>(data) {
+                Ok((_, msg)) => vec![Decoded::Message(msg)],
+                Err(_) if data == KeepAlive::PING => vec![Decoded::Ping],
+                Err(_) if data == KeepAlive::PONG => vec![Decoded::Pong],
+                Err(_) => vec![],
+            },
+        };
+        items
+            .into_iter()
+            .filter_map(|item| match item {
+                Decoded::Message(msg) => Some(self.message(key, msg, now)),
+                Decoded::Ping => Some(TransportEvent::Send(key, KeepAlive::PONG.to_vec())),
+                Decoded::Pong => Some(TransportEvent::Pong(key)),
+                Decoded::NeedMore => None,
+            })
+            .collect()
+    }
+
+    fn message(&mut self, key: ConnectionKey, mut msg: SipMessage, now: Instant) -> TransportEvent {
+        if msg.is_request() {
+            // An ACK is never answered, so it's connection isn't remembered.
+            let answered = key.transport.is_reliable() && msg.method() != Ok(Method::Ack);
+            if let Ok(via) = msg.via_header_mut() {
+                via.received_from(key.remote);
+                if let (true, Some(branch)) = (answered, via.branch()) {
+                    self.requests
+                        .insert(branch.clone(), (key, now + REQUEST_LIFETIME));
+                }
+            }
+        }
+        TransportEvent::Message(key, msg)
+    }
+
+    /// The connection `key` was closed by the remote side or failed.
+    pub fn closed(&mut self, key: &ConnectionKey) {
+        self.connections.remove(key);
+        self.requests.retain(|_, (request, _)| request != key);
+    }
+
+    /// Close every connection that has been idle since before
+    /// `now - idle_timeout` and forget the requests that were never
+    /// answered.
+    pub fn poll(&mut self, now: Instant) -> Vec<TransportEvent> {
+        self.requests.retain(|_, (_, expires)| *expires > now);
+        let idle_timeout = self.idle_timeout;
+        let idle: Vec<ConnectionKey> = self
+            .connections
+            .iter()
+            .filter(|(_, connection)| now >= connection.last_activity + idle_timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        idle.into_iter()
+            .map(|key| {
+                self.closed(&key);
+                TransportEvent::Close(key)
+            })
+            .collect()
+    }
+
+    /// The instant `poll` should next be called.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        self.connections
+            .values()
+            .map(|connection| connection.last_activity + self.idle_timeout)
+            .chain(self.requests.values().map(|(_, expires)| *expires))
+            .min()
+    }
+}
@@ -0,0 +1,337 @@
+use rand::Rng;
+
+use super::ConnectionKey;
+use crate::{Domain, Transport, Uri, UriParam, UriSchema};
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+};
+
+/// A DNS NAPTR record ([RFC3403](https://tools.ietf.org/html/rfc3403)).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NaptrRecord {
+    pub order: u16,
+    pub preference: u16,
+    pub flags: String,
+    /// The service, such as `SIP+D2U` or `SIPS+D2T`.
+    pub service: String,
+    /// The name of the SRV record to look up next.
+    pub replacement: String,
+}
+
+/// A DNS SRV record ([RFC2782](https://tools.ietf.org/html/rfc2782)).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// The DNS queries needed by the `Resolver`. A failed query is treated
+/// the same as one without any records.
+pub trait DnsResolver {
+    /// Look up the NAPTR records of `name`.
+    fn naptr(&self, name: &str) -> Vec<NaptrRecord>;
+
+    /// Look up the SRV records of `name`.
+    fn srv(&self, name: &str) -> Vec<SrvRecord>;
+
+    /// Look up the A and AAAA records of `name`.
+    fn lookup_ip(&self, name: &str) -> Vec<IpAddr>;
+}
+
+/// A fixed set of DNS records kept in memory.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct StaticZone {
+    naptr: HashMap<String, Vec<NaptrRecord>>,
+    srv: HashMap<String, Vec<SrvRecord>>,
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticZone {
+    /// Create a new zone without any records.
+    pub fn new() -> StaticZone {
+        StaticZone::default()
+    }
+
+    /// Add a NAPTR record for `name`.
+    pub fn naptr<S: Into<String>>(mut self, name: S, record: NaptrRecord) -> StaticZone {
+        self.naptr
+            .entry(name.into().to_lowercase())
+            .or_default()
+            .push(record);
+        self
+    }
+
+    /// Add a SRV record for `name`.
+    pub fn srv<S: Into<String>>(mut self, name: S, record: SrvRecord) -> StaticZone {
+        self.srv
+            .entry(name.into().to_lowercase())
+            .or_default()
+            .push(record);
+        self
+    }
+
+    /// Add an A or AAAA record for `name`.
+    pub fn host<S: Into<String>>(mut self, name: S, address: IpAddr) -> StaticZone {
+        self.hosts
+            .entry(name.into().to_lowercase())
+            .or_default()
+            .push(address);
+        self
+    }
+}
+
+impl DnsResolver for StaticZone {
+    fn naptr(&self, name: &str) -> Vec<NaptrRecord> {
+        self.naptr
+            .get(&name.to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn srv(&self, name: &str) -> Vec<SrvRecord> {
+        self.srv
+            .get(&name.to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn lookup_ip(&self, name: &str) -> Vec<IpAddr> {
+        self.hosts
+            .get(&name.to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Locates the servers a request to a uri should be sent to, following
+/// [RFC3263: Section 4](https://tools.ietf.org/html/rfc3263#section-4).
+/// The targets are returned in the order they should be tried, when a
+/// target times out or answers with a 503 the request is sent to the next
+/// one ([RFC3263: Section 4.3](https://tools.ietf.org/html/rfc3263#section-4.3)).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Resolver<D> {
+    dns: D,
+    transports: Vec<Transport>,
+}
+
+impl<D: DnsResolver> Resolver<D> {
+    /// Create a new resolver sending it's queries to `dns`.
+    pub fn new(dns: D) -> Resolver<D> {
+        Resolver {
+            dns,
+            transports: vec![
+                Transport::Udp,
+                Transport::Tcp,
+                Transport::Tls,
+                Transport::Sctp,
+                Transport::Ws,
+                Transport::Wss,
+            ],
+        }
+    }
+
+    /// Set the transports this client supports, in the order SRV records
+    /// are tried when the domain has no NAPTR records.
+    pub fn transports(mut self, transports: Vec<Transport>) -> Resolver<D> {
+        self.transports = transports;
+        self
+    }
+
+    /// Retrieve the DNS backend.
+    pub fn dns(&self) -> &D {
+        &self.dns
+    }
+
+    /// Compute the ordered list of targets for `uri`. The `maddr` parameter
+    /// replaces the host, keeping the port of the uri when it has none of
+    /// its own, an explicit `transport` parameter skips the NAPTR
+    /// lookup and an explicit port skips the SRV lookup. A tel uri holds a
+    /// number rather than a host and has no targets, it must first be turned
+    /// into a sip uri with `TelUri::to_sip_uri`.
+    pub fn resolve(&self, uri: &Uri) -> Vec<ConnectionKey> {
+        let mut host = match uri.host_domain() {
+            Some(host) => host.clone(),
+            None => return vec![],
+        };
+        let secure = uri.schema == Some(UriSchema::Sips);
+        let mut transport = None;
+        for param in &uri.parameters {
+            match param {
+                UriParam::Maddr(maddr) => host = with_port(maddr, uri.host.port()),
+                UriParam::Transport(param) => transport = Some(*param),
+                _ => {},
+            }
+        }
+        let transport = match (transport, secure) {
+            (Some(Transport::Tcp), true) => Some(Transport::Tls),
+            (Some(Transport::Ws), true) => Some(Transport::Wss),
+            (Some(Transport::Udp), true) => return vec![],
+            (transport, _) => transport,
+        };
+        let default_transport = if secure {
+            Transport::Tls
+        } else {
+            Transport::Udp
+        };
+        let (name, port) = match &host {
+            Domain::Ipv4(addr, port) => {
+                return vec![target(
+                    transport.unwrap_or(default_transport),
+                    IpAddr::V4(*addr),
+                    *port,
+                )]
+            },
+            Domain::Ipv6(addr, port) => {
+                return vec![target(
+                    transport.unwrap_or(default_transport),
+                    IpAddr::V6(*addr),
+                    *port,
+                )]
+            },
+            Domain::Domain(name, port) => (name, *port),
+        };
+        if port.is_some() {
+            return self.hosts(transport.unwrap_or(default_transport), name, port);
+        }
+        let mut targets = match transport {
+            Some(transport) => self.srv(transport, secure, name),
+            None => {
+                let targets = self.naptr(secure, name);
+                if targets.is_empty() {
+                    self.transports
+                        .iter()
+                        .flat_map(|transport| self.srv(*transport, secure, name))
+                        .collect()
+                } else {
+                    targets
+                }
+            },
+        };
+        if targets.is_empty() {
+            targets = self.hosts(transport.unwrap_or(default_transport), name, None);
+        }
+        targets
+    }
+
+    fn naptr(&self, secure: bool, name: &str) -> Vec<ConnectionKey> {
+        let mut records: Vec<(NaptrRecord, Transport)> = self
+            .dns
+            .naptr(name)
+            .into_iter()
+            .filter(|record| record.flags.eq_ignore_ascii_case("s"))
+            .filter_map(|record| {
+                let transport = naptr_transport(&record.service)?;
+                Some((record, transport))
+            })
+            .filter(|(_, transport)| !secure || transport.is_secure())
+            .filter(|(_, transport)| self.transports.contains(transport))
+            .collect();
+        records.sort_by_key(|(record, _)| (record.order, record.preference));
+        records
+            .into_iter()
+            .flat_map(|(record, transport)| self.srv_records(transport, &record.replacement))
+            .collect()
+    }
+
+    fn srv(&self, transport: Transport, secure: bool, name: &str) -> Vec<ConnectionKey> {
+        if secure && !transport.is_secure() {
+            return vec![];
+        }
+        match srv_prefix(transport) {
+            Some(prefix) => self.srv_records(transport, &format!("{}.{}", prefix, name)),
+            None => vec![],
+        }
+    }
+
+    fn srv_records(&self, transport: Transport, name: &str) -> Vec<ConnectionKey> {
+        order_srv(self.dns.srv(name))
+            .into_iter()
+            .filter(|record| record.target != ".")
+            .flat_map(|record| self.hosts(transport, &record.target, Some(record.port)))
+            .collect()
+    }
+
+    fn hosts(&self, transport: Transport, name: &str, port: Option<u16>) -> Vec<ConnectionKey> {
+        let name = name.trim_end_matches('.');
+        self.dns
+            .lookup_ip(name)
+            .into_iter()
+            .map(|address| target(transport, address, port))
+            .collect()
+    }
+}
+
+fn target(transport: Transport, address: IpAddr, port: Option<u16>) -> ConnectionKey {
+    let port = port.unwrap_or_else(|| transport.default_port());
+    ConnectionKey::new(transport, SocketAddr::new(address, port))
+}
+
+/// `maddr` with `port` when it has no port of its own.
+fn with_port(maddr: &Domain, port: Option<u16>) -> Domain {
+    match maddr.clone() {
+        Domain::Ipv4(addr, None) => Domain::Ipv4(addr, port),
+        Domain::Ipv6(addr, None) => Domain::Ipv6(addr, port),
+        Domain::Domain(name, None) => Domain::Domain(name, port),
+        maddr => maddr,
+    }
+}
+
+/// The transport of a NAPTR service field, [RFC3263: Section 4.1](https://tools.ietf.org/html/rfc3263#section-4.1)
+/// and [RFC7118: Section 7](https://tools.ietf.org/html/rfc7118#section-7).
+fn naptr_transport(service: &str) -> Option<Transport> {
+    match service.to_uppercase().as_str() {
+        "SIP+D2U" => Some(Transport::Udp),
+        "SIP+D2T" => Some(Transport::Tcp),
+        "SIPS+D2T" => Some(Transport::Tls),
+        "SIP+D2S" => Some(Transport::Sctp),
+        "SIP+D2W" => Some(Transport::Ws),
+        "SIPS+D2W" => Some(Transport::Wss),
+        _ => None,
+    }
+}
+
+/// The prefix of the SRV records for each transport. WebSocket servers
+/// don't have SRV records.
+fn srv_prefix(transport: Transport) -> Option<&'static str> {
+    match transport {
+        Transport::Udp => Some("_sip._udp"),
+        Transport::Tcp => Some("_sip._tcp"),
+        Transport::Tls => Some("_sips._tcp"),
+        Transport::Sctp => Some("_sip._sctp"),
+        Transport::Ws | Transport::Wss => None,
+    }
+}
+
+/// Order SRV records by priority, records of the same priority are
+/// shuffled by weight as described in [RFC2782](https://tools.ietf.org/html/rfc2782).
+fn order_srv(mut records: Vec<SrvRecord>) -> Vec<SrvRecord> {
+    records.sort_by_key(|record| (record.priority, record.weight));
+    let mut rng = rand::thread_rng();
+    let mut ordered = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priority = records[0].priority;
+        let count = records
+            .iter()
+            .take_while(|record| record.priority == priority)
+            .count();
+        let mut group: Vec<SrvRecord> = records.drain(..count).collect();
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|record| record.weight as u32).sum();
+            let pick = rng.gen_range(0, total + 1);
+            let mut sum = 0;
+            let index = group
+                .iter()
+                .position(|record| {
+                    sum += record.weight as u32;
+                    sum >= pick
+                })
+                .unwrap_or(0);
+            ordered.push(group.remove(index));
+        }
+    }
+    ordered
+}
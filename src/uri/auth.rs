@@ -1,17 +1,17 @@
-use crate::parse::slice_to_string;
+use crate::parse::{escape, is_password_char, is_user_char, parse_escaped};
 use serde::{Deserialize, Serialize};
 
 use nom::{
-    bytes::complete::take_while,
-    character::{complete::char, is_alphanumeric},
-    combinator::{map_res, opt},
-    error::ParseError,
+    character::complete::char,
+    combinator::opt,
+    error::{ErrorKind, ParseError},
     IResult,
 };
 
 use std::fmt;
 
-/// URI Credentials
+/// URI Credentials. Both values are stored decoded and
+/// escaped again when written.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct UriAuth {
     pub username: String,
@@ -37,30 +37,40 @@ impl UriAuth {
 impl fmt::Display for UriAuth {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(pass) = &self.password {
-            write!(f, "{}:{}", &self.username, pass)?;
+            write!(
+                f,
+                "{}:{}",
+                escape(&self.username, is_user_char),
+                escape(pass, is_password_char)
+            )?;
         } else {
-            write!(f, "{}", &self.username)?;
+            write!(f, "{}", escape(&self.username, is_user_char))?;
         }
         Ok(())
     }
 }
 
 /// Parse the username/password of a uri.
+/// ([RFC3261: Page 222, "userinfo"](https://tools.ietf.org/html/rfc3261#page-222))
 pub fn parse_uriauth<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], UriAuth, E> {
-    let (input, username) = map_res(take_while(is_alphanumeric), slice_to_string::<E>)(input)?;
-    let (input, password) = opt(parse_password::<E>)(input)?;
-    let (input, _) = char('@')(input)?;
-    Ok((input, UriAuth { username, password }))
+    let (rest, username) = parse_escaped(input, is_user_char)?;
+    if username.is_empty() {
+        return Err(nom::Err::Error(E::from_error_kind(
+            input,
+            ErrorKind::TakeWhile1,
+        )));
+    }
+    let (rest, password) = opt(parse_password::<E>)(rest)?;
+    let (rest, _) = char('@')(rest)?;
+    Ok((rest, UriAuth { username, password }))
 }
 
-/// Currently this will only accept alphanumeric characters.
+/// Parse the password part of the userinfo.
 pub fn parse_password<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], String, E> {
     let (input, _) = char(':')(input)?;
-    Ok(map_res(take_while(is_alphanumeric), slice_to_string::<E>)(
-        input,
-    )?)
+    parse_escaped(input, is_password_char)
 }
@@ -1,18 +1,31 @@
-use nom::character::{is_alphanumeric, is_digit};
+use nom::character::{is_alphanumeric, is_digit, is_hex_digit};
 use serde::{Deserialize, Serialize};
 
-use std::{fmt, net::Ipv4Addr};
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
 
 use crate::parse::{parse_ip_address, parse_u16, slice_to_string};
 
-/// Domain address for a URI. Currently Only Ipv4 and
-/// domains are implemented.
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+/// Domain address for a URI. IPv6 addresses are written inside square
+/// brackets as described in [RFC3261: Section 19.1.1](https://tools.ietf.org/html/rfc3261#section-19.1.1).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub enum Domain {
     Ipv4(Ipv4Addr, Option<u16>),
+    Ipv6(Ipv6Addr, Option<u16>),
     Domain(String, Option<u16>),
 }
 
+impl Domain {
+    /// Retrieve the port of this domain.
+    pub fn port(&self) -> Option<u16> {
+        match self {
+            Domain::Ipv4(_, port) | Domain::Ipv6(_, port) | Domain::Domain(_, port) => *port,
+        }
+    }
+}
+
 impl fmt::Display for Domain {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -23,6 +36,13 @@ impl fmt::Display for Domain {
                     write!(f, "{}", addr)
                 }
             },
+            Domain::Ipv6(addr, port) => {
+                if let Some(port) = port {
+                    write!(f, "[{}]:{}", addr, port)
+                } else {
+                    write!(f, "[{}]", addr)
+                }
+            },
             Domain::Domain(domain, port) => {
                 if let Some(port) = port {
                     write!(f, "{}:{}", domain, port)
@@ -38,7 +58,7 @@ use nom::{
     branch::alt,
     bytes::complete::take_while,
     character::complete::char,
-    combinator::{map_res, opt},
+    combinator::{map_res, opt, verify},
     error::ParseError,
     IResult,
 };
@@ -54,7 +74,38 @@ pub fn parse_port<'a, E: ParseError<&'a [u8]>>(
 }
 
 pub fn parse_domain<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Domain, E> {
-    alt((parse_ip_domain::<E>, parse_domain_domain::<E>))(input)
+    alt((
+        parse_ipv6_domain::<E>,
+        parse_ip_domain::<E>,
+        parse_domain_domain::<E>,
+    ))(input)
+}
+
+/// Parse a bracketed IPv6 reference, optionally followed by a port.
+pub fn parse_ipv6_domain<'a, E: ParseError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], Domain, E> {
+    let (input, _) = char('[')(input)?;
+    let (input, addr) = parse_ipv6_address::<E>(input)?;
+    let (input, _) = char(']')(input)?;
+    let (input, _) = opt::<_, _, E, _>(char::<_, E>(':'))(input)?;
+    let (input, port) = parse_port::<E>(input)?;
+    Ok((input, Domain::Ipv6(addr, port)))
+}
+
+/// Parse an IPv6 address without brackets.
+pub fn parse_ipv6_address<'a, E: ParseError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], Ipv6Addr, E> {
+    map_res(
+        take_while(|item| is_hex_digit(item) || item == b':' || item == b'.'),
+        |slice| {
+            ::std::str::from_utf8(slice)
+                .map_err(|_| ())?
+                .parse::<Ipv6Addr>()
+                .map_err(|_| ())
+        },
+    )(input)
 }
 
 pub fn parse_ip_domain<'a, E: ParseError<&'a [u8]>>(
@@ -66,11 +117,16 @@ pub fn parse_ip_domain<'a, E: ParseError<&'a [u8]>>(
     Ok((input, Domain::Ipv4(addr, port)))
 }
 
+/// Parse a hostname, optionally followed by a port.
+/// [RFC3261: Section 25.1](https://tools.ietf.org/html/rfc3261#section-25.1)
 pub fn parse_domain_domain<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], Domain, E> {
     let (input, domain) = map_res::<_, _, _, _, E, _, _>(
-        take_while::<_, _, E>(|item| is_alphanumeric(item) || item == b'.'),
+        verify(
+            take_while::<_, _, E>(|item| is_alphanumeric(item) || item == b'.' || item == b'-'),
+            is_hostname,
+        ),
         slice_to_string::<E>,
     )(input)?;
     let (input, _) = opt::<_, _, E, _>(char::<_, E>(':'))(input)?;
@@ -80,3 +136,19 @@ pub fn parse_domain_domain<'a, E: ParseError<&'a [u8]>>(
     ))(input)?;
     Ok((input, Domain::Domain(domain, port)))
 }
+
+/// Check `hostname = *( domainlabel "." ) toplabel [ "." ]`. Labels are
+/// non-empty and begin and end with an alphanumeric character, the top
+/// label begins with a letter.
+fn is_hostname(input: &[u8]) -> bool {
+    let input = input.strip_suffix(b".").unwrap_or(input);
+    let is_label = |label: &[u8]| match (label.first(), label.last()) {
+        (Some(first), Some(last)) => is_alphanumeric(*first) && is_alphanumeric(*last),
+        _ => false,
+    };
+    let mut labels = input.split(|item| *item == b'.').rev();
+    match labels.next() {
+        Some(top) if is_label(top) && top[0].is_ascii_alphabetic() => labels.all(is_label),
+        _ => false,
+    }
+}
@@ -2,7 +2,17 @@ use serde::{Deserialize, Serialize};
 
 use std::{fmt, io::Result as IoResult, str::FromStr};
 
-use nom::{character::complete::char, combinator::opt, error::ParseError, sequence::pair, IResult};
+use nom::{
+    bytes::complete::take_till1,
+    character::{complete::char, is_space},
+    combinator::{all_consuming, opt},
+    error::ParseError,
+    multi::separated_list1,
+    sequence::{pair, preceded},
+    IResult,
+};
+
+use crate::parse::{escape, is_header_char, parse_escaped};
 
 pub mod schema;
 pub use self::schema::{parse_schema, UriSchema};
@@ -16,6 +26,9 @@ pub use self::params::{parse_param, parse_params, UriParam};
 pub mod auth;
 pub use self::auth::{parse_uriauth, UriAuth};
 
+pub mod tel;
+pub use self::tel::{parse_tel_uri, TelUri};
+
 /// Universal Rescource Identifier for libsip.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Uri {
@@ -23,6 +36,8 @@ pub struct Uri {
     pub host: Domain,
     pub auth: Option<UriAuth>,
     pub parameters: Vec<UriParam>,
+    /// The `?name=value&...` headers component, stored decoded.
+    pub headers: Vec<(String, String)>,
 }
 
 impl Uri {
@@ -32,6 +47,7 @@ impl Uri {
             host,
             auth: None,
             parameters: vec![],
+            headers: vec![],
         }
     }
 
@@ -42,6 +58,7 @@ impl Uri {
             host,
             auth: None,
             parameters: vec![],
+            headers: vec![],
         }
     }
 
@@ -55,6 +72,27 @@ impl Uri {
         Uri::new(UriSchema::Sips, host)
     }
 
+    /// Create a new Uri with schema set to `Schema::Tel` for `number`.
+    pub fn tel(number: TelUri) -> Uri {
+        number.into()
+    }
+
+    /// The host this uri points at, `None` for a tel uri which
+    /// only stores it's number in `host`.
+    pub fn host_domain(&self) -> Option<&Domain> {
+        if self.schema == Some(UriSchema::Tel) {
+            None
+        } else {
+            Some(&self.host)
+        }
+    }
+
+    /// Interpret this uri as a telephone number, `None`
+    /// unless the schema is `Schema::Tel`.
+    pub fn tel_number(&self) -> Option<TelUri> {
+        TelUri::from_uri(self)
+    }
+
     /// Add a `UriAuth` section to this Uri.
     pub fn auth(mut self, auth: UriAuth) -> Uri {
         self.auth = Some(auth);
@@ -80,6 +118,12 @@ impl Uri {
         self
     }
 
+    /// Add a header to the headers component of this uri.
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Uri {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
     /// Remove the Schema if there is any.
     pub fn schemaless(mut self) -> Uri {
         self.schema = None;
@@ -121,15 +165,32 @@ impl fmt::Display for Uri {
         for param in &self.parameters {
             write!(f, "{}", param)?;
         }
+        for (index, (name, value)) in self.headers.iter().enumerate() {
+            write!(
+                f,
+                "{}{}={}",
+                if index == 0 { '?' } else { '&' },
+                escape(name, is_header_char),
+                escape(value, is_header_char)
+            )?;
+        }
         Ok(())
     }
 }
 
 pub fn parse_uri<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Uri, E> {
     let (input, schema) = opt(pair(parse_schema::<E>, char(':')))(input)?;
+    if let Some((UriSchema::Tel, _)) = schema {
+        let (input, tel) = parse_tel_uri::<E>(input)?;
+        return Ok((input, tel.into()));
+    }
     let (input, auth) = opt(parse_uriauth::<E>)(input)?;
     let (input, host) = parse_domain::<E>(input)?;
     let (input, parameters) = parse_params::<E>(input)?;
+    let (input, headers) = opt(preceded(
+        char('?'),
+        separated_list1(char('&'), parse_uri_header::<E>),
+    ))(input)?;
     Ok((
         input,
         Uri {
@@ -137,10 +198,55 @@ pub fn parse_uri<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u
             host,
             parameters,
             auth,
+            headers: headers.unwrap_or_default(),
         },
     ))
 }
 
+/// Parse a uri written without angle brackets, as allowed in the From, To
+/// and Contact headers. Any parameters that follow belong to the header
+/// instead of the uri ([RFC3261: Section 20](https://tools.ietf.org/html/rfc3261#section-20)).
+pub fn parse_addr_spec<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Uri, E> {
+    let (input, schema) = opt(pair(parse_schema::<E>, char(':')))(input)?;
+    if let Some((UriSchema::Tel, _)) = schema {
+        // Only the number, a local number needs it's phone-context and
+        // has to be bracketed.
+        let (input, number) =
+            take_till1(|chr| chr == b';' || chr == b',' || is_space(chr) || chr == b'\r')(input)?;
+        let (_, tel) = all_consuming(parse_tel_uri::<E>)(number)?;
+        return Ok((input, tel.into()));
+    }
+    let (input, auth) = opt(parse_uriauth::<E>)(input)?;
+    let (input, host) = parse_domain::<E>(input)?;
+    Ok((
+        input,
+        Uri {
+            schema: schema.map(|item| item.0),
+            host,
+            parameters: vec![],
+            auth,
+            headers: vec![],
+        },
+    ))
+}
+
+/// Parse a single `name=value` pair of the uri headers component.
+/// ([RFC3261: Page 222, "header"](https://tools.ietf.org/html/rfc3261#page-222))
+pub fn parse_uri_header<'a, E: ParseError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], (String, String), E> {
+    let (rest, name) = parse_escaped(input, is_header_char)?;
+    if name.is_empty() {
+        return Err(nom::Err::Error(E::from_error_kind(
+            input,
+            nom::error::ErrorKind::TakeWhile1,
+        )));
+    }
+    let (rest, _) = char('=')(rest)?;
+    let (rest, value) = parse_escaped(rest, is_header_char)?;
+    Ok((rest, (name, value)))
+}
+
 impl FromStr for Uri {
     type Err = nom::Err<nom::error::ErrorKind>;
 
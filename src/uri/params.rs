@@ -2,57 +2,123 @@ use serde::{Deserialize, Serialize};
 use std::fmt;
 
 use crate::{
-    core::{parse_transport, Transport},
-    parse::is_token,
-    uri::{parse_domain, parse_port, Domain},
+    core::{parse_method, parse_transport, Method, Transport},
+    parse::{escape, is_param_char, is_token, unescape},
+    uri::{domain::parse_ipv6_address, parse_domain, parse_port, Domain},
 };
 
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while},
-    character::is_alphabetic,
+    bytes::complete::{tag, take_while, take_while1},
     combinator::map,
-    error::ParseError,
+    error::{ErrorKind, ParseError},
     IResult,
 };
 
 /// Uri Parameters.
 ///
-/// TODO: Expand this enum. Similar to `libsip::Header`
+/// The parameters defined by [RFC3261: Section 19.1.1](https://tools.ietf.org/html/rfc3261#section-19.1.1)
+/// and the extensions used by this library have their own variant, anything
+/// else is kept in `Other`. Parameter names are matched case-insensitively.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum UriParam {
     Transport(Transport),
     Branch(String),
+    /// An IPv6 address is always written in its bracketed form, so
+    /// `received=2001:db8::1` is displayed as `received=[2001:db8::1]`.
     Received(Domain),
     RPort(Option<u16>),
+    /// `user=phone` or `user=ip`, any other token is allowed.
+    User(String),
+    /// The method of the request built from this uri.
+    Method(Method),
+    /// Time to live of multicast packets, 0 to 255.
+    Ttl(u8),
+    /// The address the request should be sent to instead of the host.
+    Maddr(Domain),
+    /// The element this uri points to is a loose router.
+    Lr,
+    /// Outbound flow token, [RFC5626: Section 5.4](https://tools.ietf.org/html/rfc5626#section-5.4).
+    Ob,
+    /// GRUU, [RFC5627: Section 3.1](https://tools.ietf.org/html/rfc5627#section-3.1).
+    Gr(Option<String>),
+    /// The `sigcomp-id` of [RFC5049: Section 9.1](https://tools.ietf.org/html/rfc5049#section-9.1).
+    SigComp(String),
+    /// The `comp` parameter of [RFC3486: Section 2](https://tools.ietf.org/html/rfc3486#section-2).
+    Comp(String),
+    /// Lifetime of the uri in seconds.
+    MaxAge(u32),
     Other(String, Option<String>),
 }
 
 impl UriParam {
-    /// Create `UriParam` from a key value pair.
-    pub fn from_key<'a, E: ParseError<&'a [u8]>>(
-        key: &'a [u8],
-        value: &'a [u8],
-    ) -> Result<UriParam, nom::Err<E>> {
-        match key {
-            b"rport" => Ok(UriParam::RPort(parse_port::<E>(&value)?.1)),
-            b"transport" => Ok(UriParam::Transport(parse_transport::<E>(&value)?.1)),
-            b"branch" => Ok(UriParam::Branch(
-                String::from_utf8(value.to_vec()).expect("Utf-8 Error"),
-            )),
-            b"received" => {
-                //let mut data = value.to_vec();
-                //data.push(b' ');
-                Ok(UriParam::Received(parse_domain::<E>(&value)?.1))
-            },
-            _method => Ok(UriParam::Other(
-                String::from_utf8_lossy(key).to_string(),
-                Some(String::from_utf8_lossy(value).to_string()),
-            )),
+    /// Create `UriParam` from a key value pair. A value that is not valid
+    /// for the given key, such as an extension method for `method`, is kept
+    /// as an `Other` parameter. So are `lr` and `ob` when given a value, such
+    /// as the `lr=on` written by some older proxies.
+    pub fn from_key(key: &[u8], value: &[u8]) -> UriParam {
+        UriParam::typed(key, value)
+            .unwrap_or_else(|| UriParam::Other(unescape(key), Some(unescape(value))))
+    }
+
+    fn typed(key: &[u8], value: &[u8]) -> Option<UriParam> {
+        Some(match key.to_ascii_lowercase().as_slice() {
+            b"rport" => UriParam::RPort(complete(parse_port, value)?),
+            b"transport" => UriParam::Transport(complete(parse_transport, value)?),
+            b"branch" => UriParam::Branch(String::from_utf8(value.to_vec()).ok()?),
+            b"received" => UriParam::Received(complete(parse_domain, value)?),
+            b"user" => UriParam::User(token(value)?),
+            b"method" => UriParam::Method(complete(parse_method, value)?),
+            b"ttl" => UriParam::Ttl(unescape(value).parse().ok()?),
+            b"maddr" => UriParam::Maddr(complete(parse_domain, value)?),
+            b"gr" => UriParam::Gr(Some(unescape(value))),
+            b"sigcomp-id" => UriParam::SigComp(unescape(value)),
+            b"comp" => UriParam::Comp(token(value)?),
+            b"maxage" => UriParam::MaxAge(unescape(value).parse().ok()?),
+            _ => return None,
+        })
+    }
+
+    /// Create `UriParam` from a key without a value.
+    pub fn from_flag(key: &[u8]) -> UriParam {
+        match key.to_ascii_lowercase().as_slice() {
+            b"rport" => UriParam::RPort(None),
+            b"lr" => UriParam::Lr,
+            b"ob" => UriParam::Ob,
+            b"gr" => UriParam::Gr(None),
+            _ => UriParam::Other(unescape(key), None),
+        }
+    }
+
+    /// Determine if this is the `lr` parameter, with or without a value.
+    pub fn is_lr(&self) -> bool {
+        match self {
+            UriParam::Lr => true,
+            UriParam::Other(key, _) => key.eq_ignore_ascii_case("lr"),
+            _ => false,
         }
     }
 }
 
+/// Run `parser` over the whole of `value`.
+fn complete<'a, O>(
+    parser: impl Fn(&'a [u8]) -> IResult<&'a [u8], O, (&'a [u8], ErrorKind)>,
+    value: &'a [u8],
+) -> Option<O> {
+    match parser(value) {
+        Ok((b"", item)) => Some(item),
+        _ => None,
+    }
+}
+
+fn token(value: &[u8]) -> Option<String> {
+    if !value.is_empty() && value.iter().all(|chr| is_token(*chr)) {
+        Some(String::from_utf8_lossy(value).to_string())
+    } else {
+        None
+    }
+}
+
 impl fmt::Display for UriParam {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -61,18 +127,30 @@ impl fmt::Display for UriParam {
             UriParam::Received(branch) => write!(f, ";received={}", branch),
             UriParam::RPort(Some(value)) => write!(f, ";rport={}", value),
             UriParam::RPort(None) => write!(f, ";rport"),
-            UriParam::Other(key, Some(value)) => write!(f, ";{}={}", key, value),
-            UriParam::Other(key, None) => write!(f, ";{}", key),
+            UriParam::User(user) => write!(f, ";user={}", user),
+            UriParam::Method(method) => write!(f, ";method={}", method),
+            UriParam::Ttl(ttl) => write!(f, ";ttl={}", ttl),
+            UriParam::Maddr(addr) => write!(f, ";maddr={}", addr),
+            UriParam::Lr => write!(f, ";lr"),
+            UriParam::Ob => write!(f, ";ob"),
+            UriParam::Gr(Some(value)) => write!(f, ";gr={}", escape(value, is_param_char)),
+            UriParam::Gr(None) => write!(f, ";gr"),
+            UriParam::SigComp(id) => write!(f, ";sigcomp-id={}", escape(id, is_param_char)),
+            UriParam::Comp(comp) => write!(f, ";comp={}", comp),
+            UriParam::MaxAge(age) => write!(f, ";maxage={}", age),
+            UriParam::Other(key, Some(value)) => write!(
+                f,
+                ";{}={}",
+                escape(key, is_param_char),
+                escape(value, is_param_char)
+            ),
+            UriParam::Other(key, None) => write!(f, ";{}", escape(key, is_param_char)),
         }
     }
 }
 
 pub fn parse_param<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], UriParam, E> {
-    alt::<_, _, E, _>((
-        parse_named_param,
-        map(tag::<_, _, E>(";rport"), |_| UriParam::RPort(None)),
-        parse_single_param,
-    ))(input)
+    alt::<_, _, E, _>((parse_named_param, parse_single_param))(input)
 }
 
 /// Parse a single named field param.
@@ -80,21 +158,40 @@ pub fn parse_named_param<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], UriParam, E> {
     let (input, _) = tag(";")(input)?;
-    let (input, key) = take_while(is_alphabetic)(input)?;
+    let (input, key) = take_while(is_param_char_or_escape)(input)?;
     let (input, _) = tag("=")(input)?;
-    let (input, value) = take_while(is_token)(input)?;
-    UriParam::from_key::<E>(key, value).and_then(|item| Ok((input, item)))
+    if key.eq_ignore_ascii_case(b"received") {
+        if let Ok(result) = parse_received_value::<E>(input) {
+            return Ok(result);
+        }
+    }
+    let (input, value) = take_while(is_param_char_or_escape)(input)?;
+    Ok((input, UriParam::from_key(key, value)))
+}
+
+/// The received parameter may hold an IPv6 address, with or without brackets.
+/// Both forms parse to the same `Domain::Ipv6`, which is displayed bracketed.
+fn parse_received_value<'a, E: ParseError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], UriParam, E> {
+    alt((
+        map(parse_ipv6_address, |addr| {
+            UriParam::Received(Domain::Ipv6(addr, None))
+        }),
+        map(parse_domain, UriParam::Received),
+    ))(input)
 }
 
 pub fn parse_single_param<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], UriParam, E> {
     let (input, _) = tag(";")(input)?;
-    let (input, key) = take_while(is_alphabetic)(input)?;
-    Ok((
-        input,
-        UriParam::Other(String::from_utf8_lossy(key).into(), None),
-    ))
+    let (input, key) = take_while1(is_param_char_or_escape)(input)?;
+    Ok((input, UriParam::from_flag(key)))
+}
+
+fn is_param_char_or_escape(chr: u8) -> bool {
+    is_param_char(chr) || chr == b'%'
 }
 
 /// Parse multiple uri parameters.
@@ -104,7 +201,7 @@ pub fn parse_params<'a, E: ParseError<&'a [u8]>>(
     let mut results = vec![];
     let mut data = input;
 
-    while let Ok((remains, param)) = parse_param::<E>(&data) {
+    while let Ok((remains, param)) = parse_param::<E>(data) {
         results.push(param);
         data = remains;
     }
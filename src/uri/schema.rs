@@ -3,11 +3,13 @@ use serde::{Deserialize, Serialize};
 use nom::{branch::alt, bytes::complete::tag_no_case, combinator::map, error::ParseError, IResult};
 use std::fmt;
 
-/// Sip URI Schema.
+/// Sip URI Schema. `Tel` is the telephone number schema of
+/// [RFC3966](https://tools.ietf.org/html/rfc3966).
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum UriSchema {
     Sip,
     Sips,
+    Tel,
 }
 
 impl fmt::Display for UriSchema {
@@ -15,16 +17,18 @@ impl fmt::Display for UriSchema {
         match self {
             UriSchema::Sip => write!(f, "sip"),
             UriSchema::Sips => write!(f, "sips"),
+            UriSchema::Tel => write!(f, "tel"),
         }
     }
 }
 
-/// Parse SIP URI schema. Only Accepts 'sip' and 'sips'.
+/// Parse SIP URI schema. Accepts 'sip', 'sips' and 'tel'.
 pub fn parse_schema<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], UriSchema, E> {
     alt::<_, _, E, _>((
         map(tag_no_case::<_, _, E>("sips"), |_| UriSchema::Sips),
         map(tag_no_case::<_, _, E>("sip"), |_| UriSchema::Sip),
+        map(tag_no_case::<_, _, E>("tel"), |_| UriSchema::Tel),
     ))(input)
 }
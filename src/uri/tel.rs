@@ -0,0 +1,210 @@
+use nom::{
+    bytes::complete::{tag, take_while, take_while1},
+    combinator::opt,
+    error::{ErrorKind, ParseError},
+    IResult,
+};
+
+use crate::{
+    parse::{escape, is_param_char, unescape},
+    uri::{Domain, Uri, UriAuth, UriParam, UriSchema},
+};
+
+use std::fmt;
+
+/// A telephone number uri as described in [RFC3966](https://tools.ietf.org/html/rfc3966).
+/// Global numbers start with a `+`, local numbers must carry a `phone-context`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TelUri {
+    /// The number including any visual separators.
+    pub number: String,
+    pub isub: Option<String>,
+    pub ext: Option<String>,
+    pub phone_context: Option<String>,
+    /// Any other parameters, in the order they appeared.
+    pub parameters: Vec<(String, Option<String>)>,
+}
+
+impl TelUri {
+    /// Create a uri for a global number such as `+1-555-0100`.
+    pub fn global<S: Into<String>>(number: S) -> TelUri {
+        TelUri {
+            number: number.into(),
+            isub: None,
+            ext: None,
+            phone_context: None,
+            parameters: vec![],
+        }
+    }
+
+    /// Create a uri for a number that is only valid within `context`.
+    pub fn local<N: Into<String>, C: Into<String>>(number: N, context: C) -> TelUri {
+        TelUri {
+            phone_context: Some(context.into()),
+            ..TelUri::global(number)
+        }
+    }
+
+    /// Set the extension of this number.
+    pub fn ext<S: Into<String>>(mut self, ext: S) -> TelUri {
+        self.ext = Some(ext.into());
+        self
+    }
+
+    /// Set the ISDN subaddress of this number.
+    pub fn isub<S: Into<String>>(mut self, isub: S) -> TelUri {
+        self.isub = Some(isub.into());
+        self
+    }
+
+    /// Add a parameter.
+    pub fn parameter<N: Into<String>>(mut self, name: N, value: Option<String>) -> TelUri {
+        self.parameters.push((name.into(), value));
+        self
+    }
+
+    /// Determine if this is a global number.
+    pub fn is_global(&self) -> bool {
+        self.number.starts_with('+')
+    }
+
+    /// The parameters of this uri in their canonical order.
+    fn all_parameters(&self) -> Vec<(String, Option<String>)> {
+        let mut params = vec![];
+        if let Some(isub) = &self.isub {
+            params.push(("isub".to_string(), Some(isub.clone())));
+        }
+        if let Some(ext) = &self.ext {
+            params.push(("ext".to_string(), Some(ext.clone())));
+        }
+        if let Some(context) = &self.phone_context {
+            params.push(("phone-context".to_string(), Some(context.clone())));
+        }
+        params.extend(self.parameters.iter().cloned());
+        params
+    }
+
+    /// Convert this number into a sip uri on `host` as described in
+    /// [RFC3261: Section 19.1.6](https://tools.ietf.org/html/rfc3261#section-19.1.6).
+    /// The number and it's parameters become the user part and the
+    /// `user=phone` parameter is added.
+    pub fn to_sip_uri(&self, host: Domain) -> Uri {
+        let mut user = self.number.clone();
+        for (key, value) in self.all_parameters() {
+            user.push(';');
+            user.push_str(&key);
+            if let Some(value) = value {
+                user.push('=');
+                user.push_str(&value);
+            }
+        }
+        Uri::sip(host)
+            .auth(UriAuth::new(user))
+            .parameter(UriParam::User("phone".into()))
+    }
+
+    /// Read a tel uri back out of a `Uri` with the tel schema.
+    pub fn from_uri(uri: &Uri) -> Option<TelUri> {
+        let number = match (&uri.schema, &uri.host) {
+            (Some(UriSchema::Tel), Domain::Domain(number, None)) => number.clone(),
+            _ => return None,
+        };
+        let mut tel = TelUri::global(number);
+        for param in &uri.parameters {
+            if let UriParam::Other(key, value) = param {
+                tel.set_parameter(key.clone(), value.clone());
+            }
+        }
+        Some(tel)
+    }
+
+    fn set_parameter(&mut self, key: String, value: Option<String>) {
+        match (key.to_ascii_lowercase().as_str(), value) {
+            ("isub", Some(value)) => self.isub = Some(value),
+            ("ext", Some(value)) => self.ext = Some(value),
+            ("phone-context", Some(value)) => self.phone_context = Some(value),
+            (_, value) => self.parameters.push((key, value)),
+        }
+    }
+}
+
+impl From<TelUri> for Uri {
+    fn from(tel: TelUri) -> Uri {
+        let parameters = tel
+            .all_parameters()
+            .into_iter()
+            .map(|(key, value)| UriParam::Other(key, value))
+            .collect();
+        Uri::new(UriSchema::Tel, Domain::Domain(tel.number, None)).parameters(parameters)
+    }
+}
+
+impl fmt::Display for TelUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tel:{}", self.number)?;
+        for (key, value) in self.all_parameters() {
+            write!(f, ";{}", escape(&key, is_param_char))?;
+            if let Some(value) = value {
+                write!(f, "={}", escape(&value, is_param_char))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_visual_separator(chr: u8) -> bool {
+    b"-.()".contains(&chr)
+}
+
+fn is_global_number_char(chr: u8) -> bool {
+    chr.is_ascii_digit() || is_visual_separator(chr)
+}
+
+fn is_local_number_char(chr: u8) -> bool {
+    chr.is_ascii_hexdigit() || chr == b'*' || chr == b'#' || is_visual_separator(chr)
+}
+
+fn is_tel_param_char(chr: u8) -> bool {
+    is_param_char(chr) || chr == b'%'
+}
+
+/// Parse the part of a tel uri following the `tel:` schema.
+/// ([RFC3966: Section 3](https://tools.ietf.org/html/rfc3966#section-3))
+pub fn parse_tel_uri<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], TelUri, E> {
+    let (rest, plus) = opt(tag("+"))(input)?;
+    let (mut rest, digits) = if plus.is_some() {
+        take_while1(is_global_number_char)(rest)?
+    } else {
+        take_while1(is_local_number_char)(rest)?
+    };
+    if !digits
+        .iter()
+        .any(|chr| chr.is_ascii_hexdigit() || *chr == b'*' || *chr == b'#')
+    {
+        return Err(nom::Err::Error(E::from_error_kind(input, ErrorKind::Digit)));
+    }
+    let mut number = String::from_utf8_lossy(digits).to_string();
+    if plus.is_some() {
+        number.insert(0, '+');
+    }
+    let mut tel = TelUri::global(number);
+    while let Ok((data, _)) = tag::<_, _, E>(";")(rest) {
+        let (data, key) = take_while1(is_tel_param_char)(data)?;
+        let (data, value) = match tag::<_, _, E>("=")(data) {
+            Ok((data, _)) => {
+                let (data, value) = take_while(is_tel_param_char)(data)?;
+                (data, Some(unescape(value)))
+            },
+            Err(_) => (data, None),
+        };
+        tel.set_parameter(unescape(key), value);
+        rest = data;
+    }
+    if !tel.is_global() && tel.phone_context.is_none() {
+        return Err(nom::Err::Error(E::from_error_kind(
+            input,
+            ErrorKind::Verify,
+        )));
+    }
+    Ok((rest, tel))
+}
@@ -0,0 +1,124 @@
+use libsip::*;
+
+use nom::error::VerboseError;
+
+const CHALLENGE: &[u8] = b"SIP/2.0 407 Proxy Authentication Required\r\n\
+Via: SIP/2.0/UDP 192.168.1.10:5060;branch=z9hG4bK776asdhds\r\n\
+To: <sip:bob@example.com>;tag=1928301774\r\n\
+From: <sip:alice@example.com>;tag=a73kszlfl\r\n\
+Call-ID: a84b4c76e66710\r\n\
+CSeq: 1 MESSAGE\r\n\
+Proxy-Authenticate: Digest realm=\"atlanta.com\", nonce=\"f84f1cec41e6cbe5aea9c8e88d359\", qop=\"auth\"\r\n\
+Proxy-Authenticate: Digest realm=\"biloxi.com\", nonce=\"c60f3082ee1212b402a21831ae\", algorithm=SHA-256\r\n\
+Content-Length: 0\r\n\r\n";
+
+fn challenge() -> SipMessage {
+    parse_message::<VerboseError<&[u8]>>(CHALLENGE).unwrap().1
+}
+
+fn via() -> Header {
+    Header::Via(ViaHeader {
+        branch: Some("z9hG4bK776asdhds".into()),
+        ..ViaHeader::new(ip_domain!(192, 168, 1, 10, 5060), Transport::Udp)
+    })
+}
+
+fn credentials(msg: &SipMessage) -> Vec<(bool, AuthHeader)> {
+    msg.headers()
+        .iter()
+        .filter_map(|header| match header {
+            Header::Authorization(auth) => Some((false, auth.clone())),
+            Header::ProxyAuthorization(auth) => Some((true, auth.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn answer_several_realms() {
+    let mut writer = MessageWriter::new(Uri::sip(domain!("example.com")).auth(uri_auth!("alice")));
+    let message = writer
+        .write_message(
+            b"hello".to_vec(),
+            Uri::sip(domain!("example.com")).auth(uri_auth!("bob")),
+            via(),
+            &HeaderWriteConfig::default(),
+        )
+        .unwrap();
+    let retry = writer
+        .authenticate(&message, &challenge(), "alice", "secret")
+        .unwrap();
+
+    assert_eq!((2, Method::Message), retry.cseq().unwrap());
+    assert_eq!(message.call_id(), retry.call_id());
+    assert_ne!(message.via_header_branch(), retry.via_header_branch());
+    assert_eq!(message.body(), retry.body());
+
+    let creds = credentials(&retry);
+    assert_eq!(2, creds.len());
+    let realms: Vec<_> = creds
+        .iter()
+        .map(|(proxy, AuthHeader(_, params))| (*proxy, params.get("realm").unwrap().clone()))
+        .collect();
+    assert_eq!(
+        vec![(true, "atlanta.com".to_string()), (true, "biloxi.com".to_string())],
+        realms
+    );
+    let (_, AuthHeader(_, biloxi)) = &creds[1];
+    assert_eq!(Some(&"SHA-256".to_string()), biloxi.get("algorithm"));
+    assert_eq!(None, biloxi.get("qop"));
+    assert_eq!(
+        Some(&"sip:bob@example.com".to_string()),
+        biloxi.get("uri")
+    );
+}
+
+#[test]
+fn reject_non_challenges() {
+    let mut writer = InviteWriter::new(Uri::sip(domain!("example.com")).auth(uri_auth!("alice")));
+    let invite = writer
+        .generate_invite(
+            Uri::sip(domain!("example.com")).auth(uri_auth!("bob")),
+            vec![],
+            Header::Via(ViaHeader::new(
+                ip_domain!(192, 168, 1, 10, 5060),
+                Transport::Udp,
+            )),
+            ContactHeader::new(Uri::sip(ip_domain!(192, 168, 1, 10, 5060))),
+            &Default::default(),
+        )
+        .unwrap();
+    let response = ResponseGenerator::new()
+        .code(486)
+        .header(Header::CSeq(1, Method::Invite))
+        .build()
+        .unwrap();
+    assert!(writer
+        .authenticate(&invite, &response, "alice", "secret")
+        .is_err());
+}
+
+#[test]
+fn registration_answers_proxy_challenge() {
+    let mut reg = RegistrationManager::new(
+        Uri::sip(domain!("example.com")).auth(uri_auth!("alice")),
+        Uri::sip(ip_domain!(192, 168, 1, 10, 5060)),
+    );
+    reg.username("alice");
+    reg.password("secret");
+    let cfg = HeaderWriteConfig::default();
+    assert!(credentials(&reg.get_request(&cfg).unwrap()).is_empty());
+
+    reg.set_challenge(challenge()).unwrap();
+    let request = reg.get_request(&cfg).unwrap();
+    let creds = credentials(&request);
+    assert_eq!(2, creds.len());
+    assert!(creds.iter().all(|(proxy, _)| *proxy));
+    let (_, AuthHeader(_, atlanta)) = &creds[0];
+    assert_eq!(Some(&"00000001".to_string()), atlanta.get("nc"));
+    assert_eq!(Some(&"sip:example.com".to_string()), atlanta.get("uri"));
+
+    let request = reg.get_request(&cfg).unwrap();
+    let (_, AuthHeader(_, atlanta)) = &credentials(&request)[0];
+    assert_eq!(Some(&"00000002".to_string()), atlanta.get("nc"));
+}
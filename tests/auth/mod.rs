@@ -0,0 +1,304 @@
+use libsip::*;
+
+/// The example of RFC2617: Section 3.5.
+fn rfc2617<'a>() -> DigestInput<'a> {
+    DigestInput {
+        algorithm: DigestAlgorithm::Md5,
+        username: "Mufasa",
+        realm: "testrealm@host.com",
+        password: "Circle Of Life",
+        nonce: "dcd98b7102dd2f0e8b11d0f600bfb0c093",
+        method: "GET",
+        uri: "/dir/index.html",
+        qop: Some("auth"),
+        nc: 1,
+        cnonce: "0a4f113b",
+        body: b"",
+    }
+}
+
+/// The examples of RFC7616: Section 3.9.1.
+fn rfc7616<'a>(algorithm: DigestAlgorithm) -> DigestInput<'a> {
+    DigestInput {
+        algorithm,
+        username: "Mufasa",
+        realm: "http-auth@example.org",
+        password: "Circle of Life",
+        nonce: "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v",
+        method: "GET",
+        uri: "/dir/index.html",
+        qop: Some("auth"),
+        nc: 1,
+        cnonce: "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+        body: b"",
+    }
+}
+
+fn challenge(params: &[(&str, &str)]) -> AuthHeader {
+    let mut map = AuthParams::new();
+    for (key, value) in params {
+        map.insert(key.to_string(), value.to_string());
+    }
+    AuthHeader(AuthSchema::Digest, map)
+}
+
+#[test]
+fn rfc2617_vector() {
+    assert_eq!(
+        "6629fae49393a05397450978507c4ef1",
+        rfc2617().response()
+    );
+}
+
+#[test]
+fn rfc7616_vectors() {
+    assert_eq!(
+        "8ca523f5e9506fed4657c9700eebdbec",
+        rfc7616(DigestAlgorithm::Md5).response()
+    );
+    assert_eq!(
+        "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1",
+        rfc7616(DigestAlgorithm::Sha256).response()
+    );
+}
+
+/// The inputs of RFC7616: Section 3.9.2. The hashes published there don't
+/// match these inputs (see the RFC errata), the expected values were
+/// calculated with an independent SHA-512/256 implementation.
+#[test]
+fn rfc7616_userhash_vector() {
+    let input = DigestInput {
+        algorithm: DigestAlgorithm::Sha512_256,
+        username: "J\u{e4}s\u{f8}n Doe",
+        realm: "api@example.org",
+        password: "Secret, or not?",
+        nonce: "5TsQWLVdgBdmrQ0XsxbDODV+57QdFR34I9HAbC/RVvkK",
+        method: "GET",
+        uri: "/doe.json",
+        qop: Some("auth"),
+        nc: 1,
+        cnonce: "NTg6RKcb9boFIAS3KrFK9BGeh+iDa/sm6jUMp2wds69v",
+        body: b"",
+    };
+    assert_eq!(
+        "793263caabb707a56211940d90411ea4a575adeccb7e360aeb624ed06ece9b0b",
+        input.username_hash()
+    );
+    assert_eq!(
+        "3798d4131c277846293534c3edc11bd8a5e4cdcbff78b05db9d95eeb1cec68a5",
+        input.response()
+    );
+}
+
+#[test]
+fn sha512_256() {
+    // FIPS 180-4 example for SHA-512/256.
+    assert_eq!(
+        "53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23",
+        DigestAlgorithm::Sha512_256.hash("abc")
+    );
+}
+
+#[test]
+fn session_algorithms() {
+    let input = DigestInput {
+        algorithm: DigestAlgorithm::Md5Sess,
+        ..rfc2617()
+    };
+    let ha1 = md5::compute("Mufasa:testrealm@host.com:Circle Of Life");
+    let ha1 = md5::compute(format!(
+        "{:x}:dcd98b7102dd2f0e8b11d0f600bfb0c093:0a4f113b",
+        ha1
+    ));
+    let ha2 = md5::compute("GET:/dir/index.html");
+    let expected = md5::compute(format!(
+        "{:x}:dcd98b7102dd2f0e8b11d0f600bfb0c093:00000001:0a4f113b:auth:{:x}",
+        ha1, ha2
+    ));
+    assert_eq!(format!("{:x}", expected), input.response());
+
+    for alg in &[
+        DigestAlgorithm::Sha256Sess,
+        DigestAlgorithm::Sha512_256Sess,
+    ] {
+        let plain = DigestInput {
+            algorithm: match alg {
+                DigestAlgorithm::Sha256Sess => DigestAlgorithm::Sha256,
+                _ => DigestAlgorithm::Sha512_256,
+            },
+            ..rfc7616(*alg)
+        };
+        assert_ne!(plain.response(), rfc7616(*alg).response());
+        assert_eq!(64, rfc7616(*alg).response().len());
+    }
+}
+
+#[test]
+fn algorithm_names() {
+    for name in &[
+        "MD5",
+        "MD5-sess",
+        "SHA-256",
+        "SHA-256-sess",
+        "SHA-512-256",
+        "SHA-512-256-sess",
+    ] {
+        let alg: DigestAlgorithm = name.parse().unwrap();
+        assert_eq!(*name, format!("{}", alg));
+    }
+    assert!("SHA-1".parse::<DigestAlgorithm>().is_err());
+}
+
+#[test]
+fn authenticate_with_userhash() {
+    let uri = Uri::sip(domain!("example.com"));
+    let header = challenge(&[
+        ("realm", "example.com"),
+        ("nonce", "abc123"),
+        ("qop", "auth"),
+        ("algorithm", "SHA-256"),
+        ("userhash", "true"),
+    ]);
+    let ctx = AuthContext::new("alice", "secret", Method::Register, &uri).cnonce("0a4f113b");
+    let AuthHeader(_, params) = header.authenticate(ctx).unwrap();
+    assert_eq!(
+        Some(&DigestAlgorithm::Sha256.hash("alice:example.com")),
+        params.get("username")
+    );
+    assert_eq!(Some(&"true".to_string()), params.get("userhash"));
+    assert_eq!(Some(&"SHA-256".to_string()), params.get("algorithm"));
+    assert_eq!(
+        64,
+        params.get("response").map(String::len).unwrap_or_default()
+    );
+}
+
+#[test]
+fn rfc2069_digest() {
+    let input = DigestInput {
+        qop: None,
+        ..rfc2617()
+    };
+    let ha1 = md5::compute("Mufasa:testrealm@host.com:Circle Of Life");
+    let ha2 = md5::compute("GET:/dir/index.html");
+    let expected = md5::compute(format!(
+        "{:x}:dcd98b7102dd2f0e8b11d0f600bfb0c093:{:x}",
+        ha1, ha2
+    ));
+    assert_eq!(format!("{:x}", expected), input.response());
+}
+
+#[test]
+fn auth_int_hashes_body() {
+    let body = b"v=0\r\n";
+    let input = DigestInput {
+        qop: Some("auth-int"),
+        body,
+        ..rfc2617()
+    };
+    let ha1 = md5::compute("Mufasa:testrealm@host.com:Circle Of Life");
+    let ha2 = md5::compute(format!("GET:/dir/index.html:{:x}", md5::compute(body)));
+    let expected = md5::compute(format!(
+        "{:x}:dcd98b7102dd2f0e8b11d0f600bfb0c093:00000001:0a4f113b:auth-int:{:x}",
+        ha1, ha2
+    ));
+    assert_eq!(format!("{:x}", expected), input.response());
+}
+
+#[test]
+fn authenticate_request() {
+    let uri = Uri::sip(domain!("example.com")).auth(uri_auth!("bob"));
+    let header = challenge(&[
+        ("realm", "example.com"),
+        ("nonce", "abc123"),
+        ("qop", "auth-int"),
+        ("opaque", "xyz"),
+    ]);
+    let body = b"v=0\r\n";
+    let ctx = AuthContext::new("alice", "secret", Method::Invite, &uri)
+        .nc(2)
+        .body(body)
+        .cnonce("0a4f113b");
+    let AuthHeader(_, params) = header.authenticate(ctx).unwrap();
+    let expected = DigestInput {
+        algorithm: DigestAlgorithm::Md5,
+        username: "alice",
+        realm: "example.com",
+        password: "secret",
+        nonce: "abc123",
+        method: "INVITE",
+        uri: "sip:bob@example.com",
+        qop: Some("auth-int"),
+        nc: 2,
+        cnonce: "0a4f113b",
+        body,
+    };
+    assert_eq!(
+        Some(&expected.response()),
+        params.get("response")
+    );
+    assert_eq!(Some(&"sip:bob@example.com".to_string()), params.get("uri"));
+    assert_eq!(Some(&"00000002".to_string()), params.get("nc"));
+    assert_eq!(Some(&"xyz".to_string()), params.get("opaque"));
+}
+
+#[test]
+fn authenticate_without_qop() {
+    let uri = Uri::sip(domain!("example.com"));
+    let header = challenge(&[("realm", "example.com"), ("nonce", "abc123")]);
+    let ctx = AuthContext::new("alice", "secret", Method::Message, &uri);
+    let AuthHeader(_, params) = header.authenticate(ctx).unwrap();
+    assert_eq!(None, params.get("qop"));
+    assert_eq!(None, params.get("cnonce"));
+    assert_eq!(None, params.get("nc"));
+}
+
+mod challenges;
+
+#[test]
+fn strongest_qop() {
+    let uri = Uri::sip(domain!("example.com"));
+    let header = challenge(&[
+        ("realm", "example.com"),
+        ("nonce", "abc123"),
+        ("qop", "auth,auth-int"),
+    ]);
+    let ctx = AuthContext::new("alice", "secret", Method::Register, &uri);
+    let AuthHeader(_, params) = header.authenticate(ctx).unwrap();
+    assert_eq!(Some(&"auth-int".to_string()), params.get("qop"));
+}
+
+#[test]
+fn malformed_challenges() {
+    let uri = Uri::sip(domain!("example.com"));
+    let ctx = || AuthContext::new("alice", "secret", Method::Register, &uri);
+
+    let header = challenge(&[("nonce", "abc123")]);
+    assert_eq!(
+        Err(AuthError::MissingParameter("realm")),
+        header.authenticate(ctx())
+    );
+    let header = challenge(&[("realm", "example.com")]);
+    assert_eq!(
+        Err(AuthError::MissingParameter("nonce")),
+        header.authenticate(ctx())
+    );
+    let header = challenge(&[
+        ("realm", "example.com"),
+        ("nonce", "abc123"),
+        ("algorithm", "SHA-1"),
+    ]);
+    assert_eq!(
+        Err(AuthError::UnsupportedAlgorithm("SHA-1".into())),
+        header.authenticate(ctx())
+    );
+    let header = challenge(&[
+        ("realm", "example.com"),
+        ("nonce", "abc123"),
+        ("qop", "auth-conf"),
+    ]);
+    assert_eq!(
+        Err(AuthError::UnsupportedQop("auth-conf".into())),
+        header.authenticate(ctx())
+    );
+}
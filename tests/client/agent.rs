@@ -0,0 +1,430 @@
+use libsip::*;
+
+use nom::error::VerboseError;
+use tokio::{net::UdpSocket, time::timeout};
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+struct Recorder {
+    messages: Vec<Vec<u8>>,
+    notifications: usize,
+    invites: usize,
+    errors: usize,
+}
+
+impl RequestHandler for Recorder {
+    /// Offerless calls are left ringing.
+    fn on_invite(&mut self, invite: &SipMessage) -> Option<Vec<u8>> {
+        self.invites += 1;
+        if invite.body().is_empty() {
+            None
+        } else {
+            Some(b"v=0\r\n".to_vec())
+        }
+    }
+
+    fn on_message(&mut self, message: &SipMessage) {
+        self.messages.push(message.body().clone());
+    }
+
+    fn on_notify(&mut self, _notify: &SipMessage) {
+        self.notifications += 1;
+    }
+
+    fn on_error(&mut self, _error: std::io::Error) {
+        self.errors += 1;
+    }
+}
+
+fn domain(addr: SocketAddr) -> Domain {
+    match addr.ip() {
+        IpAddr::V4(ip) => Domain::Ipv4(ip, Some(addr.port())),
+        IpAddr::V6(ip) => Domain::Ipv6(ip, Some(addr.port())),
+    }
+}
+
+/// A registrar listening on a loopback socket.
+struct LoopbackRegistrar {
+    socket: UdpSocket,
+    registrar: Registrar<HashMap<String, Vec<Binding>>>,
+}
+
+impl LoopbackRegistrar {
+    async fn recv(&mut self) -> (SipMessage, SocketAddr) {
+        let mut buf = vec![0; 65535];
+        let (amount, source) = timeout(Duration::from_secs(5), self.socket.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        let (_, msg) = parse_message::<VerboseError<&[u8]>>(&buf[..amount]).unwrap();
+        (msg, source)
+    }
+
+    async fn send(&mut self, msg: &SipMessage, target: SocketAddr) {
+        self.socket
+            .send_to(format!("{}", msg).as_bytes(), target)
+            .await
+            .unwrap();
+    }
+
+    /// Answer the next REGISTER request.
+    async fn process(&mut self) {
+        let (request, source) = self.recv().await;
+        assert_eq!(Ok(Method::Register), request.method());
+        let response = self.registrar.process(&request, Instant::now()).unwrap();
+        self.send(&response, source).await;
+    }
+
+    fn request(&self, method: Method, body: &[u8]) -> SipMessage {
+        let local = self.socket.local_addr().unwrap();
+        let via = ViaHeader {
+            rport: Some(None),
+            ..ViaHeader::new(domain(local), Transport::Udp)
+        };
+        let mut request = RequestGenerator::new()
+            .method(method)
+            .uri(Uri::sip(ip_domain!(127, 0, 0, 1)).auth(uri_auth!("20")))
+            .header(Header::From(
+                NamedHeader::new(Uri::sip(domain(local)).auth(uri_auth!("30")))
+                    .param("tag", Some("1928301774")),
+            ))
+            .header(Header::To(NamedHeader::new(
+                Uri::sip(ip_domain!(127, 0, 0, 1)).auth(uri_auth!("20")),
+            )))
+            .header(Header::CallId(format!("{}@example.com", method)))
+            .header(Header::CSeq(1, method))
+            .header(Header::Contact(ContactHeader::new(
+                Uri::sip(domain(local)).auth(uri_auth!("30")),
+            )))
+            .header(Header::ContentLength(body.len() as u32))
+            .body(body.to_vec())
+            .build()
+            .unwrap();
+        request.push_via(via);
+        request
+    }
+
+    /// A request within the call or transaction `to` belongs to.
+    fn request_to(&self, method: Method, to: &SipMessage) -> SipMessage {
+        let mut request = self.request(method, b"");
+        *request.call_id_mut().unwrap() = to.call_id().unwrap().clone();
+        if let Ok(tag) = to.to_header_tag() {
+            request.set_to_header_tag(tag.clone());
+        }
+        request
+    }
+}
+
+#[tokio::test]
+async fn loopback_registrar() {
+    let mut registrar = LoopbackRegistrar {
+        socket: UdpSocket::bind("127.0.0.1:0").await.unwrap(),
+        registrar: Registrar::new(HashMap::new()),
+    };
+    let registrar_addr = registrar.socket.local_addr().unwrap();
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let local_addr = socket.local_addr().unwrap();
+
+    let local_uri = Uri::sip(domain(local_addr))
+        .auth(uri_auth!("20"))
+        .parameter(UriParam::Transport(Transport::Udp));
+    let account_uri = Uri::sip(domain(registrar_addr)).auth(uri_auth!("20"));
+    let phone = SoftPhone::new(local_uri, account_uri);
+    let agent = UserAgent::new(socket, registrar_addr, phone, Recorder::default()).unwrap();
+    assert_eq!(local_addr, agent.local_addr().unwrap());
+    let runner = agent.clone();
+    tokio::spawn(async move { runner.run().await });
+
+    let registered = agent.register().unwrap();
+    registrar.process().await;
+    let response = timeout(Duration::from_secs(5), registered)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Ok(200), response.status_code());
+    assert!(agent.is_registered());
+
+    let message = registrar.request(Method::Message, b"Hello");
+    registrar.send(&message, local_addr).await;
+    let (response, _) = registrar.recv().await;
+    assert_eq!(Ok(200), response.status_code());
+    assert_eq!(Ok((1, Method::Message)), response.cseq());
+
+    registrar
+        .send(&registrar.request(Method::Notify, b""), local_addr)
+        .await;
+    assert_eq!(Ok(200), registrar.recv().await.0.status_code());
+
+    registrar
+        .send(&registrar.request(Method::Invite, b"v=0\r\n"), local_addr)
+        .await;
+    assert_eq!(Ok(100), registrar.recv().await.0.status_code());
+    assert_eq!(Ok(180), registrar.recv().await.0.status_code());
+    let (ok, _) = registrar.recv().await;
+    assert_eq!(Ok(200), ok.status_code());
+    assert_eq!(&b"v=0\r\n".to_vec(), ok.body());
+    assert!(ok.contact_header().is_ok());
+
+    // The 200 OK is retransmitted until it is acknowledged.
+    let (retransmitted, _) = registrar.recv().await;
+    assert_eq!(ok, retransmitted);
+    let mut ack = registrar.request(Method::Ack, b"");
+    ack.set_to_header_tag(ok.to_header_tag().unwrap().clone());
+    *ack.call_id_mut().unwrap() = ok.call_id().unwrap().clone();
+    registrar.send(&ack, local_addr).await;
+
+    // A re-INVITE stays within the call, the handler declines it.
+    let mut reinvite = registrar.request_to(Method::Invite, &ok);
+    *reinvite.cseq_mut().unwrap().0 = 2;
+    registrar.send(&reinvite, local_addr).await;
+    assert_eq!(Ok(100), registrar.recv().await.0.status_code());
+    let (declined, _) = registrar.recv().await;
+    assert_eq!(Ok(488), declined.status_code());
+    assert_eq!(ok.to_header_tag(), declined.to_header_tag());
+    let mut ack = registrar.request_to(Method::Ack, &ok);
+    *ack.cseq_mut().unwrap().0 = 2;
+    registrar.send(&ack, local_addr).await;
+
+    agent.with_handler(|handler| {
+        assert_eq!(vec![b"Hello".to_vec()], handler.messages);
+        assert_eq!(1, handler.notifications);
+        assert_eq!(1, handler.invites);
+    });
+
+    // A BYE ends the call, so a second one matches no call.
+    let mut bye = registrar.request_to(Method::Bye, &ok);
+    *bye.cseq_mut().unwrap().0 = 3;
+    registrar.send(&bye, local_addr).await;
+    assert_eq!(Ok(200), registrar.recv().await.0.status_code());
+    let mut bye = registrar.request_to(Method::Bye, &ok);
+    *bye.cseq_mut().unwrap().0 = 4;
+    registrar.send(&bye, local_addr).await;
+    assert_eq!(Ok(481), registrar.recv().await.0.status_code());
+
+    // A ringing call is ended by CANCEL with a 487 for the INVITE.
+    let invite = registrar.request(Method::Invite, b"");
+    registrar.send(&invite, local_addr).await;
+    assert_eq!(Ok(100), registrar.recv().await.0.status_code());
+    let (ringing, _) = registrar.recv().await;
+    assert_eq!(Ok(180), ringing.status_code());
+    let mut cancel = registrar.request_to(Method::Cancel, &invite);
+    cancel.pop_via();
+    cancel.push_via(invite.via_header().unwrap().clone());
+    registrar.send(&cancel, local_addr).await;
+    let (terminated, _) = registrar.recv().await;
+    assert_eq!(Ok(487), terminated.status_code());
+    assert_eq!(Ok((1, Method::Invite)), terminated.cseq());
+    assert_eq!(ringing.to_header_tag(), terminated.to_header_tag());
+    let (cancelled, _) = registrar.recv().await;
+    assert_eq!(Ok(200), cancelled.status_code());
+    assert_eq!(Ok((1, Method::Cancel)), cancelled.cseq());
+    assert_eq!(ringing.to_header_tag(), cancelled.to_header_tag());
+    let mut ack = registrar.request_to(Method::Ack, &terminated);
+    ack.pop_via();
+    ack.push_via(invite.via_header().unwrap().clone());
+    registrar.send(&ack, local_addr).await;
+    // The call is gone.
+    let cancel = registrar.request_to(Method::Cancel, &invite);
+    registrar.send(&cancel, local_addr).await;
+    assert_eq!(Ok(481), registrar.recv().await.0.status_code());
+
+    // Unknown methods are rejected.
+    registrar
+        .send(&registrar.request(Method::Subscribe, b""), local_addr)
+        .await;
+    assert_eq!(Ok(501), registrar.recv().await.0.status_code());
+
+    // Outgoing requests resolve to their final response.
+    let mut options = registrar.request(Method::Options, b"");
+    options.pop_via();
+    options.push_via(ViaHeader::new(domain(local_addr), Transport::Udp));
+    let pending = agent.send_request(options).unwrap();
+    let (request, source) = registrar.recv().await;
+    assert_eq!(local_addr, source);
+    let response = ResponseGenerator::from_request(&request)
+        .code(200)
+        .build()
+        .unwrap();
+    registrar.send(&response, local_addr).await;
+    let response = timeout(Duration::from_secs(5), pending)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Ok((1, Method::Options)), response.cseq());
+
+    // A challenged INVITE is sent again with credentials.
+    agent.with_phone(|phone| {
+        phone.registry_mut().username("20");
+        phone.registry_mut().password("secret");
+    });
+    let bob = Uri::sip(domain(registrar_addr)).auth(uri_auth!("30"));
+    let call = agent.invite(bob, b"v=0\r\n".to_vec()).unwrap();
+    let (invite, _) = registrar.recv().await;
+    assert_eq!(Ok(Method::Invite), invite.method());
+    let mut params = AuthParams::new();
+    params.insert("realm".into(), "example.com".into());
+    params.insert("nonce".into(), "f84f1cec41e6cbe5".into());
+    let challenge = ResponseGenerator::from_request(&invite)
+        .code(401)
+        .header(Header::WwwAuthenticate(AuthHeader(
+            AuthSchema::Digest,
+            params.clone(),
+        )))
+        .build()
+        .unwrap();
+    registrar.send(&challenge, local_addr).await;
+    let (ack, _) = registrar.recv().await;
+    assert_eq!(Ok(Method::Ack), ack.method());
+    let (retry, _) = registrar.recv().await;
+    assert_eq!(Ok((2, Method::Invite)), retry.cseq());
+    assert!(retry
+        .headers()
+        .iter()
+        .any(|h| matches!(h, Header::Authorization(_))));
+    let ok = ResponseGenerator::from_request(&retry)
+        .code(200)
+        .build()
+        .unwrap();
+    registrar.send(&ok, local_addr).await;
+    let response = timeout(Duration::from_secs(5), call)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Ok(200), response.status_code());
+
+    // Credentials are only sent once for a realm.
+    let registered = agent.register().unwrap();
+    let (register, _) = registrar.recv().await;
+    let challenge = ResponseGenerator::from_request(&register)
+        .code(401)
+        .header(Header::WwwAuthenticate(AuthHeader(
+            AuthSchema::Digest,
+            params.clone(),
+        )))
+        .build()
+        .unwrap();
+    registrar.send(&challenge, local_addr).await;
+    let (retry, _) = registrar.recv().await;
+    assert!(retry
+        .headers()
+        .iter()
+        .any(|h| matches!(h, Header::Authorization(_))));
+    let challenge = ResponseGenerator::from_request(&retry)
+        .code(401)
+        .header(Header::WwwAuthenticate(AuthHeader(AuthSchema::Digest, params)))
+        .build()
+        .unwrap();
+    registrar.send(&challenge, local_addr).await;
+    let response = timeout(Duration::from_secs(5), registered)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Ok(401), response.status_code());
+
+    // A second runner is refused.
+    assert!(agent.run().await.is_err());
+}
+
+#[tokio::test]
+async fn refresh_failure_backs_off() {
+    let mut registrar = LoopbackRegistrar {
+        socket: UdpSocket::bind("127.0.0.1:0").await.unwrap(),
+        registrar: Registrar::new(HashMap::new()),
+    };
+    let registrar_addr = registrar.socket.local_addr().unwrap();
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let local_addr = socket.local_addr().unwrap();
+    let local_uri = Uri::sip(domain(local_addr)).auth(uri_auth!("20"));
+    let account_uri = Uri::sip(domain(registrar_addr)).auth(uri_auth!("20"));
+    let phone = SoftPhone::new(local_uri, account_uri);
+    let agent = UserAgent::new(socket, registrar_addr, phone, Recorder::default()).unwrap();
+    let runner = agent.clone();
+    tokio::spawn(async move { runner.run().await });
+
+    // The registration is refreshed within a second.
+    let registered = agent.register().unwrap();
+    let (register, _) = registrar.recv().await;
+    let ok = ResponseGenerator::from_request(&register)
+        .code(200)
+        .header(Header::Expires(1))
+        .build()
+        .unwrap();
+    registrar.send(&ok, local_addr).await;
+    timeout(Duration::from_secs(5), registered)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let (refresh, _) = registrar.recv().await;
+    assert_eq!(Ok(Method::Register), refresh.method());
+    let forbidden = ResponseGenerator::from_request(&refresh)
+        .code(403)
+        .build()
+        .unwrap();
+    registrar.send(&forbidden, local_addr).await;
+
+    // The failed refresh is not sent again straight away.
+    let mut buf = vec![0; 65535];
+    assert!(
+        timeout(Duration::from_secs(3), registrar.socket.recv_from(&mut buf))
+            .await
+            .is_err()
+    );
+    let refresh_at = agent.with_phone(|phone| phone.registry().refresh_at().unwrap());
+    assert!(refresh_at > Instant::now() + Duration::from_secs(20));
+}
+
+#[tokio::test]
+async fn errors_do_not_stop_agent() {
+    let mut registrar = LoopbackRegistrar {
+        socket: UdpSocket::bind("127.0.0.1:0").await.unwrap(),
+        registrar: Registrar::new(HashMap::new()),
+    };
+    let registrar_addr = registrar.socket.local_addr().unwrap();
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let local_addr = socket.local_addr().unwrap();
+    let local_uri = Uri::sip(domain(local_addr)).auth(uri_auth!("20"));
+    let account_uri = Uri::sip(domain(registrar_addr)).auth(uri_auth!("20"));
+    let phone = SoftPhone::new(local_uri, account_uri);
+    let agent = UserAgent::new(socket, registrar_addr, phone, Recorder::default()).unwrap();
+    let runner = agent.clone();
+    tokio::spawn(async move { runner.run().await });
+
+    // Without a Via header the request matches no transaction.
+    let mut orphan = registrar.request(Method::Message, b"orphan");
+    orphan
+        .headers_mut()
+        .0
+        .retain(|header| !matches!(header, Header::Via(_)));
+    registrar.send(&orphan, local_addr).await;
+
+    // A call without a Contact header can't be accepted, it is answered
+    // with a 500 after ringing.
+    let mut invite = registrar.request(Method::Invite, b"v=0\r\n");
+    invite
+        .headers_mut()
+        .0
+        .retain(|header| !matches!(header, Header::Contact(_)));
+    registrar.send(&invite, local_addr).await;
+    let mut codes = vec![];
+    while codes.last() != Some(&500) {
+        let (response, _) = registrar.recv().await;
+        codes.push(response.status_code().unwrap());
+    }
+    assert_eq!(vec![100, 180, 500], codes);
+
+    // Later requests are still answered.
+    let message = registrar.request(Method::Message, b"hello");
+    registrar.send(&message, local_addr).await;
+    let (response, _) = registrar.recv().await;
+    assert_eq!(Ok(200), response.status_code());
+    agent.with_handler(|handler| {
+        assert_eq!(vec![b"hello".to_vec()], handler.messages);
+        assert_eq!(2, handler.errors);
+    });
+}
@@ -0,0 +1,175 @@
+use libsip::*;
+
+use nom::error::VerboseError;
+
+const UNAUTHORIZED: &str = "SIP/2.0 401 Unauthorized\r\n\
+Via: SIP/2.0/TCP 192.168.1.10:5060;branch=z9hG4bK776asdhds\r\n\
+To: <sip:bob@example.com>;tag=1928301774\r\n\
+From: <sip:alice@example.com>;tag=a73kszlfl\r\n\
+Call-ID: a84b4c76e66710\r\n\
+CSeq: 1 INVITE\r\n\
+WWW-Authenticate: Digest realm=\"example.com\", nonce=\"f84f1cec41e6cbe5aea9c8e88d359\", qop=\"auth\"\r\n\
+Content-Length: 0\r\n\r\n";
+
+fn phone() -> SoftPhone {
+    SoftPhone::new(
+        Uri::sip(ip_domain!(192, 168, 1, 10, 5060)).parameter(UriParam::Transport(Transport::Tcp)),
+        Uri::sip(domain!("example.com")).auth(uri_auth!("alice")),
+    )
+}
+
+#[test]
+fn complete_invite() {
+    let mut phone = phone();
+    phone.registry_mut().username("alice");
+    phone.header_cfg_mut().supported = Some(vec!["timer".into()]);
+    let sdp = b"v=0\r\n".to_vec();
+    let bob = Uri::sip(domain!("example.com")).auth(uri_auth!("bob"));
+    let invite = phone.send_invite(sdp.clone(), bob.clone()).unwrap();
+
+    // The INVITE survives a trip over the wire.
+    let invite = parse_message::<VerboseError<&[u8]>>(format!("{}", invite).as_bytes())
+        .unwrap()
+        .1;
+    let via = invite.via_header().unwrap();
+    assert_eq!(Transport::Tcp, via.transport);
+    assert_eq!(ip_domain!(192, 168, 1, 10, 5060), via.sent_by);
+    assert!(via.is_rfc3261_branch());
+    assert!(invite.from_header_tag().is_ok());
+    assert_eq!(Ok(&NamedHeader::new(bob)), invite.to_header());
+    assert_eq!(Ok((1, Method::Invite)), invite.cseq());
+    assert_eq!(
+        Uri::sip(ip_domain!(192, 168, 1, 10, 5060))
+            .parameter(UriParam::Transport(Transport::Tcp))
+            .auth(uri_auth!("alice")),
+        invite.contact_header().unwrap().uri
+    );
+    assert_eq!(&sdp, invite.body());
+    let headers = invite.headers();
+    assert!(headers.iter().any(|h| h == &Header::MaxForwards(70)));
+    assert!(headers
+        .iter()
+        .any(|h| h == &Header::ContentType(ContentType::Sdp)));
+    assert!(headers
+        .iter()
+        .any(|h| h == &Header::ContentLength(sdp.len() as u32)));
+    assert!(headers
+        .iter()
+        .any(|h| h == &Header::Supported(vec!["timer".into()])));
+    assert!(headers.iter().any(|h| matches!(h, Header::Allow(_))));
+    assert!(headers.iter().any(|h| matches!(h, Header::UserAgent(_))));
+
+    // Every INVITE starts a new transaction and a new dialog.
+    let next = phone
+        .send_invite(vec![], Uri::sip(domain!("example.com")))
+        .unwrap();
+    assert_ne!(invite.via_header_branch(), next.via_header_branch());
+    assert_ne!(invite.from_header_tag(), next.from_header_tag());
+    assert_ne!(invite.call_id(), next.call_id());
+    // An offerless INVITE has no Content-Type.
+    assert!(!next
+        .headers()
+        .iter()
+        .any(|h| matches!(h, Header::ContentType(_))));
+}
+
+#[test]
+fn authenticate_invite() {
+    let mut phone = phone();
+    let invite = phone
+        .send_invite(
+            vec![],
+            Uri::sip(domain!("example.com")).auth(uri_auth!("bob")),
+        )
+        .unwrap();
+    let challenge = parse_message::<VerboseError<&[u8]>>(UNAUTHORIZED.as_bytes())
+        .unwrap()
+        .1;
+    assert!(phone.authenticate_invite(&invite, &challenge).is_err());
+
+    phone.registry_mut().username("alice");
+    phone.registry_mut().password("secret");
+    let retry = phone.authenticate_invite(&invite, &challenge).unwrap();
+    assert_eq!(Ok((2, Method::Invite)), retry.cseq());
+    assert_eq!(invite.call_id(), retry.call_id());
+    assert_ne!(invite.via_header_branch(), retry.via_header_branch());
+    assert!(retry
+        .headers()
+        .iter()
+        .any(|h| matches!(h, Header::Authorization(_))));
+}
+
+const INCOMING: &str = "INVITE sip:alice@192.168.1.10:5060 SIP/2.0\r\n\
+Via: SIP/2.0/UDP 192.168.1.20:5060;branch=z9hG4bK74bf9\r\n\
+To: <sip:alice@example.com>\r\n\
+From: <sip:bob@example.com>;tag=9fxced76sl\r\n\
+Call-ID: 3848276298220188511@example.com\r\n\
+CSeq: 1 INVITE\r\n\
+Contact: <sip:bob@192.168.1.20:5060>\r\n\
+Content-Length: 0\r\n\r\n";
+
+/// A request from the caller within the call started by `invite`.
+fn within(invite: &SipMessage, method: Method, cseq: u32, tag: &str) -> SipMessage {
+    let mut request = invite.clone();
+    if let SipMessage::Request { method: m, .. } = &mut request {
+        *m = method;
+    }
+    *request.cseq_mut().unwrap().0 = cseq;
+    *request.cseq_mut().unwrap().1 = method;
+    request.set_to_header_tag(tag.to_string());
+    request
+}
+
+#[test]
+fn receive_reinvite() {
+    let mut phone = phone();
+    let invite = parse_message::<VerboseError<&[u8]>>(INCOMING.as_bytes())
+        .unwrap()
+        .1;
+    let call_id = invite.call_id().unwrap().clone();
+    let ringing = phone.get_received_request(invite.clone()).unwrap();
+    assert_eq!(Ok(180), ringing.status_code());
+    let ok = phone
+        .get_accept_request(b"v=0\r\n".to_vec(), &call_id)
+        .unwrap();
+    let tag = ok.to_header_tag().unwrap().clone();
+    assert!(phone
+        .receive_ack(&within(&invite, Method::Ack, 1, &tag))
+        .unwrap());
+
+    // The re-INVITE is answered within the same dialog, without ringing.
+    let reinvite = within(&invite, Method::Invite, 2, &tag);
+    let trying = phone.get_received_request(reinvite).unwrap();
+    assert_eq!(Ok(100), trying.status_code());
+    let ok = phone
+        .get_accept_request(b"v=1\r\n".to_vec(), &call_id)
+        .unwrap();
+    assert_eq!(Ok(&tag), ok.to_header_tag());
+    assert_eq!(Ok((2, Method::Invite)), ok.cseq());
+    assert!(phone
+        .receive_ack(&within(&invite, Method::Ack, 2, &tag))
+        .unwrap());
+
+    // Out of order and unknown re-INVITEs get a final response.
+    let stale = within(&invite, Method::Invite, 1, &tag);
+    let response = phone.get_received_request(stale).unwrap();
+    assert_eq!(Ok(500), response.status_code());
+    let unknown = within(&invite, Method::Invite, 3, "a6c85cf");
+    let response = phone.get_received_request(unknown).unwrap();
+    assert_eq!(Ok(481), response.status_code());
+
+    // Rejecting a re-INVITE keeps the call.
+    let reinvite = within(&invite, Method::Invite, 3, &tag);
+    phone.get_received_request(reinvite).unwrap();
+    let rejected = phone.get_reject_request(&call_id, 488, None).unwrap();
+    assert_eq!(Ok(488), rejected.status_code());
+    let bye = phone.get_bye_request(&call_id).unwrap();
+    assert_eq!(Ok(&"9fxced76sl".to_string()), bye.to_header_tag());
+    assert_eq!(
+        Ok(200),
+        phone
+            .receive_bye(&within(&invite, Method::Bye, 4, &tag))
+            .unwrap()
+            .status_code()
+    );
+}
@@ -0,0 +1,5 @@
+#[cfg(feature = "tokio")]
+mod agent;
+mod registration;
+mod invite;
+mod outbound;
@@ -0,0 +1,180 @@
+use libsip::{headers::parse::parse_contact_header, *};
+
+use nom::error::VerboseError;
+
+use std::time::{Duration, Instant};
+
+const INSTANCE: &str = "urn:uuid:00000000-0000-1000-8000-AABBCCDDEEFF";
+
+fn manager() -> RegistrationManager {
+    let mut reg = RegistrationManager::new(
+        Uri::sip(domain!("example.com")).auth(uri_auth!("alice")),
+        Uri::sip(ip_domain!(192, 168, 1, 10, 5060)),
+    );
+    reg.username("alice");
+    reg.instance(INSTANCE);
+    reg.reg_id(1);
+    reg
+}
+
+fn response(headers: &str) -> SipMessage {
+    let msg = format!(
+        "SIP/2.0 200 OK\r\n\
+         Via: SIP/2.0/TCP 192.168.1.10:5060;branch=z9hG4bK776asdhds\r\n\
+         To: <sip:alice@example.com>;tag=1928301774\r\n\
+         From: <sip:alice@example.com>;tag=a73kszlfl\r\n\
+         Call-ID: a84b4c76e66710\r\n\
+         CSeq: 1 REGISTER\r\n\
+         {}Content-Length: 0\r\n\r\n",
+        headers
+    );
+    parse_message::<VerboseError<&[u8]>>(msg.as_bytes())
+        .unwrap()
+        .1
+}
+
+#[test]
+fn contact_parameters() {
+    let contact = ContactHeader::new(Uri::sip(domain!("example.com")))
+        .instance(INSTANCE)
+        .reg_id(2);
+    assert_eq!(Some(INSTANCE.to_string()), contact.sip_instance());
+    assert_eq!(Some(2), contact.registration_id());
+
+    let input = format!(
+        "Contact: <sip:alice@192.168.1.10:5060>;+sip.instance=\"<{}>\";reg-id=1;\
+         pub-gruu=\"sip:alice@example.com;gr={}\";temp-gruu=\"sip:tgruu.7hs==jd7vnzga5w7fajsc7-ajd6fabz0f8g5@example.com;gr\"\r\n",
+        INSTANCE, INSTANCE
+    );
+    let (_, header) = parse_contact_header::<VerboseError<&[u8]>>(input.as_bytes()).unwrap();
+    let contact = match header {
+        Header::Contact(contact) => contact,
+        _ => panic!("expected a Contact header"),
+    };
+    assert_eq!(Some(INSTANCE.to_string()), contact.sip_instance());
+    assert_eq!(Some(1), contact.registration_id());
+    assert_eq!(
+        Some(
+            Uri::sip(domain!("example.com"))
+                .auth(uri_auth!("alice"))
+                .parameter(UriParam::Gr(Some(INSTANCE.into())))
+        ),
+        contact.pub_gruu()
+    );
+    assert_eq!(
+        Some(&UriParam::Gr(None)),
+        contact.temp_gruu().unwrap().parameters.last()
+    );
+}
+
+#[test]
+fn register_with_outbound_and_gruu() {
+    let mut reg = manager();
+    let request = reg.get_request(&HeaderWriteConfig::default()).unwrap();
+    let contact = request.contact_header().unwrap();
+    assert_eq!(Some(INSTANCE.to_string()), contact.sip_instance());
+    assert_eq!(Some(1), contact.registration_id());
+    assert!(request
+        .headers()
+        .iter()
+        .any(|header| header == &Header::Supported(vec!["gruu".into(), "outbound".into()])));
+
+    let cfg = HeaderWriteConfig {
+        supported: Some(vec!["timer".into(), "gruu".into()]),
+        ..HeaderWriteConfig::default()
+    };
+    let supported: Vec<_> = reg
+        .get_request(&cfg)
+        .unwrap()
+        .headers()
+        .iter()
+        .filter(|header| matches!(header, Header::Supported(_)))
+        .cloned()
+        .collect();
+    assert_eq!(
+        supported,
+        vec![Header::Supported(vec![
+            "timer".into(),
+            "gruu".into(),
+            "outbound".into()
+        ])]
+    );
+
+    let ok = response(&format!(
+        "Require: outbound\r\n\
+         Flow-Timer: 30\r\n\
+         Contact: <sip:alice@192.168.1.20:5060>;expires=600\r\n\
+         Contact: <sip:alice@192.168.1.10:5060>;+sip.instance=\"<{}>\";reg-id=1;expires=300;\
+         pub-gruu=\"sip:alice@example.com;gr={}\"\r\n",
+        INSTANCE, INSTANCE
+    ));
+    assert_eq!(
+        RegistrationStatus::Registered(300),
+        reg.handle_response(&ok, Instant::now()).unwrap()
+    );
+    assert!(reg.is_outbound());
+    assert_eq!(Some(30), reg.flow_timer());
+    let gruu = Uri::sip(domain!("example.com"))
+        .auth(uri_auth!("alice"))
+        .parameter(UriParam::Gr(Some(INSTANCE.into())));
+    assert_eq!(Some(&gruu), reg.pub_gruu());
+    assert_eq!(gruu, reg.dialog_contact().uri);
+}
+
+#[test]
+fn outbound_without_gruu() {
+    let mut reg = manager();
+    reg.get_request(&HeaderWriteConfig::default()).unwrap();
+    let ok =
+        response("Require: outbound\r\nContact: <sip:alice@192.168.1.10:5060>;expires=300\r\n");
+    let now = Instant::now();
+    reg.handle_response(&ok, now).unwrap();
+    assert!(reg.is_outbound());
+    assert_eq!(None, reg.pub_gruu());
+    assert!(reg.dialog_contact().uri.parameters.contains(&UriParam::Ob));
+
+    assert!(!reg.needs_refresh(now));
+    reg.flow_failed(now);
+    assert!(reg.needs_refresh(now));
+}
+
+#[test]
+fn keep_alive() {
+    let now = Instant::now();
+    let mut keep_alive = KeepAlive::new(Some(30), now);
+    assert_eq!(None, keep_alive.poll(now + Duration::from_secs(23)));
+    assert_eq!(
+        Some(KeepAlive::PING),
+        keep_alive.poll(now + Duration::from_secs(30))
+    );
+    assert_eq!(None, keep_alive.poll(now + Duration::from_secs(31)));
+    assert!(!keep_alive.is_failed(now + Duration::from_secs(39)));
+
+    keep_alive.pong(now + Duration::from_secs(31));
+    assert!(!keep_alive.is_failed(now + Duration::from_secs(60)));
+    assert!(keep_alive.next_timeout() >= now + Duration::from_secs(55));
+
+    let sent = keep_alive.next_timeout();
+    assert_eq!(Some(KeepAlive::PING), keep_alive.poll(sent));
+    assert_eq!(sent + KeepAlive::PONG_TIMEOUT, keep_alive.next_timeout());
+    assert!(keep_alive.is_failed(sent + KeepAlive::PONG_TIMEOUT));
+}
+
+#[test]
+fn zero_flow_timer() {
+    let mut reg = manager();
+    reg.get_request(&HeaderWriteConfig::default()).unwrap();
+    let ok = response(&format!(
+        "Require: outbound\r\n\
+         Flow-Timer: 0\r\n\
+         Contact: <sip:alice@192.168.1.10:5060>;+sip.instance=\"<{}>\";reg-id=1;expires=300\r\n",
+        INSTANCE
+    ));
+    reg.handle_response(&ok, Instant::now()).unwrap();
+    assert_eq!(None, reg.flow_timer());
+
+    let now = Instant::now();
+    let keep_alive = KeepAlive::new(Some(0), now);
+    assert!(keep_alive.next_timeout() >= now + Duration::from_secs(95));
+    assert!(keep_alive.next_timeout() <= now + Duration::from_secs(120));
+}
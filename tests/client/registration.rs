@@ -0,0 +1,247 @@
+use libsip::*;
+
+use nom::error::VerboseError;
+
+use std::time::{Duration, Instant};
+
+fn manager() -> RegistrationManager {
+    let mut reg = RegistrationManager::new(
+        Uri::sip(domain!("example.com")).auth(uri_auth!("alice")),
+        Uri::sip(ip_domain!(192, 168, 1, 10, 5060)),
+    );
+    reg.username("alice");
+    reg.password("secret");
+    reg
+}
+
+fn response(code: u32, headers: &str) -> SipMessage {
+    let msg = format!(
+        "SIP/2.0 {} Reason\r\n\
+         Via: SIP/2.0/UDP 192.168.1.10:5060;branch=z9hG4bK776asdhds\r\n\
+         To: <sip:alice@example.com>;tag=1928301774\r\n\
+         From: <sip:alice@example.com>;tag=a73kszlfl\r\n\
+         Call-ID: a84b4c76e66710\r\n\
+         CSeq: 1 REGISTER\r\n\
+         {}Content-Length: 0\r\n\r\n",
+        code, headers
+    );
+    parse_message::<VerboseError<&[u8]>>(msg.as_bytes()).unwrap().1
+}
+
+fn expires(request: &SipMessage) -> u32 {
+    request.expires_header().unwrap()
+}
+
+fn contact(request: &SipMessage) -> ContactHeader {
+    match request.headers().contact() {
+        Some(Header::Contact(contact)) => contact,
+        _ => panic!("request has no Contact header"),
+    }
+}
+
+#[test]
+fn granted_interval() {
+    let mut reg = manager();
+    reg.set_expires(3600);
+    let cfg = HeaderWriteConfig::default();
+    assert_eq!(3600, expires(&reg.get_request(&cfg).unwrap()));
+
+    let now = Instant::now();
+    let ok = response(
+        200,
+        "Contact: <sip:alice@192.168.1.10:5060>;expires=1800\r\n\
+         Contact: <sip:alice@192.168.1.20:5060>;expires=600\r\n",
+    );
+    assert_eq!(
+        RegistrationStatus::Registered(1800),
+        reg.handle_response(&ok, now).unwrap()
+    );
+    assert_eq!(Some(1800), reg.granted_expires());
+    assert_eq!(2, reg.bindings().len());
+    assert!(reg.is_registered(now + Duration::from_secs(1799)));
+    assert!(!reg.is_registered(now + Duration::from_secs(1800)));
+
+    let refresh = reg.refresh_at().unwrap();
+    assert!(refresh >= now + Duration::from_secs(900));
+    assert!(refresh <= now + Duration::from_secs(1620));
+    assert!(!reg.needs_refresh(now + Duration::from_secs(899)));
+    assert!(reg.needs_refresh(now + Duration::from_secs(1620)));
+}
+
+#[test]
+fn expires_header_fallback() {
+    let mut reg = manager();
+    reg.get_request(&HeaderWriteConfig::default()).unwrap();
+    let ok = response(200, "Expires: 120\r\n");
+    assert_eq!(
+        RegistrationStatus::Registered(120),
+        reg.handle_response(&ok, Instant::now()).unwrap()
+    );
+}
+
+#[test]
+fn interval_too_brief() {
+    let mut reg = manager();
+    reg.set_expires(60);
+    let cfg = HeaderWriteConfig::default();
+    reg.get_request(&cfg).unwrap();
+
+    let brief = response(423, "Min-Expires: 300\r\n");
+    assert_eq!(
+        RegistrationStatus::IntervalTooBrief(300),
+        reg.handle_response(&brief, Instant::now()).unwrap()
+    );
+    assert_eq!(300, expires(&reg.get_request(&cfg).unwrap()));
+    assert!(reg
+        .handle_response(&response(423, ""), Instant::now())
+        .is_err());
+}
+
+#[test]
+fn challenged_and_failed() {
+    let mut reg = manager();
+    let cfg = HeaderWriteConfig::default();
+    reg.get_request(&cfg).unwrap();
+
+    let challenge = response(
+        401,
+        "WWW-Authenticate: Digest realm=\"example.com\", nonce=\"ea9c8e88df84f1cec4341ae6cbe5a359\"\r\n",
+    );
+    assert_eq!(
+        RegistrationStatus::Challenged,
+        reg.handle_response(&challenge, Instant::now()).unwrap()
+    );
+    let request = reg.get_request(&cfg).unwrap();
+    assert!(request
+        .headers()
+        .iter()
+        .any(|header| matches!(header, Header::Authorization(_))));
+
+    // A stale nonce is retried, a second challenge for the realm is not.
+    let stale = response(
+        401,
+        "WWW-Authenticate: Digest realm=\"example.com\", nonce=\"0b5c3e6d2a1f4e8c9d7b6a5f4e3d2c1b\", stale=true\r\n",
+    );
+    assert_eq!(
+        RegistrationStatus::Challenged,
+        reg.handle_response(&stale, Instant::now()).unwrap()
+    );
+    reg.get_request(&cfg).unwrap();
+    assert_eq!(
+        RegistrationStatus::Failed(401),
+        reg.handle_response(&challenge, Instant::now()).unwrap()
+    );
+
+    let mut anonymous = RegistrationManager::new(
+        Uri::sip(domain!("example.com")).auth(uri_auth!("alice")),
+        Uri::sip(ip_domain!(192, 168, 1, 10, 5060)),
+    );
+    anonymous.get_request(&cfg).unwrap();
+    assert_eq!(
+        RegistrationStatus::Failed(401),
+        anonymous.handle_response(&challenge, Instant::now()).unwrap()
+    );
+
+    assert_eq!(
+        RegistrationStatus::Failed(403),
+        reg.handle_response(&response(403, ""), Instant::now())
+            .unwrap()
+    );
+    assert!(reg
+        .handle_response(&response(100, ""), Instant::now())
+        .is_err());
+}
+
+#[test]
+fn unregister() {
+    let mut reg = manager();
+    let cfg = HeaderWriteConfig::default();
+    let now = Instant::now();
+    reg.get_request(&cfg).unwrap();
+    reg.handle_response(
+        &response(200, "Contact: <sip:alice@192.168.1.10:5060>;expires=60\r\n"),
+        now,
+    )
+    .unwrap();
+
+    let request = reg.get_unregister_request(&cfg, false).unwrap();
+    assert_eq!(0, expires(&request));
+    assert!(!contact(&request).is_wildcard());
+
+    let request = reg.get_unregister_request(&cfg, true).unwrap();
+    assert_eq!(0, expires(&request));
+    assert!(contact(&request).is_wildcard());
+    assert!(format!("{}", request).contains("\r\nContact: *\r\n"));
+
+    assert_eq!(
+        RegistrationStatus::Unregistered,
+        reg.handle_response(&response(200, ""), now).unwrap()
+    );
+    assert!(reg.bindings().is_empty());
+    assert!(!reg.is_registered(now));
+    assert_eq!(None, reg.refresh_at());
+
+    let request = reg.get_request(&cfg).unwrap();
+    assert!(!contact(&request).is_wildcard());
+}
+
+#[test]
+fn tel_uris_have_no_host() {
+    let cfg = HeaderWriteConfig::default();
+    let mut reg = RegistrationManager::new(
+        Uri::tel(TelUri::global("+15550100")),
+        Uri::sip(ip_domain!(192, 168, 1, 10, 5060)),
+    );
+    assert!(reg.get_request(&cfg).is_err());
+
+    let mut reg = RegistrationManager::new(
+        Uri::sip(domain!("example.com")).auth(uri_auth!("alice")),
+        Uri::tel(TelUri::global("+15550100")),
+    );
+    assert!(reg.get_request(&cfg).is_err());
+}
+
+#[test]
+fn failed_refresh_backs_off() {
+    let mut reg = manager();
+    let cfg = HeaderWriteConfig::default();
+    let now = Instant::now();
+
+    // A registration that was never accepted is not retried.
+    reg.get_request(&cfg).unwrap();
+    assert_eq!(
+        RegistrationStatus::Failed(403),
+        reg.handle_response(&response(403, ""), now).unwrap()
+    );
+    assert_eq!(None, reg.refresh_at());
+
+    reg.get_request(&cfg).unwrap();
+    reg.handle_response(&response(200, "Expires: 60\r\n"), now)
+        .unwrap();
+    let later = now + Duration::from_secs(60);
+    reg.get_request(&cfg).unwrap();
+    assert_eq!(
+        RegistrationStatus::Failed(503),
+        reg.handle_response(&response(503, "Retry-After: 120 (overloaded)\r\n"), later)
+            .unwrap()
+    );
+    assert_eq!(Some(later + Duration::from_secs(120)), reg.refresh_at());
+
+    // Without Retry-After the delay doubles, starting at 30 to 60 seconds.
+    reg.get_request(&cfg).unwrap();
+    reg.handle_response(&response(500, ""), later).unwrap();
+    assert!(!reg.needs_refresh(later + Duration::from_secs(59)));
+    assert!(reg.needs_refresh(later + Duration::from_secs(120)));
+
+    reg.get_request(&cfg).unwrap();
+    reg.timed_out(later);
+    assert!(!reg.needs_refresh(later + Duration::from_secs(119)));
+    assert!(reg.needs_refresh(later + Duration::from_secs(240)));
+
+    reg.get_request(&cfg).unwrap();
+    reg.handle_response(&response(200, "Expires: 60\r\n"), later)
+        .unwrap();
+    reg.get_request(&cfg).unwrap();
+    reg.timed_out(later);
+    assert!(reg.needs_refresh(later + Duration::from_secs(60)));
+}
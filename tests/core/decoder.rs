@@ -0,0 +1,83 @@
+use libsip::*;
+
+const REGISTER: &[u8] = b"REGISTER sip:example.com SIP/2.0\r\nContent-Length: 5\r\n\r\nhello";
+const OK: &[u8] = b"SIP/2.0 200 OK\r\nContent-Length: 0\r\n\r\n";
+
+fn register() -> SipMessage {
+    RequestGenerator::new()
+        .method(Method::Register)
+        .uri(Uri::sip(domain!("example.com")))
+        .header(Header::ContentLength(5))
+        .body(b"hello".to_vec())
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn decode_partial() {
+    let mut decoder = MessageDecoder::new();
+    for chunk in REGISTER[..REGISTER.len() - 1].chunks(7) {
+        decoder.extend(chunk);
+        assert_eq!(decoder.decode().unwrap(), Decoded::NeedMore);
+    }
+    decoder.extend(&REGISTER[REGISTER.len() - 1..]);
+    assert_eq!(decoder.decode().unwrap(), Decoded::Message(register()));
+    assert!(decoder.buffered().is_empty());
+}
+
+#[test]
+fn decode_multiple() {
+    let mut data = REGISTER.to_vec();
+    data.extend_from_slice(OK);
+    data.extend_from_slice(&REGISTER[..10]);
+    let mut decoder = MessageDecoder::new();
+    let items = decoder.decode_all(&data).unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0], Decoded::Message(register()));
+    assert!(matches!(&items[1], Decoded::Message(msg) if msg.status_code() == Ok(200)));
+    assert_eq!(decoder.buffered(), &REGISTER[..10]);
+}
+
+#[test]
+fn decode_keep_alive() {
+    let mut decoder = MessageDecoder::new();
+    let mut data = b"\r\n\r\n".to_vec();
+    data.extend_from_slice(OK);
+    data.extend_from_slice(b"\r\n");
+    let items = decoder.decode_all(&data).unwrap();
+    assert_eq!(items[0], Decoded::Ping);
+    assert!(matches!(items[1], Decoded::Message(_)));
+    assert_eq!(items.len(), 2);
+    // The trailing CRLF might be the first half of a ping.
+    assert_eq!(decoder.buffered(), b"\r\n");
+    let items = decoder.decode_all(OK).unwrap();
+    assert_eq!(items[0], Decoded::Pong);
+    assert!(matches!(items[1], Decoded::Message(_)));
+
+    // A connection we opened only receives pongs.
+    let mut decoder = MessageDecoder::new().pings(false);
+    assert_eq!(
+        decoder.decode_all(b"\r\n\r\n").unwrap(),
+        vec![Decoded::Pong, Decoded::Pong]
+    );
+}
+
+#[test]
+fn decode_split_ping() {
+    let mut decoder = MessageDecoder::new();
+    assert!(decoder.decode_all(b"\r\n").unwrap().is_empty());
+    assert_eq!(decoder.decode_all(b"\r\n").unwrap(), vec![Decoded::Ping]);
+
+    assert!(decoder.decode_all(b"\r\n\r").unwrap().is_empty());
+    assert_eq!(decoder.decode_all(b"\n").unwrap(), vec![Decoded::Ping]);
+    assert!(decoder.buffered().is_empty());
+}
+
+#[test]
+fn decode_errors() {
+    let mut decoder = MessageDecoder::new();
+    assert!(decoder.decode_all(b"garbage\r\n\r\n").is_err());
+
+    let mut decoder = MessageDecoder::new().max_size(16);
+    assert!(decoder.decode_all(REGISTER).is_err());
+}
@@ -38,3 +38,77 @@ fn read_complex() {
         parse_message::<VerboseError<&[u8]>>(b"REGISTER sip:user@example.com;rport;new;Some=Param;Other SIP/2.0\r\nExpires: 10\r\nContent-Length: 5\r\n\r\n66666")
     );
 }
+
+#[test]
+fn read_compact_headers() {
+    let msg = b"INVITE sip:bob@example.com SIP/2.0\r\nv: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bKa\r\nf: <sip:alice@example.com>;tag=1\r\nt: <sip:bob@example.com>\r\ni: abcd\r\nm: <sip:alice@10.0.0.1>\r\nk: path, gruu\r\nl: 0\r\n\r\n";
+    let (_, msg) = parse_message::<VerboseError<&[u8]>>(msg).unwrap();
+    assert_eq!(msg.via_header_branch(), Ok(&"z9hG4bKa".to_string()));
+    assert_eq!(msg.from_header_tag(), Ok(&"1".to_string()));
+    assert_eq!(msg.to_header_username(), Ok(&"bob".to_string()));
+    assert_eq!(msg.call_id(), Ok(&"abcd".to_string()));
+    assert_eq!(msg.contact_header_username(), Ok(&"alice".to_string()));
+    assert!(msg
+        .headers()
+        .iter()
+        .any(|h| h == &Header::Supported(vec!["path".into(), "gruu".into()])));
+    assert!(msg.headers().iter().any(|h| h == &Header::ContentLength(0)));
+}
+
+#[test]
+fn read_folded_headers() {
+    let msg = b"SIP/2.0 200 OK\r\nSubject: I know you're there,\r\n     pick up the phone\r\n\tand talk to me!\r\nCall-ID: abcd\r\n\r\n";
+    let (_, msg) = parse_message::<VerboseError<&[u8]>>(msg).unwrap();
+    assert_eq!(
+        msg.headers().0,
+        vec![
+            Header::Subject("I know you're there, pick up the phone and talk to me!".into()),
+            Header::CallId("abcd".into()),
+        ]
+    );
+}
+
+#[test]
+fn read_multi_value_headers() {
+    let msg = b"SIP/2.0 200 OK\r\n\
+Via: SIP/2.0/UDP 10.0.0.2:5060;branch=z9hG4bKp1, SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bKa\r\n\
+Record-Route: <sip:p2.example.com;lr>,\r\n <sip:p1.example.com;lr>\r\n\
+Contact: \"Bob, Jr.\" <sip:bob@10.0.0.3>, <sip:bob@10.0.0.4>;expires=60\r\n\
+Content-Length: 0\r\n\r\n";
+    let (_, msg) = parse_message::<VerboseError<&[u8]>>(msg).unwrap();
+    let headers = &msg.headers().0;
+    assert_eq!(headers.len(), 7);
+    assert_eq!(msg.via_header_branch(), Ok(&"z9hG4bKp1".to_string()));
+    if let Header::Via(via) = &headers[1] {
+        assert_eq!(via.branch(), Some(&"z9hG4bKa".to_string()));
+    } else {
+        panic!("expected a second Via header");
+    }
+    assert_eq!(headers[2], Header::RecordRoute(crate::dialog::route("p2.example.com")));
+    assert_eq!(headers[3], Header::RecordRoute(crate::dialog::route("p1.example.com")));
+    if let Header::Contact(contact) = &headers[4] {
+        assert_eq!(contact.display_name, Some("Bob, Jr.".into()));
+    } else {
+        panic!("expected a Contact header");
+    }
+    assert!(
+        matches!(&headers[5], Header::Contact(contact) if contact.parameters.get("expires") == Some(&Some("60".into())))
+    );
+}
+
+#[test]
+fn read_invalid_header_values() {
+    let msg = b"SIP/2.0 200 OK\r\n\
+X.Custom_Header: some value\r\n\
+Expires: 60 seconds\r\n\
+Content-Length: 0\r\n\r\n";
+    let (_, msg) = parse_message::<VerboseError<&[u8]>>(msg).unwrap();
+    assert_eq!(
+        msg.headers().0,
+        vec![
+            Header::Other("X.Custom_Header".into(), "some value".into()),
+            Header::Other("Expires".into(), " 60 seconds".into()),
+            Header::ContentLength(0),
+        ]
+    );
+}
@@ -3,3 +3,4 @@ mod request;
 mod response;
 mod version;
 mod invalid;
+mod decoder;
@@ -0,0 +1,93 @@
+use crate::transaction::response;
+
+use libsip::*;
+
+fn invite() -> SipMessage {
+    let mut writer = InviteWriter::new(Uri::sip(domain!("example.com")).auth(uri_auth!("alice")));
+    writer
+        .generate_invite(
+            Uri::sip(domain!("example.com")).auth(uri_auth!("bob")),
+            vec![],
+            Header::Via(ViaHeader::new(
+                ip_domain!(192, 168, 1, 2, 5060),
+                Transport::Udp,
+            )),
+            ContactHeader::new(Uri::sip(ip_domain!(192, 168, 1, 2, 5060)).auth(uri_auth!("alice"))),
+            &Default::default(),
+        )
+        .unwrap()
+}
+
+fn answer(invite: &SipMessage, code: u32) -> SipMessage {
+    let mut res = response(invite, code);
+    res.headers_mut().push(Header::Contact(ContactHeader::new(
+        Uri::sip(ip_domain!(10, 0, 0, 9, 5070)).auth(uri_auth!("bob")),
+    )));
+    res.headers_mut()
+        .push(Header::RecordRoute(crate::dialog::route("p1.example.com")));
+    res
+}
+
+fn request_uri(msg: &SipMessage) -> &Uri {
+    match msg {
+        SipMessage::Request { uri, .. } => uri,
+        SipMessage::Response { .. } => panic!("expected a request"),
+    }
+}
+
+#[test]
+fn ack_2xx() {
+    let invite = invite();
+    let writer = InviteWriter::new(Uri::sip(domain!("example.com")));
+    let ok = answer(&invite, 200);
+    let (ack, dialog) = writer.ack(&invite, &ok, &Default::default()).unwrap();
+    let dialog = dialog.unwrap();
+
+    assert_eq!(ack.method(), Ok(Method::Ack));
+    assert_eq!(ack.cseq(), Ok((1, Method::Ack)));
+    assert_eq!(dialog.local_seq(), 1);
+    assert_ne!(ack.via_header_branch(), invite.via_header_branch());
+    assert!(ack.via_header_branch().unwrap().starts_with("z9hG4bK"));
+    assert_eq!(request_uri(&ack), dialog.remote_target());
+    assert_eq!(ack.to_header(), ok.to_header());
+    assert!(ack
+        .headers()
+        .iter()
+        .any(|h| h == &Header::Route(crate::dialog::route("p1.example.com"))));
+}
+
+#[test]
+fn ack_2xx_from_dialog() {
+    let invite = invite();
+    let mut dialog = Dialog::from_uac(&invite, &answer(&invite, 180)).unwrap();
+    let reinvite = dialog.reinvite(vec![], &Default::default()).unwrap();
+    let ok = answer(&reinvite, 200);
+    let ack = dialog.ack(&ok, &Default::default()).unwrap();
+    assert_eq!(dialog.state(), DialogState::Confirmed);
+    assert_eq!(ack.cseq(), Ok((2, Method::Ack)));
+    assert_ne!(ack.via_header_branch(), reinvite.via_header_branch());
+
+    assert!(dialog
+        .ack(&answer(&reinvite, 180), &Default::default())
+        .is_err());
+    let bye = dialog.bye(&Default::default()).unwrap();
+    assert!(dialog.ack(&answer(&bye, 200), &Default::default()).is_err());
+}
+
+#[test]
+fn ack_non_2xx() {
+    let invite = invite();
+    let writer = InviteWriter::new(Uri::sip(domain!("example.com")));
+    let busy = answer(&invite, 486);
+    let (ack, dialog) = writer.ack(&invite, &busy, &Default::default()).unwrap();
+    assert!(dialog.is_none());
+    assert_eq!(ack.cseq(), Ok((1, Method::Ack)));
+    assert_eq!(ack.via_header_branch(), invite.via_header_branch());
+    assert_eq!(request_uri(&ack), request_uri(&invite));
+    assert_eq!(ack.to_header(), busy.to_header());
+    assert_eq!(ack.from_header(), invite.from_header());
+
+    assert!(writer
+        .ack(&invite, &answer(&invite, 180), &Default::default())
+        .is_err());
+}
@@ -0,0 +1,10 @@
+mod ack;
+mod uac;
+mod uas;
+
+use libsip::*;
+
+/// A loose routing Route / Record-Route value for `host`.
+pub fn route(host: &str) -> RouteHeader {
+    RouteHeader::new(Uri::sip(domain!(host)).parameter(UriParam::Lr))
+}
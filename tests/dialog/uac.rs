@@ -0,0 +1,149 @@
+use crate::transaction::{request, response};
+
+use libsip::*;
+
+fn invite() -> SipMessage {
+    let mut invite = request(Method::Invite, Transport::Udp);
+    let contact = Uri::sip(ip_domain!(192, 168, 1, 2, 5060)).auth(uri_auth!("alice"));
+    invite
+        .headers_mut()
+        .push(Header::Contact(ContactHeader::new(contact)));
+    invite
+}
+
+fn answer(invite: &SipMessage, code: u32) -> SipMessage {
+    let mut res = response(invite, code);
+    let contact = Uri::sip(ip_domain!(10, 0, 0, 9, 5070)).auth(uri_auth!("bob"));
+    res.headers_mut()
+        .push(Header::Contact(ContactHeader::new(contact)));
+    res.headers_mut()
+        .push(Header::RecordRoute(crate::dialog::route("p1.example.com")));
+    res.headers_mut()
+        .push(Header::RecordRoute(crate::dialog::route("p2.example.com")));
+    res
+}
+
+#[test]
+fn create_from_2xx() {
+    let invite = invite();
+    let dialog = Dialog::from_uac(&invite, &answer(&invite, 200)).unwrap();
+    assert_eq!(dialog.state(), DialogState::Confirmed);
+    assert_eq!(
+        dialog.id(),
+        &DialogId {
+            call_id: "a84b4c76e66710".into(),
+            local_tag: "1928301774".into(),
+            remote_tag: "a6c85cf".into(),
+        }
+    );
+    assert_eq!(dialog.local_seq(), 314159);
+    assert_eq!(
+        dialog.remote_target(),
+        &Uri::sip(ip_domain!(10, 0, 0, 9, 5070)).auth(uri_auth!("bob"))
+    );
+    // The UAC uses the Record-Route headers in reverse order.
+    assert_eq!(
+        dialog.route_set(),
+        &[
+            crate::dialog::route("p2.example.com"),
+            crate::dialog::route("p1.example.com")
+        ]
+    );
+}
+
+#[test]
+fn early_dialog() {
+    let invite = invite();
+    let mut dialog = Dialog::from_uac(&invite, &answer(&invite, 180)).unwrap();
+    assert_eq!(dialog.state(), DialogState::Early);
+    dialog.on_response(&answer(&invite, 200)).unwrap();
+    assert_eq!(dialog.state(), DialogState::Confirmed);
+
+    // A 2xx from another fork creates a separate dialog.
+    let mut early = Dialog::from_uac(&invite, &answer(&invite, 180)).unwrap();
+    let mut forked = answer(&invite, 200);
+    forked.set_to_header_tag("b7d96dg".into());
+    assert!(early.on_response(&forked).is_err());
+    assert_eq!(early.state(), DialogState::Early);
+    assert_eq!(early.id().remote_tag, "a6c85cf");
+    let fork = Dialog::from_uac(&invite, &forked).unwrap();
+    assert_eq!(fork.id().remote_tag, "b7d96dg");
+    assert_eq!(fork.state(), DialogState::Confirmed);
+
+    let mut dialog = Dialog::from_uac(&invite, &answer(&invite, 183)).unwrap();
+    dialog.on_response(&answer(&invite, 486)).unwrap();
+    assert_eq!(dialog.state(), DialogState::Terminated);
+    assert!(dialog.bye(&Default::default()).is_err());
+
+    assert!(Dialog::from_uac(&invite, &answer(&invite, 100)).is_err());
+}
+
+#[test]
+fn in_dialog_requests() {
+    let invite = invite();
+    let mut dialog = Dialog::from_uac(&invite, &answer(&invite, 200)).unwrap();
+    let cfg = HeaderWriteConfig::default();
+
+    let info = dialog
+        .info(ContentType::PlainText, b"hello".to_vec(), &cfg)
+        .unwrap();
+    let bye = dialog.bye(&cfg).unwrap();
+
+    assert_eq!(info.cseq(), Ok((314160, Method::Info)));
+    assert_eq!(bye.cseq(), Ok((314161, Method::Bye)));
+    assert_eq!(bye.from_header_tag(), Ok(&"1928301774".to_string()));
+    assert_eq!(bye.to_header_tag(), Ok(&"a6c85cf".to_string()));
+    assert_ne!(bye.via_header_branch(), invite.via_header_branch());
+    assert_ne!(bye.via_header_branch(), info.via_header_branch());
+    assert!(bye.via_header_branch().unwrap().starts_with("z9hG4bK"));
+    if let SipMessage::Request { uri, .. } = &bye {
+        assert_eq!(uri, dialog.remote_target());
+    }
+    let routes: Vec<&Header> = bye
+        .headers()
+        .iter()
+        .filter(|h| matches!(h, Header::Route(_)))
+        .collect();
+    assert_eq!(
+        routes,
+        vec![
+            &Header::Route(crate::dialog::route("p2.example.com")),
+            &Header::Route(crate::dialog::route("p1.example.com"))
+        ]
+    );
+    assert!(bye.contact_header().is_err());
+
+    let reinvite = dialog.reinvite(b"v=0".to_vec(), &cfg).unwrap();
+    assert_eq!(reinvite.cseq(), Ok((314162, Method::Invite)));
+    assert!(reinvite.contact_header().is_ok());
+    assert!(reinvite
+        .headers()
+        .iter()
+        .any(|h| h == &Header::ContentType(ContentType::Sdp)));
+
+    let update = dialog.update(vec![], &cfg).unwrap();
+    assert_eq!(update.cseq(), Ok((314163, Method::Update)));
+    let refer = dialog
+        .refer(
+            Uri::sip(domain!("example.com")).auth(uri_auth!("carol")),
+            &cfg,
+        )
+        .unwrap();
+    assert!(refer
+        .headers()
+        .iter()
+        .any(|h| h == &Header::Other("Refer-To".into(), "<sip:carol@example.com>".into())));
+}
+
+#[test]
+fn target_refresh() {
+    let invite = invite();
+    let mut dialog = Dialog::from_uac(&invite, &answer(&invite, 200)).unwrap();
+    let reinvite = dialog.reinvite(vec![], &Default::default()).unwrap();
+    let mut ok = response(&reinvite, 200);
+    let contact = Uri::sip(ip_domain!(10, 0, 0, 10)).auth(uri_auth!("bob"));
+    ok.headers_mut()
+        .push(Header::Contact(ContactHeader::new(contact.clone())));
+    dialog.on_response(&ok).unwrap();
+    assert_eq!(dialog.remote_target(), &contact);
+}
@@ -0,0 +1,283 @@
+use crate::transaction::{request, response};
+
+use libsip::*;
+
+use std::time::{Duration, Instant};
+
+fn invite() -> SipMessage {
+    let mut invite = request(Method::Invite, Transport::Udp);
+    let contact = Uri::sip(ip_domain!(192, 168, 1, 2, 5060)).auth(uri_auth!("alice"));
+    invite
+        .headers_mut()
+        .push(Header::Contact(ContactHeader::new(contact)));
+    invite
+        .headers_mut()
+        .push(Header::RecordRoute(crate::dialog::route("p1.example.com")));
+    invite
+}
+
+#[test]
+fn invite_helper_bye() {
+    let cfg = HeaderWriteConfig::default();
+    let mut helper = InviteHelper::new(invite()).unwrap();
+    assert!(helper.bye(&cfg).is_err());
+
+    let ok = helper.accept(vec![], &cfg).unwrap();
+    let local_tag = ok.to_header_tag().unwrap().clone();
+    assert_eq!(helper.dialog().unwrap().state(), DialogState::Confirmed);
+    assert_eq!(helper.dialog().unwrap().remote_seq(), Some(314159));
+
+    let bye = helper.bye(&cfg).unwrap();
+    // From and To are swapped for requests sent by the callee.
+    assert_eq!(bye.from_header_tag(), Ok(&local_tag));
+    assert_eq!(bye.to_header_tag(), Ok(&"1928301774".to_string()));
+    assert_eq!(bye.cseq(), Ok((1, Method::Bye)));
+    assert_ne!(bye.via_header_branch(), invite().via_header_branch());
+    assert_eq!(
+        bye.headers().iter().find(|h| matches!(h, Header::Route(_))),
+        Some(&Header::Route(crate::dialog::route("p1.example.com")))
+    );
+    if let SipMessage::Request { uri, .. } = bye {
+        assert_eq!(
+            uri,
+            Uri::sip(ip_domain!(192, 168, 1, 2, 5060)).auth(uri_auth!("alice"))
+        );
+    }
+}
+
+#[test]
+fn remote_cseq_ordering() {
+    let cfg = HeaderWriteConfig::default();
+    let mut helper = InviteHelper::new(invite()).unwrap();
+    let ok = helper.accept(vec![], &cfg).unwrap();
+    let mut dialog = Dialog::from_uas(&helper.request(), &ok).unwrap();
+
+    let mut info = invite();
+    *info.cseq_mut().unwrap().0 = 314160;
+    *info.cseq_mut().unwrap().1 = Method::Info;
+    info.set_to_header_tag(ok.to_header_tag().unwrap().clone());
+    assert_eq!(
+        DialogId::from_incoming_request(&info).as_ref(),
+        Ok(dialog.id())
+    );
+    dialog.on_request(&info).unwrap();
+    assert_eq!(dialog.remote_seq(), Some(314160));
+    // Only a lower CSeq is out of order.
+    dialog.on_request(&info).unwrap();
+    let mut stale = info.clone();
+    *stale.cseq_mut().unwrap().0 = 314159;
+    assert!(dialog.on_request(&stale).is_err());
+    assert_eq!(dialog.remote_seq(), Some(314160));
+
+    let mut bye = info.clone();
+    *bye.cseq_mut().unwrap().0 = 314161;
+    *bye.cseq_mut().unwrap().1 = Method::Bye;
+    dialog.on_request(&bye).unwrap();
+    assert_eq!(dialog.state(), DialogState::Terminated);
+}
+
+fn helper() -> InviteHelper {
+    let contact = ContactHeader::new(Uri::sip(ip_domain!(192, 168, 1, 3, 5060)).auth(uri_auth!("bob")));
+    InviteHelper::new(invite()).unwrap().contact(contact)
+}
+
+fn header<'a>(msg: &'a SipMessage, name: &str) -> Option<&'a Header> {
+    msg.headers().iter().find(|h| match h {
+        Header::Contact(_) => name == "Contact",
+        Header::RecordRoute(_) => name == "Record-Route",
+        Header::ContentType(_) => name == "Content-Type",
+        Header::Other(key, _) => key == name,
+        _ => false,
+    })
+}
+
+#[test]
+fn uas_responses_share_to_tag() {
+    let cfg = HeaderWriteConfig::default();
+    let mut helper = helper();
+
+    let trying = helper.trying(&cfg).unwrap();
+    assert_eq!(Ok(100), trying.status_code());
+    assert!(trying.to_header_tag().is_err());
+    assert!(header(&trying, "Contact").is_none());
+
+    let ringing = helper.ringing(&cfg).unwrap();
+    assert_eq!(Ok(180), ringing.status_code());
+    assert_eq!(Ok(&helper.tag().to_string()), ringing.to_header_tag());
+    assert!(header(&ringing, "Contact").is_some());
+    assert!(header(&ringing, "Content-Type").is_none());
+    assert_eq!(
+        Some(&Header::RecordRoute(crate::dialog::route("p1.example.com"))),
+        header(&ringing, "Record-Route")
+    );
+
+    let progress = helper
+        .session_progress(Some(b"v=0\r\n".to_vec()), &cfg)
+        .unwrap();
+    assert_eq!(Ok(183), progress.status_code());
+    assert_eq!(ringing.to_header_tag(), progress.to_header_tag());
+    assert_eq!(
+        Some(&Header::ContentType(ContentType::Sdp)),
+        header(&progress, "Content-Type")
+    );
+    assert_eq!(&b"v=0\r\n".to_vec(), progress.body());
+    assert!(helper.provisional(200, None, &cfg).is_err());
+
+    let ok = helper.accept(b"v=0\r\n".to_vec(), &cfg).unwrap();
+    assert_eq!(ringing.to_header_tag(), ok.to_header_tag());
+    assert_eq!(
+        Some(&Header::Contact(ContactHeader::new(
+            Uri::sip(ip_domain!(192, 168, 1, 3, 5060)).auth(uri_auth!("bob"))
+        ))),
+        header(&ok, "Contact")
+    );
+    assert!(header(&ok, "Record-Route").is_some());
+    assert_eq!(
+        helper.dialog().unwrap().id().local_tag,
+        helper.tag().to_string()
+    );
+    assert!(helper.reject(486, None, &cfg).is_err());
+
+    // A re-INVITE carries the tag of the existing dialog.
+    let mut reinvite = invite();
+    reinvite.set_to_header_tag("a6c85cf".into());
+    let helper = InviteHelper::new(reinvite).unwrap();
+    assert_eq!("a6c85cf", helper.tag());
+    assert_eq!(
+        Ok(&"a6c85cf".to_string()),
+        helper.ringing(&cfg).unwrap().to_header_tag()
+    );
+}
+
+#[test]
+fn uas_reject_with_reason() {
+    let cfg = HeaderWriteConfig::default();
+    let mut helper = helper();
+    assert!(helper.reject(200, None, &cfg).is_err());
+
+    let busy = helper
+        .reject(486, Some("Busy \"Here\""), &cfg)
+        .unwrap();
+    assert_eq!(Ok(486), busy.status_code());
+    assert_eq!(Ok(&helper.tag().to_string()), busy.to_header_tag());
+    assert_eq!(
+        Some(&Header::Other(
+            "Reason".into(),
+            "SIP;cause=486;text=\"Busy \\\"Here\\\"\"".into()
+        )),
+        header(&busy, "Reason")
+    );
+    assert!(header(&busy, "Contact").is_none());
+    assert!(helper.accept(vec![], &cfg).is_err());
+    assert!(helper.reject(603, None, &cfg).is_err());
+
+    let declined = self::helper().reject(603, None, &cfg).unwrap();
+    assert_eq!(Ok(603), declined.status_code());
+    assert!(header(&declined, "Reason").is_none());
+}
+
+#[test]
+fn uas_2xx_retransmission() {
+    let cfg = HeaderWriteConfig::default();
+    let now = Instant::now();
+    let mut helper = helper();
+    let ok = helper.accept_at(vec![], &cfg, now).unwrap();
+    assert!(helper.awaiting_ack());
+
+    // Timer G starts at T1 and doubles up to T2.
+    let mut at = now;
+    for interval in &[500, 1000, 2000, 4000, 4000] {
+        let due = at + Duration::from_millis(*interval);
+        assert_eq!(Some(due), helper.next_timeout());
+        assert_eq!(None, helper.on_timer(due - Duration::from_millis(1), &cfg).unwrap());
+        assert_eq!(Some(ok.clone()), helper.on_timer(due, &cfg).unwrap());
+        at = due;
+    }
+
+    // An ACK for another call or transaction is ignored.
+    let mut ack = request(Method::Ack, Transport::Udp);
+    assert!(!helper.receive_ack(&ack).unwrap());
+    ack.set_to_header_tag(helper.tag().into());
+    *ack.cseq_mut().unwrap().0 = 314160;
+    assert!(!helper.receive_ack(&ack).unwrap());
+    *ack.cseq_mut().unwrap().0 = 314159;
+    assert!(helper.receive_ack(&ack).unwrap());
+    assert!(!helper.awaiting_ack());
+    assert_eq!(None, helper.next_timeout());
+    assert_eq!(None, helper.on_timer(at + Duration::from_secs(60), &cfg).unwrap());
+}
+
+#[test]
+fn uas_2xx_late_timer() {
+    let cfg = HeaderWriteConfig::default();
+    let now = Instant::now();
+    let mut helper = helper();
+    helper.accept_at(vec![], &cfg, now).unwrap();
+
+    // A late timer doesn't delay the following retransmissions.
+    let late = now + Duration::from_millis(800);
+    assert!(helper.on_timer(late, &cfg).unwrap().is_some());
+    assert_eq!(
+        Some(now + Duration::from_millis(1500)),
+        helper.next_timeout()
+    );
+}
+
+#[test]
+fn uas_2xx_timer_h() {
+    let cfg = HeaderWriteConfig::default();
+    let now = Instant::now();
+    let mut helper = helper();
+    helper.accept_at(vec![], &cfg, now).unwrap();
+
+    // Without an ACK the dialog is ended after 64*T1.
+    let bye = helper
+        .on_timer(now + Duration::from_secs(32), &cfg)
+        .unwrap()
+        .unwrap();
+    assert_eq!(Ok(Method::Bye), bye.method());
+    assert_eq!(bye.from_header_tag(), Ok(&helper.tag().to_string()));
+    assert!(!helper.awaiting_ack());
+    assert_eq!(None, helper.next_timeout());
+    assert_eq!(None, helper.on_timer(now + Duration::from_secs(33), &cfg).unwrap());
+}
+
+#[test]
+fn uas_cancel() {
+    let cfg = HeaderWriteConfig::default();
+    let mut helper = helper();
+    let ringing = helper.ringing(&cfg).unwrap();
+    let (ok, terminated) = helper.cancel(&cfg).unwrap();
+    assert_eq!(Ok(200), ok.status_code());
+    assert_eq!(Ok((314159, Method::Cancel)), ok.cseq());
+    assert_eq!(Ok(487), terminated.status_code());
+    assert_eq!(Ok((314159, Method::Invite)), terminated.cseq());
+    assert_eq!(ringing.to_header_tag(), ok.to_header_tag());
+    assert_eq!(ringing.to_header_tag(), terminated.to_header_tag());
+    assert!(helper.accept(vec![], &cfg).is_err());
+
+    // Once answered a CANCEL has no effect.
+    let mut helper = self::helper();
+    helper.accept(vec![], &cfg).unwrap();
+    assert!(helper.cancel(&cfg).is_err());
+}
+
+#[test]
+fn uas_tel_request_uri() {
+    let mut invite = invite();
+    if let SipMessage::Request { uri, .. } = &mut invite {
+        *uri = Uri::tel(TelUri::global("+15550100"));
+    }
+    let mut ok = response(&invite, 200);
+    assert!(Dialog::from_uas(&invite, &ok).is_err());
+
+    let tel = ContactHeader::new(Uri::tel(TelUri::global("+15550100")));
+    ok.headers_mut().push(Header::Contact(tel));
+    assert!(Dialog::from_uas(&invite, &ok).is_err());
+
+    let contact = ContactHeader::new(Uri::sip(ip_domain!(192, 168, 1, 3, 5060)));
+    ok.headers_mut().0.retain(|h| !matches!(h, Header::Contact(_)));
+    ok.headers_mut().push(Header::Contact(contact));
+    let dialog = Dialog::from_uas(&invite, &ok).unwrap();
+    assert_eq!(dialog.state(), DialogState::Confirmed);
+}
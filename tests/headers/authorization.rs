@@ -2,11 +2,9 @@ use libsip::{headers::parse::parse_authorization_header, *};
 
 use nom::error::VerboseError;
 
-use std::collections::HashMap;
-
 #[test]
 fn write() {
-    let mut map = HashMap::new();
+    let mut map = AuthParams::new();
     map.insert("key".into(), "value".into());
     let header = Header::Authorization(AuthHeader(AuthSchema::Digest, map));
     assert_eq!(
@@ -18,7 +16,7 @@ fn write() {
 #[test]
 fn read() {
     let remains = vec![];
-    let mut map = HashMap::new();
+    let mut map = AuthParams::new();
     map.insert("key".into(), "value".into());
     let header = Header::Authorization(AuthHeader(AuthSchema::Digest, map));
     assert_eq!(
@@ -29,7 +27,7 @@ fn read() {
 
 #[test]
 fn qop_write() {
-    let mut map = HashMap::new();
+    let mut map = AuthParams::new();
     map.insert("qop".into(), "auth".into());
     let header = AuthHeader(AuthSchema::Digest, map);
     assert_eq!(
@@ -40,7 +38,7 @@ fn qop_write() {
 
 #[test]
 fn auth_write() {
-    let mut map = HashMap::new();
+    let mut map = AuthParams::new();
     map.insert("nc".into(), "1".into());
     let header = AuthHeader(AuthSchema::Digest, map);
     assert_eq!(
@@ -48,3 +46,15 @@ fn auth_write() {
         String::from("Digest nc=1")
     );
 }
+
+#[test]
+fn round_trip() {
+    let input = "Authorization: Digest username=\"bob\", realm=\"biloxi.com\", \
+                 nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", uri=\"sip:bob@biloxi.com\", \
+                 response=\"245f23415f11432b3434341c022\", algorithm=MD5, qop=auth, nc=00000001, \
+                 cnonce=\"0a4f113b\", userhash=false";
+    let (_, header) =
+        parse_authorization_header::<VerboseError<&[u8]>>(format!("{}\r\n", input).as_ref())
+            .unwrap();
+    assert_eq!(input, format!("{}", header));
+}
@@ -100,3 +100,63 @@ fn read() {
         parse_contact_header::<VerboseError<&[u8]>>(b"Contact: <sip:guy@example.com>;+sip.instance=\"<urn:uuid:1e020c2b-46f6-4867-9d11-65547b8967fa>\"\r\n")
     );
 }
+
+#[test]
+fn wildcard() {
+    let remains = vec![];
+    let header = Header::Contact(ContactHeader::wildcard());
+    assert_eq!("Contact: *", format!("{}", header));
+    assert_eq!(
+        Ok((remains.as_ref(), header)),
+        parse_contact_header::<VerboseError<&[u8]>>(b"Contact: *\r\n")
+    );
+}
+
+#[test]
+fn escape_quoted_values() {
+    let uri = Uri::sip(domain!("example.com")).auth(uri_auth!("guy"));
+    let mut contact = contact_header!(uri, "Guy \"The\" \\Face");
+    contact.set_param("+sip.note", Some("\"a \\\"b\\\" \\\\c\""));
+    let input = "Contact: \"Guy \\\"The\\\" \\\\Face\" <sip:guy@example.com>;+sip.note=\"a \\\"b\\\" \\\\c\"\r\n";
+    assert_eq!(input, format!("{}\r\n", Header::Contact(contact.clone())));
+    assert_eq!(
+        Ok((&b""[..], Header::Contact(contact))),
+        parse_contact_header::<VerboseError<&[u8]>>(input.as_bytes())
+    );
+
+    let uri = Uri::sip(domain!("example.com"));
+    let mut empty = contact_header!(uri);
+    empty.display_name = Some(String::new());
+    assert_eq!("Contact: \"\" <sip:example.com>", format!("{}", Header::Contact(empty)));
+}
+
+#[test]
+fn addr_spec_params() {
+    let remains = vec![];
+    let uri = Uri::sip(domain!("example.com")).auth(uri_auth!("guy"));
+    let mut contact = ContactHeader::new(uri.clone());
+    contact.set_param("+sip.foo", Some("bar"));
+    assert_eq!(
+        Ok((remains.as_ref(), Header::Contact(contact.clone()))),
+        parse_contact_header::<VerboseError<&[u8]>>(
+            b"Contact: sip:guy@example.com;+sip.foo=bar\r\n"
+        )
+    );
+    assert_eq!(
+        "Contact: sip:guy@example.com;+sip.foo=bar",
+        format!("{}", Header::Contact(contact))
+    );
+
+    let uri = uri.parameter(UriParam::Transport(Transport::Tcp));
+    let mut contact = contact_header!(uri);
+    contact.set_param("+sip.foo", Some("bar"));
+    let input = format!("{}\r\n", Header::Contact(contact.clone()));
+    assert_eq!(
+        "Contact: <sip:guy@example.com;transport=TCP>;+sip.foo=bar\r\n",
+        input
+    );
+    assert_eq!(
+        Ok((remains.as_ref(), Header::Contact(contact))),
+        parse_contact_header::<VerboseError<&[u8]>>(input.as_bytes())
+    );
+}
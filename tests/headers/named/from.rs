@@ -59,3 +59,54 @@ fn read() {
         parse_from_header::<VerboseError<&[u8]>>(b"From: sip:unknown@127.0.0.1\r\n")
     );
 }
+
+#[test]
+fn addr_spec_params() {
+    let uri = Uri::sip(ip_domain!(127, 0, 0, 1, 5000)).auth(uri_auth!("guy"));
+    let header = Header::From(NamedHeader::new(uri.clone()).param("tag", Some("1928301774")));
+    let remains = vec![];
+    assert_eq!(
+        Ok((remains.as_ref(), header.clone())),
+        parse_from_header::<VerboseError<&[u8]>>(
+            b"From: sip:guy@127.0.0.1:5000;tag=1928301774\r\n"
+        )
+    );
+    assert_eq!(
+        "From: sip:guy@127.0.0.1:5000;tag=1928301774",
+        format!("{}", header)
+    );
+
+    let uri = uri.parameter(UriParam::Transport(Transport::Tcp));
+    let header = Header::From(NamedHeader::new(uri).param("tag", Some("1928301774")));
+    let input = format!("{}\r\n", header);
+    assert_eq!(
+        "From: <sip:guy@127.0.0.1:5000;transport=TCP>;tag=1928301774\r\n",
+        input
+    );
+    assert_eq!(
+        Ok((remains.as_ref(), header)),
+        parse_from_header::<VerboseError<&[u8]>>(input.as_bytes())
+    );
+}
+
+#[test]
+fn quote_display_names() {
+    let remains = vec![];
+    let uri = Uri::sip(domain!("example.com")).auth(uri_auth!("john"));
+    let header = Header::From(named_header!(uri, "Doe, John"));
+    let input = format!("{}\r\n", header);
+    assert_eq!("From: \"Doe, John\" <sip:john@example.com>\r\n", input);
+    assert_eq!(
+        Ok((remains.as_ref(), header)),
+        parse_from_header::<VerboseError<&[u8]>>(input.as_bytes())
+    );
+
+    let uri = Uri::sip(domain!("example.com")).auth(uri_auth!("john"));
+    let header = Header::From(named_header!(uri, "J.Doe-2"));
+    let input = format!("{}\r\n", header);
+    assert_eq!("From: J.Doe-2 <sip:john@example.com>\r\n", input);
+    assert_eq!(
+        Ok((remains.as_ref(), header)),
+        parse_from_header::<VerboseError<&[u8]>>(input.as_bytes())
+    );
+}
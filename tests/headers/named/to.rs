@@ -54,3 +54,41 @@ fn read() {
         parse_to_header::<VerboseError<&[u8]>>(b"To: <sip:guy@example.com>\r\n")
     );
 }
+
+#[test]
+fn quote_display_names() {
+    let remains = vec![];
+    let uri = Uri::sip(domain!("example.com")).auth(uri_auth!("john"));
+    let header = Header::To(named_header!(uri, "Doe, John"));
+    let input = format!("{}\r\n", header);
+    assert_eq!("To: \"Doe, John\" <sip:john@example.com>\r\n", input);
+    assert_eq!(
+        Ok((remains.as_ref(), header)),
+        parse_to_header::<VerboseError<&[u8]>>(input.as_bytes())
+    );
+}
+
+#[test]
+fn addr_spec_params() {
+    let remains = vec![];
+    let uri = Uri::sip(domain!("example.com")).auth(uri_auth!("bob"));
+    let header = Header::To(NamedHeader::new(uri.clone()).param("tag", Some("a6c85cf")));
+    assert_eq!(
+        Ok((remains.as_ref(), header.clone())),
+        parse_to_header::<VerboseError<&[u8]>>(b"To: sip:bob@example.com;tag=a6c85cf\r\n")
+    );
+    assert_eq!("To: sip:bob@example.com;tag=a6c85cf", format!("{}", header));
+
+    // A uri with headers is bracketed so they stay with it.
+    let uri = uri.header("subject", "lunch");
+    let header = Header::To(NamedHeader::new(uri).param("tag", Some("a6c85cf")));
+    let input = format!("{}\r\n", header);
+    assert_eq!(
+        "To: <sip:bob@example.com?subject=lunch>;tag=a6c85cf\r\n",
+        input
+    );
+    assert_eq!(
+        Ok((remains.as_ref(), header)),
+        parse_to_header::<VerboseError<&[u8]>>(input.as_bytes())
+    );
+}
@@ -1,12 +1,14 @@
-use libsip::{headers::parse::parse_proxy_authenticate_header, Header};
+use libsip::{headers::parse::parse_proxy_authenticate_header, *};
 
 use nom::error::VerboseError;
 
 #[test]
 fn write() {
-    let header = Header::ProxyAuthenticate("call@id.com".into());
+    let mut map = AuthParams::new();
+    map.insert("realm".into(), "atlanta.com".into());
+    let header = Header::ProxyAuthenticate(AuthHeader(AuthSchema::Digest, map));
     assert_eq!(
-        "Proxy-Authenticate: call@id.com".to_string(),
+        "Proxy-Authenticate: Digest realm=\"atlanta.com\"".to_string(),
         format!("{}", header)
     );
 }
@@ -14,11 +16,14 @@ fn write() {
 #[test]
 fn read() {
     let remains = vec![];
-    let header = Header::ProxyAuthenticate("call@id.com".into());
+    let mut map = AuthParams::new();
+    map.insert("realm".into(), "atlanta.com".into());
+    map.insert("nonce".into(), "wf84f1ceczx41ae6cbe5aea9c8e88d359".into());
+    let header = Header::ProxyAuthenticate(AuthHeader(AuthSchema::Digest, map));
     assert_eq!(
         Ok((remains.as_ref(), header)),
         parse_proxy_authenticate_header::<VerboseError<&[u8]>>(
-            b"Proxy-Authenticate: call@id.com\r\n"
+            b"Proxy-Authenticate: Digest realm=\"atlanta.com\", nonce=\"wf84f1ceczx41ae6cbe5aea9c8e88d359\"\r\n"
         )
     );
 }
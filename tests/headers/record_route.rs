@@ -1,12 +1,14 @@
-use libsip::{headers::parse::parse_record_route_header, Header};
+use libsip::{headers::parse::parse_record_route_header, *};
 
 use nom::error::VerboseError;
 
 #[test]
 fn write() {
-    let header = Header::RecordRoute("Softphone 1.0".into());
+    let header = Header::RecordRoute(RouteHeader::new(
+        Uri::sip(domain!("p1.example.com")).parameter(UriParam::Lr),
+    ));
     assert_eq!(
-        "Record-Route: Softphone 1.0".to_string(),
+        "Record-Route: <sip:p1.example.com;lr>".to_string(),
         format!("{}", header)
     );
 }
@@ -14,9 +16,33 @@ fn write() {
 #[test]
 fn read() {
     let remains = vec![];
-    let header = Header::RecordRoute("Softphone 1.0".into());
+    let header = Header::RecordRoute(RouteHeader::new(
+        Uri::sip(domain!("p1.example.com")).parameter(UriParam::Lr),
+    ));
     assert_eq!(
         Ok((remains.as_ref(), header)),
-        parse_record_route_header::<VerboseError<&[u8]>>(b"Record-Route: Softphone 1.0\r\n")
+        parse_record_route_header::<VerboseError<&[u8]>>(
+            b"Record-Route: <sip:p1.example.com;lr>\r\n"
+        )
     );
 }
+
+#[test]
+fn parameter_order() {
+    let input = "Record-Route: <sip:p1.example.com;lr>;zeta=1;alpha;mid=2;beta=3\r\n";
+    let (_, header) = parse_record_route_header::<VerboseError<&[u8]>>(input.as_bytes()).unwrap();
+    assert_eq!(input, format!("{}\r\n", header));
+}
+
+#[test]
+fn escape_quoted_values() {
+    let input = "Record-Route: \"Proxy \\\"One\\\" \\\\ Two\" <sip:p1.example.com;lr>;note=\"a b\"\r\n";
+    let (_, header) = parse_record_route_header::<VerboseError<&[u8]>>(input.as_bytes()).unwrap();
+    if let Header::RecordRoute(route) = &header {
+        assert_eq!(route.display_name, Some("Proxy \"One\" \\ Two".into()));
+        assert_eq!(route.parameters.get("note"), Some(&Some("a b".into())));
+    } else {
+        panic!("expected a Record-Route header");
+    }
+    assert_eq!(input, format!("{}\r\n", header));
+}
@@ -1,19 +1,53 @@
-use libsip::{headers::parse::parse_route_header, Header};
+use libsip::{headers::parse::parse_route_header, *};
 
 use nom::error::VerboseError;
 
 #[test]
 fn write() {
-    let header = Header::Route("Softphone 1.0".into());
-    assert_eq!("Route: Softphone 1.0".to_string(), format!("{}", header));
+    let header = Header::Route(RouteHeader::new(
+        Uri::sip(domain!("p1.example.com")).parameter(UriParam::Lr),
+    ));
+    assert_eq!(
+        "Route: <sip:p1.example.com;lr>".to_string(),
+        format!("{}", header)
+    );
 }
 
 #[test]
 fn read() {
     let remains = vec![];
-    let header = Header::Route("Softphone 1.0".into());
+    let header = Header::Route(RouteHeader::new(
+        Uri::sip(domain!("p1.example.com")).parameter(UriParam::Lr),
+    ));
     assert_eq!(
         Ok((remains.as_ref(), header)),
-        parse_route_header::<VerboseError<&[u8]>>(b"Route: Softphone 1.0\r\n")
+        parse_route_header::<VerboseError<&[u8]>>(b"Route: <sip:p1.example.com;lr>\r\n")
     );
 }
+
+#[test]
+fn read_strict() {
+    let (_, header) =
+        parse_route_header::<VerboseError<&[u8]>>(b"Route: \"Proxy\" <sip:p2.example.com>\r\n")
+            .unwrap();
+    if let Header::Route(route) = header {
+        assert_eq!(route.display_name, Some("Proxy".into()));
+        assert!(!route.is_loose());
+    } else {
+        panic!("expected a Route header");
+    }
+}
+
+#[test]
+fn token_display_name() {
+    let input = "Route: Proxy <sip:p2.example.com;lr>\r\n";
+    let (_, header) = parse_route_header::<VerboseError<&[u8]>>(input.as_bytes()).unwrap();
+    assert_eq!(input, format!("{}\r\n", header));
+}
+
+#[test]
+fn escape_display_name() {
+    let input = "Route: \"Proxy \\\"Two\\\"\" <sip:p2.example.com;lr>\r\n";
+    let (_, header) = parse_route_header::<VerboseError<&[u8]>>(input.as_bytes()).unwrap();
+    assert_eq!(input, format!("{}\r\n", header));
+}
@@ -2,15 +2,31 @@ use libsip::{headers::parse::parse_via_header, *};
 
 use nom::error::VerboseError;
 
+use std::net::{Ipv6Addr, SocketAddr};
+
+fn parse(input: &[u8]) -> ViaHeader {
+    match parse_via_header::<VerboseError<&[u8]>>(input) {
+        Ok((b"", Header::Via(via))) => via,
+        other => panic!("failed to parse via: {:?}", other),
+    }
+}
+
 #[test]
 fn write() {
+    let header = ViaHeader::new(domain!("example.com"), Transport::Udp);
+    assert_eq!(
+        "Via: SIP/2.0/UDP example.com".to_string(),
+        format!("{}", header)
+    );
+
     let header = ViaHeader {
-        version: Version::default(),
-        transport: Transport::Udp,
-        uri: Uri::new_schemaless(domain!("example.com")),
+        branch: Some("z9hG4bK776asdhds".into()),
+        received: Some(ip_domain!(192, 0, 2, 1)),
+        rport: Some(Some(9988)),
+        ..ViaHeader::new(ip_domain!(10, 1, 1, 1, 4540), Transport::Udp)
     };
     assert_eq!(
-        "Via: SIP/2.0/UDP example.com".to_string(),
+        "Via: SIP/2.0/UDP 10.1.1.1:4540;branch=z9hG4bK776asdhds;received=192.0.2.1;rport=9988",
         format!("{}", header)
     );
 }
@@ -18,57 +34,172 @@ fn write() {
 #[test]
 fn read() {
     let remains = vec![];
-    let header = ViaHeader {
-        version: Version::default(),
-        transport: Transport::Udp,
-        uri: Uri::new_schemaless(domain!("example.com")),
-    };
+    let header = ViaHeader::new(domain!("example.com"), Transport::Udp);
     assert_eq!(
         Ok((remains.as_ref(), Header::Via(header))),
         parse_via_header::<VerboseError<&[u8]>>(b"Via: SIP/2.0/UDP example.com\r\n")
     );
 
-    let input = b"Via: SIP/2.0/UDP 192.168.1.120;rport;branch=z9hG4bK7Q6y313Qrt6Uc\r\n";
-    let remains = vec![];
     let header = ViaHeader {
-        version: Version::default(),
-        transport: Transport::Udp,
-        uri: Uri::new_schemaless(ip_domain!(192, 168, 1, 120))
-            .parameter(UriParam::RPort(None))
-            .parameter(UriParam::Branch("z9hG4bK7Q6y313Qrt6Uc".into())),
+        rport: Some(None),
+        branch: Some("z9hG4bK7Q6y313Qrt6Uc".into()),
+        ..ViaHeader::new(ip_domain!(192, 168, 1, 120), Transport::Udp)
     };
     assert_eq!(
-        Ok((remains.as_ref(), Header::Via(header))),
-        parse_via_header::<VerboseError<&[u8]>>(input)
+        header,
+        parse(b"Via: SIP/2.0/UDP 192.168.1.120;rport;branch=z9hG4bK7Q6y313Qrt6Uc\r\n")
     );
 
-    let input = b"Via: SIP/2.0/UDP 192.168.1.1:5060;rport=5060;received=192.168.1.1;branch=8e7ec4e3d1e1380bc111f8723341ca70;transport=UDP\r\n";
-    let remains = vec![];
     let header = ViaHeader {
-        version: Version::default(),
-        transport: Transport::Udp,
-        uri: Uri::new_schemaless(ip_domain!(192, 168, 1, 1, 5060))
-            .parameter(UriParam::RPort(Some(5060)))
-            .parameter(UriParam::Received(ip_domain!(192, 168, 1, 1)))
-            .parameter(UriParam::Branch("8e7ec4e3d1e1380bc111f8723341ca70".into()))
-            .parameter(UriParam::Transport(Transport::Udp)),
-    };
+        rport: Some(Some(5060)),
+        received: Some(ip_domain!(192, 168, 1, 1)),
+        branch: Some("8e7ec4e3d1e1380bc111f8723341ca70".into()),
+        ..ViaHeader::new(ip_domain!(192, 168, 1, 1, 5060), Transport::Udp)
+    }
+    .parameter("transport", Some("UDP".into()));
     assert_eq!(
-        Ok((remains.as_ref(), Header::Via(header))),
-        parse_via_header::<VerboseError<&[u8]>>(input)
+        header,
+        parse(b"Via: SIP/2.0/UDP 192.168.1.1:5060;rport=5060;received=192.168.1.1;branch=8e7ec4e3d1e1380bc111f8723341ca70;transport=UDP\r\n")
     );
 
-    let input = b"Via: SIP/2.0/UDP 192.168.1.120;branch=03395ed83a7b9502c671c769bbe369cb;received=192.168.1.76\r\n";
-    let remains = vec![];
     let header = ViaHeader {
-        version: Version::default(),
-        transport: Transport::Udp,
-        uri: Uri::new_schemaless(ip_domain!(192, 168, 1, 120))
-            .parameter(UriParam::Branch("03395ed83a7b9502c671c769bbe369cb".into()))
-            .parameter(UriParam::Received(ip_domain!(192, 168, 1, 76))),
+        branch: Some("03395ed83a7b9502c671c769bbe369cb".into()),
+        received: Some(ip_domain!(192, 168, 1, 76)),
+        ..ViaHeader::new(ip_domain!(192, 168, 1, 120), Transport::Udp)
     };
     assert_eq!(
-        Ok((remains.as_ref(), Header::Via(header))),
-        parse_via_header::<VerboseError<&[u8]>>(input)
+        header,
+        parse(b"Via: SIP/2.0/UDP 192.168.1.120;branch=03395ed83a7b9502c671c769bbe369cb;received=192.168.1.76\r\n")
+    );
+}
+
+#[test]
+fn read_maddr_ttl_and_generic_params() {
+    let via = parse(
+        b"Via: SIP/2.0/UDP example.com:5070;maddr=239.255.255.1;TTL=16;branch=z9hG4bKa;hide;x-tag=\"a b\"\r\n",
     );
+    assert_eq!(Some(ip_domain!(239, 255, 255, 1)), via.maddr);
+    assert_eq!(Some(16), via.ttl);
+    assert_eq!(Some(&"z9hG4bKa".to_string()), via.branch());
+    assert_eq!(
+        vec![
+            ("hide".to_string(), None),
+            ("x-tag".to_string(), Some("\"a b\"".to_string()))
+        ],
+        via.parameters
+    );
+    assert_eq!(
+        "Via: SIP/2.0/UDP example.com:5070;branch=z9hG4bKa;maddr=239.255.255.1;ttl=16;hide;x-tag=\"a b\"",
+        format!("{}", via)
+    );
+
+    assert!(parse_via_header::<VerboseError<&[u8]>>(b"Via: SIP/2.0/UDP example.com;ttl=300\r\n").is_err());
+    assert!(parse_via_header::<VerboseError<&[u8]>>(b"Via: SIP/2.0/UDP example.com;rport=x\r\n").is_err());
+}
+
+#[test]
+fn read_ipv6() {
+    let addr: Ipv6Addr = "2001:db8::1".parse().unwrap();
+    let received: Ipv6Addr = "2001:db8::9".parse().unwrap();
+    let input = "Via: SIP/2.0/TCP [2001:db8::1]:5060;branch=z9hG4bK776;received=[2001:db8::9]\r\n";
+    let header = ViaHeader {
+        branch: Some("z9hG4bK776".into()),
+        received: Some(Domain::Ipv6(received, None)),
+        ..ViaHeader::new(Domain::Ipv6(addr, Some(5060)), Transport::Tcp)
+    };
+    assert_eq!(header, parse(input.as_bytes()));
+    assert_eq!(format!("{}\r\n", header), input);
+
+    let bare = parse(b"Via: SIP/2.0/TCP [2001:db8::1]:5060;branch=z9hG4bK776;received=2001:db8::9\r\n");
+    assert_eq!(bare, header);
+}
+
+#[test]
+fn received_from() {
+    let source: SocketAddr = "192.0.2.4:9988".parse().unwrap();
+
+    let mut via = ViaHeader::new(ip_domain!(192, 0, 2, 4, 5060), Transport::Udp);
+    via.received_from(source);
+    assert_eq!(None, via.received);
+
+    let mut via = ViaHeader::new(domain!("pc33.atlanta.com"), Transport::Udp);
+    via.received_from(source);
+    assert_eq!(Some(ip_domain!(192, 0, 2, 4)), via.received);
+    assert_eq!(None, via.rport);
+
+    let mut via = ViaHeader {
+        rport: Some(None),
+        ..ViaHeader::new(ip_domain!(192, 0, 2, 4, 5060), Transport::Udp)
+    };
+    via.received_from(source);
+    assert_eq!(Some(ip_domain!(192, 0, 2, 4)), via.received);
+    assert_eq!(Some(Some(9988)), via.rport);
+}
+
+#[test]
+fn response_target() {
+    let via = ViaHeader::new(domain!("pc33.atlanta.com"), Transport::Udp);
+    let target = via.response_target();
+    assert_eq!(domain!("pc33.atlanta.com", 5060), target.host);
+    assert!(!target.reuse_connection);
+
+    let via = ViaHeader {
+        received: Some(ip_domain!(192, 0, 2, 1)),
+        ..ViaHeader::new(domain!("pc33.atlanta.com", 5070), Transport::Udp)
+    };
+    assert_eq!(ip_domain!(192, 0, 2, 1, 5070), via.response_target().host);
+
+    let via = ViaHeader {
+        received: Some(ip_domain!(192, 0, 2, 1)),
+        rport: Some(Some(9988)),
+        ..ViaHeader::new(ip_domain!(10, 1, 1, 1, 4540), Transport::Udp)
+    };
+    assert_eq!(ip_domain!(192, 0, 2, 1, 9988), via.response_target().host);
+
+    let via = ViaHeader {
+        maddr: Some(ip_domain!(239, 255, 255, 1)),
+        ttl: Some(16),
+        ..ViaHeader::new(ip_domain!(10, 1, 1, 1), Transport::Udp)
+    };
+    let target = via.response_target();
+    assert_eq!(ip_domain!(239, 255, 255, 1, 5060), target.host);
+    assert_eq!(Some(16), target.ttl);
+
+    let via = ViaHeader {
+        received: Some(ip_domain!(192, 0, 2, 1)),
+        ..ViaHeader::new(ip_domain!(10, 1, 1, 1, 5062), Transport::Tcp)
+    };
+    let target = via.response_target();
+    assert!(target.reuse_connection);
+    assert_eq!(ip_domain!(192, 0, 2, 1, 5062), target.host);
+}
+
+#[test]
+fn push_and_pop() {
+    let client = ViaHeader {
+        branch: Some("z9hG4bK776asdhds".into()),
+        ..ViaHeader::new(domain!("pc33.atlanta.com"), Transport::Udp)
+    };
+    let mut request = RequestGenerator::new()
+        .method(Method::Invite)
+        .uri(Uri::sip(domain!("biloxi.com")).auth(uri_auth!("bob")))
+        .header(Header::CallId("a84b4c76e66710".into()))
+        .header(Header::Via(client.clone()))
+        .build()
+        .unwrap();
+
+    request.push_via(ViaHeader::new(domain!("bigbox3.site3.atlanta.com"), Transport::Udp));
+    let vias = request.via_headers();
+    assert_eq!(2, vias.len());
+    assert_eq!(domain!("bigbox3.site3.atlanta.com"), vias[0].sent_by);
+    assert!(vias[0].is_rfc3261_branch());
+    assert_ne!(vias[0].branch(), client.branch());
+    assert_eq!(&client, vias[1]);
+    assert_eq!(Ok(vias[0].branch().unwrap()), request.via_header_branch());
+
+    let proxy = request.pop_via().unwrap();
+    assert_eq!(domain!("bigbox3.site3.atlanta.com"), proxy.sent_by);
+    assert_eq!(vec![&client], request.via_headers());
+    assert_eq!(Some(client), request.pop_via());
+    assert_eq!(None, request.pop_via());
 }
@@ -1,11 +1,9 @@
 use libsip::{headers::parse::parse_www_authenticate_header, *};
 use nom::error::VerboseError;
 
-use std::collections::HashMap;
-
 #[test]
 fn write() {
-    let mut map = HashMap::new();
+    let mut map = AuthParams::new();
     map.insert("key".into(), "value".into());
     let header = Header::WwwAuthenticate(AuthHeader(AuthSchema::Digest, map));
     assert_eq!(
@@ -17,7 +15,7 @@ fn write() {
 #[test]
 fn read() {
     let remains = vec![];
-    let mut map = HashMap::new();
+    let mut map = AuthParams::new();
     map.insert("key".into(), "value".into());
     let header = Header::WwwAuthenticate(AuthHeader(AuthSchema::Digest, map));
     assert_eq!(
@@ -27,3 +25,21 @@ fn read() {
         )
     );
 }
+
+#[test]
+fn round_trip() {
+    let input = "WWW-Authenticate: Digest realm=\"atlanta \\\"east\\\"\", qop=\"auth,auth-int\", \
+                 algorithm=SHA-256, nonce=\"84a4cc6f3082121f32b42a2187831a9e\", stale=TRUE, \
+                 opaque=\"a\\\\b\"";
+    let (_, header) =
+        parse_www_authenticate_header::<VerboseError<&[u8]>>(format!("{}\r\n", input).as_ref())
+            .unwrap();
+    match &header {
+        Header::WwwAuthenticate(AuthHeader(_, params)) => {
+            assert_eq!(Some(&"atlanta \"east\"".to_string()), params.get("realm"));
+            assert_eq!(Some(&"a\\b".to_string()), params.get("opaque"));
+        },
+        _ => panic!("expected a WWW-Authenticate header"),
+    }
+    assert_eq!(input, format!("{}", header));
+}
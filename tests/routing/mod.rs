@@ -0,0 +1,124 @@
+use libsip::{routing::*, *};
+
+fn loose(host: &str) -> RouteHeader {
+    crate::dialog::route(host)
+}
+
+fn strict(host: &str) -> RouteHeader {
+    RouteHeader::new(Uri::sip(domain!(host)))
+}
+
+fn target() -> Uri {
+    Uri::sip(ip_domain!(10, 0, 0, 9, 5070)).auth(uri_auth!("bob"))
+}
+
+fn request(uri: Uri, routes: Vec<RouteHeader>) -> SipMessage {
+    let mut req = RequestGenerator::new()
+        .method(Method::Invite)
+        .uri(uri)
+        .header(Header::MaxForwards(70));
+    for route in routes {
+        req = req.header(Header::Route(route));
+    }
+    req.header(Header::CallId("abcd".into())).build().unwrap()
+}
+
+fn request_uri(msg: &SipMessage) -> &Uri {
+    match msg {
+        SipMessage::Request { uri, .. } => uri,
+        SipMessage::Response { .. } => panic!("expected a request"),
+    }
+}
+
+#[test]
+fn loose_routing() {
+    let route_set = vec![loose("p1.example.com"), strict("p2.example.com")];
+    assert_eq!(
+        request_target(&target(), &route_set),
+        (target(), route_set.clone())
+    );
+    assert_eq!(request_target(&target(), &[]), (target(), vec![]));
+}
+
+#[test]
+fn strict_routing() {
+    let route_set = vec![strict("p1.example.com"), loose("p2.example.com")];
+    let (uri, expected) = request_target(&target(), &route_set);
+    assert_eq!(uri, Uri::sip(domain!("p1.example.com")));
+    assert_eq!(
+        expected,
+        vec![loose("p2.example.com"), RouteHeader::new(target())]
+    );
+
+    let mut req = request(
+        Uri::sip(domain!("example.com")),
+        vec![loose("old.example.com")],
+    );
+    apply_route_set(&mut req, &target(), &route_set);
+    assert_eq!(request_uri(&req), &uri);
+    assert_eq!(routes(&req), expected);
+    assert_eq!(req.headers().0[1], Header::Route(loose("p2.example.com")));
+    assert_eq!(
+        next_hop(&req),
+        Some(Uri::sip(domain!("p2.example.com")).parameter(UriParam::Lr))
+    );
+}
+
+#[test]
+fn proxy_routing() {
+    let me = |uri: &Uri| uri.host == domain!("p1.example.com");
+
+    // A loose router removes it's own Route header.
+    let mut req = request(
+        target(),
+        vec![loose("p1.example.com"), strict("p2.example.com")],
+    );
+    preprocess_routes(&mut req, me);
+    assert_eq!(routes(&req), vec![strict("p2.example.com")]);
+
+    // The next hop is a strict router, so it becomes the Request-URI.
+    postprocess_routes(&mut req);
+    assert_eq!(request_uri(&req), &Uri::sip(domain!("p2.example.com")));
+    assert_eq!(routes(&req), vec![RouteHeader::new(target())]);
+
+    // The previous hop was a strict router, restore the Request-URI.
+    let mut req = request(
+        Uri::sip(domain!("p1.example.com")).parameter(UriParam::Lr),
+        vec![loose("p3.example.com"), RouteHeader::new(target())],
+    );
+    preprocess_routes(&mut req, me);
+    assert_eq!(request_uri(&req), &target());
+    assert_eq!(routes(&req), vec![loose("p3.example.com")]);
+}
+
+#[test]
+fn dialog_routes() {
+    let mut res = ResponseGenerator::new()
+        .code(200)
+        .header(Header::RecordRoute(loose("p1.example.com")))
+        .header(Header::RecordRoute(strict("p2.example.com")))
+        .build()
+        .unwrap();
+    assert_eq!(
+        dialog_route_set(&res, true),
+        vec![strict("p2.example.com"), loose("p1.example.com")]
+    );
+    assert_eq!(
+        dialog_route_set(&res, false),
+        vec![loose("p1.example.com"), strict("p2.example.com")]
+    );
+    res.headers_mut().0.clear();
+    assert!(dialog_route_set(&res, true).is_empty());
+}
+
+#[test]
+fn tel_next_hop() {
+    let tel = Uri::tel(TelUri::global("+15550100"));
+    assert_eq!(next_hop(&request(tel.clone(), vec![])), None);
+    assert_eq!(
+        next_hop(&request(tel, vec![loose("p1.example.com")])),
+        Some(loose("p1.example.com").uri)
+    );
+    let route = RouteHeader::new(Uri::tel(TelUri::global("+15550101")));
+    assert_eq!(next_hop(&request(target(), vec![route])), None);
+}
@@ -0,0 +1,187 @@
+use libsip::*;
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+fn now() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_600_000_000)
+}
+
+fn store() -> HashMap<String, Credential> {
+    let mut store = HashMap::new();
+    store.insert("alice".to_string(), Credential::Password("secret".into()));
+    store
+}
+
+fn request(method: Method, body: &[u8]) -> SipMessage {
+    let to = Uri::sip(domain!("example.com")).auth(uri_auth!("alice"));
+    RequestGenerator::new()
+        .method(method)
+        .uri(Uri::sip(domain!("example.com")))
+        .header(Header::To(named_header!(to)))
+        .header(Header::CallId("a84b4c76e66710".into()))
+        .header(Header::CSeq(1, method))
+        .body(body.to_vec())
+        .build()
+        .unwrap()
+}
+
+/// Answer the challenge of `response` the way a client does.
+fn answer(request: &SipMessage, response: &SipMessage, pass: &str) -> SipMessage {
+    let mut challenges = AuthChallenges::new();
+    challenges.set_challenge(response).unwrap();
+    let mut request = request.clone();
+    challenges.authorize(&mut request, "alice", pass).unwrap();
+    request
+}
+
+#[test]
+fn challenge_and_verify() {
+    let mut verifier = DigestVerifier::new("example.com", store());
+    let req = request(Method::Register, b"");
+    assert_eq!(Verification::Unauthorized, verifier.verify(&req, now()));
+
+    let challenge = verifier.challenge_response(&req, now(), false).unwrap();
+    assert_eq!(401, challenge.status_code().unwrap());
+    assert!(challenge.to_header_tag().is_ok());
+    assert_eq!(req.call_id(), challenge.call_id());
+
+    let authorized = answer(&req, &challenge, "secret");
+    assert_eq!(
+        Verification::Authorized("alice".into()),
+        verifier.verify(&authorized, now())
+    );
+    assert_eq!(
+        Verification::Unauthorized,
+        verifier.verify(&answer(&req, &challenge, "wrong"), now())
+    );
+}
+
+#[test]
+fn reject_replays() {
+    let mut verifier = DigestVerifier::new("example.com", store());
+    let req = request(Method::Register, b"");
+    let challenge = verifier.challenge_response(&req, now(), false).unwrap();
+    let mut challenges = AuthChallenges::new();
+    challenges.set_challenge(&challenge).unwrap();
+
+    let mut first = req.clone();
+    challenges.authorize(&mut first, "alice", "secret").unwrap();
+    assert_eq!(
+        Verification::Authorized("alice".into()),
+        verifier.verify(&first, now())
+    );
+    assert_eq!(Verification::Replay, verifier.verify(&first, now()));
+
+    let mut second = req;
+    challenges.authorize(&mut second, "alice", "secret").unwrap();
+    assert_eq!(
+        Verification::Authorized("alice".into()),
+        verifier.verify(&second, now())
+    );
+}
+
+#[test]
+fn reject_qopless_replays() {
+    let mut verifier = DigestVerifier::new("example.com", store()).qop(vec![]);
+    let req = request(Method::Register, b"");
+    let challenge = verifier.challenge_response(&req, now(), false).unwrap();
+    let authorized = answer(&req, &challenge, "secret");
+    assert_eq!(
+        Verification::Authorized("alice".into()),
+        verifier.verify(&authorized, now())
+    );
+    assert_eq!(Verification::Replay, verifier.verify(&authorized, now()));
+}
+
+#[test]
+fn reject_other_request_uri() {
+    let mut verifier = DigestVerifier::new("example.com", store());
+    let req = request(Method::Register, b"");
+    let challenge = verifier.challenge_response(&req, now(), false).unwrap();
+    let mut authorized = answer(&req, &challenge, "secret");
+    if let SipMessage::Request { uri, .. } = &mut authorized {
+        *uri = Uri::sip(domain!("attacker.example.com"));
+    }
+    assert_eq!(
+        Verification::Unauthorized,
+        verifier.verify(&authorized, now())
+    );
+}
+
+#[test]
+fn stale_nonce() {
+    let mut verifier = DigestVerifier::new("example.com", store())
+        .nonce_lifetime(Duration::from_secs(60));
+    let req = request(Method::Register, b"");
+    let challenge = verifier.challenge_response(&req, now(), false).unwrap();
+    let authorized = answer(&req, &challenge, "secret");
+    let later = now() + Duration::from_secs(61);
+    assert_eq!(Verification::Stale, verifier.verify(&authorized, later));
+    // A wrong password is never reported as stale.
+    assert_eq!(
+        Verification::Unauthorized,
+        verifier.verify(&answer(&req, &challenge, "wrong"), later)
+    );
+
+    match verifier.challenge(later, true) {
+        Header::WwwAuthenticate(AuthHeader(_, params)) => {
+            assert_eq!(Some(&"true".to_string()), params.get("stale"))
+        },
+        header => panic!("unexpected challenge {:?}", header),
+    }
+}
+
+#[test]
+fn nonces_are_signed() {
+    let verifier = DigestVerifier::new("example.com", store()).secret(b"shared");
+    let mut other = DigestVerifier::new("example.com", store());
+    let mut peer = DigestVerifier::new("example.com", store()).secret(b"shared");
+    let req = request(Method::Register, b"");
+    let challenge = verifier.challenge_response(&req, now(), false).unwrap();
+    let authorized = answer(&req, &challenge, "secret");
+    assert_eq!(Verification::Unauthorized, other.verify(&authorized, now()));
+    assert_eq!(
+        Verification::Authorized("alice".into()),
+        peer.verify(&authorized, now())
+    );
+}
+
+#[test]
+fn ha1_credentials_and_auth_int() {
+    let ha1 = DigestAlgorithm::Sha256.hash("alice:example.com:secret");
+    let mut store = HashMap::new();
+    store.insert("alice".to_string(), Credential::Ha1(ha1));
+    let mut verifier = DigestVerifier::new("example.com", store)
+        .algorithm(DigestAlgorithm::Sha256)
+        .qop(vec!["auth-int".into()]);
+    let req = request(Method::Message, b"hello");
+    let challenge = verifier.challenge_response(&req, now(), false).unwrap();
+    let mut authorized = answer(&req, &challenge, "secret");
+    assert_eq!(
+        Verification::Authorized("alice".into()),
+        verifier.verify(&authorized, now())
+    );
+
+    *authorized.body_mut() = b"tampered".to_vec();
+    assert_eq!(Verification::Unauthorized, verifier.verify(&authorized, now()));
+}
+
+#[test]
+fn proxy_challenge() {
+    let mut verifier = DigestVerifier::new("example.com", store()).proxy(true);
+    let req = request(Method::Invite, b"");
+    let challenge = verifier.challenge_response(&req, now(), false).unwrap();
+    assert_eq!(407, challenge.status_code().unwrap());
+    let authorized = answer(&req, &challenge, "secret");
+    assert!(authorized
+        .headers()
+        .iter()
+        .any(|header| matches!(header, Header::ProxyAuthorization(_))));
+    assert_eq!(
+        Verification::Authorized("alice".into()),
+        verifier.verify(&authorized, now())
+    );
+}
@@ -0,0 +1,214 @@
+use libsip::*;
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+type MemoryRegistrar = Registrar<HashMap<String, Vec<Binding>>>;
+
+fn aor() -> Uri {
+    Uri::sip(domain!("example.com")).auth(uri_auth!("alice"))
+}
+
+fn contact(host: &str) -> ContactHeader {
+    ContactHeader::new(Uri::sip(domain!(host)).auth(uri_auth!("alice")))
+}
+
+fn register(
+    call_id: &str,
+    cseq: u32,
+    expires: Option<u32>,
+    contacts: Vec<ContactHeader>,
+) -> SipMessage {
+    let mut req = RequestGenerator::new()
+        .method(Method::Register)
+        .uri(Uri::sip(domain!("example.com")))
+        .header(Header::To(NamedHeader::new(aor())))
+        .header(Header::From(NamedHeader::new(aor())))
+        .header(Header::CallId(call_id.into()))
+        .header(Header::CSeq(cseq, Method::Register));
+    for contact in contacts {
+        req = req.header(Header::Contact(contact));
+    }
+    if let Some(expires) = expires {
+        req = req.header(Header::Expires(expires));
+    }
+    req.build().unwrap()
+}
+
+fn contacts(response: &SipMessage) -> Vec<(String, Option<String>)> {
+    response
+        .headers()
+        .iter()
+        .filter_map(|header| match header {
+            Header::Contact(contact) => Some((
+                contact.uri.host(),
+                contact
+                    .parameters
+                    .get("expires")
+                    .and_then(|value| value.as_ref())
+                    .map(|value| value.to_string()),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn add_and_refresh() {
+    let mut registrar = MemoryRegistrar::new(HashMap::new());
+    let now = Instant::now();
+
+    let req = register("call-1", 1, Some(600), vec![contact("desk.example.com")]);
+    let res = registrar.process(&req, now).unwrap();
+    assert_eq!(Ok(200), res.status_code());
+    assert!(res.to_header().unwrap().parameters.contains_key("tag"));
+    assert_eq!(
+        vec![("desk.example.com".to_string(), Some("600".to_string()))],
+        contacts(&res)
+    );
+
+    let phone = contact("phone.example.com").param("expires", Some("120"));
+    let req = register("call-2", 1, None, vec![phone]);
+    let res = registrar
+        .process(&req, now + Duration::from_secs(100))
+        .unwrap();
+    assert_eq!(
+        vec![
+            ("desk.example.com".to_string(), Some("500".to_string())),
+            ("phone.example.com".to_string(), Some("120".to_string())),
+        ],
+        contacts(&res)
+    );
+
+    let req = register("call-1", 2, Some(3600), vec![contact("desk.example.com")]);
+    registrar
+        .process(&req, now + Duration::from_secs(200))
+        .unwrap();
+    let bindings = registrar.lookup(&aor(), now + Duration::from_secs(250));
+    assert_eq!(1, bindings.len());
+    assert_eq!(3550, bindings[0].remaining(now + Duration::from_secs(250)));
+}
+
+#[test]
+fn query_without_contacts() {
+    let mut registrar = MemoryRegistrar::new(HashMap::new());
+    let now = Instant::now();
+    let req = register("call-1", 1, None, vec![contact("desk.example.com")]);
+    registrar.process(&req, now).unwrap();
+
+    let res = registrar
+        .process(&register("call-9", 1, None, vec![]), now)
+        .unwrap();
+    assert_eq!(
+        vec![("desk.example.com".to_string(), Some("3600".to_string()))],
+        contacts(&res)
+    );
+}
+
+#[test]
+fn out_of_order() {
+    let mut registrar = MemoryRegistrar::new(HashMap::new());
+    let now = Instant::now();
+    let req = register("call-1", 5, None, vec![contact("desk.example.com")]);
+    registrar.process(&req, now).unwrap();
+
+    let req = register("call-1", 5, Some(0), vec![contact("desk.example.com")]);
+    assert_eq!(Ok(500), registrar.process(&req, now).unwrap().status_code());
+    assert_eq!(1, registrar.lookup(&aor(), now).len());
+}
+
+#[test]
+fn interval_too_brief() {
+    let mut registrar = MemoryRegistrar::new(HashMap::new())
+        .min_expires(300)
+        .max_expires(7200);
+    let now = Instant::now();
+
+    let req = register("call-1", 1, Some(60), vec![contact("desk.example.com")]);
+    let res = registrar.process(&req, now).unwrap();
+    assert_eq!(Ok(423), res.status_code());
+    assert!(res
+        .headers()
+        .iter()
+        .any(|header| header == &Header::MinExpires(300)));
+    assert!(registrar.lookup(&aor(), now).is_empty());
+
+    let req = register("call-1", 2, Some(86400), vec![contact("desk.example.com")]);
+    let res = registrar.process(&req, now).unwrap();
+    assert_eq!(
+        vec![("desk.example.com".to_string(), Some("7200".to_string()))],
+        contacts(&res)
+    );
+}
+
+#[test]
+fn wildcard_removal() {
+    let mut registrar = MemoryRegistrar::new(HashMap::new());
+    let now = Instant::now();
+    let req = register(
+        "call-1",
+        1,
+        None,
+        vec![contact("desk.example.com"), contact("phone.example.com")],
+    );
+    registrar.process(&req, now).unwrap();
+
+    let req = register("call-2", 1, Some(60), vec![ContactHeader::wildcard()]);
+    assert_eq!(Ok(400), registrar.process(&req, now).unwrap().status_code());
+
+    let req = register("call-2", 1, Some(0), vec![ContactHeader::wildcard()]);
+    let res = registrar.process(&req, now).unwrap();
+    assert_eq!(Ok(200), res.status_code());
+    assert!(contacts(&res).is_empty());
+    assert!(registrar.location().is_empty());
+}
+
+#[test]
+fn lookup_orders_by_q_and_drops_expired() {
+    let mut registrar = MemoryRegistrar::new(HashMap::new());
+    let now = Instant::now();
+    let req = register(
+        "call-1",
+        1,
+        None,
+        vec![
+            contact("desk.example.com").param("q", Some("0.5")),
+            contact("phone.example.com").param("q", Some("0.9")),
+            contact("laptop.example.com").param("expires", Some("60")),
+        ],
+    );
+    registrar.process(&req, now).unwrap();
+
+    let hosts = |at| {
+        registrar
+            .lookup(&aor(), at)
+            .iter()
+            .map(|binding| binding.contact.uri.host())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        vec![
+            "laptop.example.com",
+            "phone.example.com",
+            "desk.example.com"
+        ],
+        hosts(now)
+    );
+    assert_eq!(
+        vec!["phone.example.com", "desk.example.com"],
+        hosts(now + Duration::from_secs(60))
+    );
+}
+
+#[test]
+fn address_of_record_is_canonical() {
+    let uri = Uri::sip(domain!("example.com"))
+        .auth(uri_auth!("alice").password("secret"))
+        .parameter(UriParam::Transport(Transport::Tcp));
+    assert_eq!(
+        "sip:alice@example.com",
+        libsip::server::address_of_record(&uri)
+    );
+}
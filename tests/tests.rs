@@ -1,3 +1,10 @@
 mod core;
 mod headers;
 mod uri;
+mod transaction;
+mod dialog;
+mod routing;
+mod auth;
+mod server;
+mod client;
+mod transport;
@@ -0,0 +1,161 @@
+use super::{request, response};
+
+use libsip::{
+    transaction::{ClientInviteTransaction, ClientNonInviteTransaction, TimerConfig},
+    *,
+};
+
+use std::time::{Duration, Instant};
+
+#[test]
+fn invite_retransmits_until_timeout() {
+    let start = Instant::now();
+    let invite = request(Method::Invite, Transport::Udp);
+    let mut tx =
+        ClientInviteTransaction::new(invite.clone(), TimerConfig::default(), start).unwrap();
+    assert_eq!(tx.state(), TransactionState::Calling);
+    assert_eq!(tx.next_timeout(), Some(start + Duration::from_millis(500)));
+
+    // Timer A doubles: 0.5s, 1.5s, 3.5s, 7.5s, 15.5s, 31.5s
+    let mut sent = 0;
+    for millis in &[500, 1500, 3500, 7500, 15500, 31500] {
+        let events = tx.on_timer(start + Duration::from_millis(*millis));
+        assert_eq!(events, vec![TransactionEvent::Send(invite.clone())]);
+        sent += 1;
+    }
+    assert_eq!(sent, 6);
+    assert!(tx.on_timer(start + Duration::from_millis(31999)).is_empty());
+
+    let events = tx.on_timer(start + Duration::from_secs(32));
+    let key = tx.key().clone();
+    assert_eq!(
+        events,
+        vec![
+            TransactionEvent::Timeout(key.clone()),
+            TransactionEvent::Terminated(key)
+        ]
+    );
+    assert_eq!(tx.state(), TransactionState::Terminated);
+}
+
+#[test]
+fn invite_reliable_has_no_timer_a() {
+    let start = Instant::now();
+    let invite = request(Method::Invite, Transport::Tcp);
+    let mut tx = ClientInviteTransaction::new(invite, TimerConfig::default(), start).unwrap();
+    assert_eq!(tx.next_timeout(), Some(start + Duration::from_secs(32)));
+    assert!(tx.on_timer(start + Duration::from_secs(10)).is_empty());
+}
+
+#[test]
+fn invite_non_2xx_is_acked_and_absorbed() {
+    let start = Instant::now();
+    let invite = request(Method::Invite, Transport::Udp);
+    let mut tx =
+        ClientInviteTransaction::new(invite.clone(), TimerConfig::default(), start).unwrap();
+
+    let ringing = response(&invite, 180);
+    assert_eq!(
+        tx.on_response(ringing.clone(), start),
+        vec![TransactionEvent::Deliver(ringing)]
+    );
+    assert_eq!(tx.state(), TransactionState::Proceeding);
+    // Timer A is stopped once a provisional response arrives.
+    assert_eq!(tx.next_timeout(), Some(start + Duration::from_secs(32)));
+
+    let busy = response(&invite, 486);
+    let events = tx.on_response(busy.clone(), start);
+    let ack = transaction::non_2xx_ack(&invite, &busy).unwrap();
+    assert_eq!(
+        events,
+        vec![
+            TransactionEvent::Send(ack.clone()),
+            TransactionEvent::Deliver(busy.clone())
+        ]
+    );
+    assert_eq!(tx.state(), TransactionState::Completed);
+    assert_eq!(ack.method(), Ok(Method::Ack));
+    assert_eq!(ack.via_header_branch(), invite.via_header_branch());
+    assert_eq!(ack.cseq(), Ok((314159, Method::Ack)));
+    assert_eq!(ack.to_header_tag(), Ok(&"a6c85cf".to_string()));
+
+    // Retransmissions of the final response only trigger a new ACK.
+    assert_eq!(
+        tx.on_response(busy, start),
+        vec![TransactionEvent::Send(ack)]
+    );
+
+    let events = tx.on_timer(start + Duration::from_secs(32));
+    assert_eq!(events, vec![TransactionEvent::Terminated(tx.key().clone())]);
+}
+
+#[test]
+fn invite_2xx_terminates() {
+    let start = Instant::now();
+    let invite = request(Method::Invite, Transport::Udp);
+    let mut tx =
+        ClientInviteTransaction::new(invite.clone(), TimerConfig::default(), start).unwrap();
+    let ok = response(&invite, 200);
+    assert_eq!(
+        tx.on_response(ok.clone(), start),
+        vec![
+            TransactionEvent::Deliver(ok),
+            TransactionEvent::Terminated(tx.key().clone())
+        ]
+    );
+    assert_eq!(tx.next_timeout(), None);
+}
+
+#[test]
+fn non_invite_retransmits_capped_at_t2() {
+    let start = Instant::now();
+    let register = request(Method::Register, Transport::Udp);
+    let mut tx =
+        ClientNonInviteTransaction::new(register.clone(), TimerConfig::default(), start).unwrap();
+    assert_eq!(tx.state(), TransactionState::Trying);
+
+    // Timer E: 0.5s, 1s, 2s, 4s, 4s
+    for millis in &[500, 1500, 3500, 7500, 11500] {
+        assert_eq!(
+            tx.on_timer(start + Duration::from_millis(*millis)),
+            vec![TransactionEvent::Send(register.clone())]
+        );
+    }
+    assert_eq!(
+        tx.next_timeout(),
+        Some(start + Duration::from_millis(15500))
+    );
+}
+
+#[test]
+fn non_invite_completed_waits_timer_k() {
+    let start = Instant::now();
+    let register = request(Method::Register, Transport::Udp);
+    let mut tx =
+        ClientNonInviteTransaction::new(register.clone(), TimerConfig::default(), start).unwrap();
+
+    let trying = response(&register, 100);
+    assert_eq!(
+        tx.on_response(trying.clone(), start),
+        vec![TransactionEvent::Deliver(trying)]
+    );
+    assert_eq!(tx.state(), TransactionState::Proceeding);
+
+    // In Proceeding the request is retransmitted every T2.
+    tx.on_timer(start + Duration::from_millis(500));
+    assert_eq!(tx.next_timeout(), Some(start + Duration::from_millis(4500)));
+
+    let ok = response(&register, 200);
+    assert_eq!(
+        tx.on_response(ok.clone(), start + Duration::from_secs(1)),
+        vec![TransactionEvent::Deliver(ok.clone())]
+    );
+    assert_eq!(tx.state(), TransactionState::Completed);
+    assert!(tx
+        .on_response(ok, start + Duration::from_secs(2))
+        .is_empty());
+    assert_eq!(
+        tx.on_timer(start + Duration::from_secs(6)),
+        vec![TransactionEvent::Terminated(tx.key().clone())]
+    );
+}
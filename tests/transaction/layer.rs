@@ -0,0 +1,114 @@
+use super::{request, response};
+
+use libsip::{transaction::TimerConfig, *};
+
+use std::time::{Duration, Instant};
+
+#[test]
+fn client_transaction_matching() {
+    let start = Instant::now();
+    let mut layer = TransactionLayer::new(TimerConfig::default());
+    let register = request(Method::Register, Transport::Udp);
+    assert_eq!(
+        layer.send_request(register.clone(), start).unwrap(),
+        vec![TransactionEvent::Send(register.clone())]
+    );
+    assert_eq!(layer.len(), 1);
+    assert_eq!(
+        layer.next_timeout(),
+        Some(start + Duration::from_millis(500))
+    );
+
+    // A response with a different CSeq method belongs to another transaction.
+    let mut stray = response(&register, 200);
+    *stray.cseq_mut().unwrap().1 = Method::Options;
+    assert_eq!(
+        layer.receive(stray.clone(), start).unwrap(),
+        vec![TransactionEvent::Deliver(stray)]
+    );
+    assert_eq!(
+        layer
+            .get(&TransactionKey::new("z9hG4bK776asdhds", Method::Register))
+            .map(Transaction::state),
+        Some(TransactionState::Trying)
+    );
+
+    let ok = response(&register, 200);
+    assert_eq!(
+        layer.receive(ok.clone(), start).unwrap(),
+        vec![TransactionEvent::Deliver(ok)]
+    );
+    let events = layer.on_timer(start + Duration::from_secs(5));
+    assert_eq!(events.len(), 1);
+    assert!(layer.is_empty());
+}
+
+#[test]
+fn server_transaction_creation() {
+    let start = Instant::now();
+    let mut layer = TransactionLayer::new(TimerConfig::default());
+    let invite = request(Method::Invite, Transport::Udp);
+    let events = layer.receive(invite.clone(), start).unwrap();
+    assert_eq!(events.len(), 2);
+    if let TransactionEvent::Send(trying) = &events[0] {
+        assert_eq!(trying.status_code(), Ok(100));
+    } else {
+        panic!("expected a 100 Trying response");
+    }
+    assert_eq!(events[1], TransactionEvent::Deliver(invite.clone()));
+
+    // The retransmitted INVITE is absorbed and answered with the 100 Trying.
+    let events = layer.receive(invite.clone(), start).unwrap();
+    assert_eq!(events.len(), 1);
+
+    let ok = response(&invite, 200);
+    layer.send_response(ok, start).unwrap();
+    assert!(layer.is_empty());
+
+    // The ACK for a 2xx is passed to the transaction user.
+    let ack = request(Method::Ack, Transport::Udp);
+    assert_eq!(
+        layer.receive(ack.clone(), start).unwrap(),
+        vec![TransactionEvent::Deliver(ack)]
+    );
+    assert!(layer.send_response(response(&invite, 200), start).is_err());
+}
+
+#[test]
+fn branch_collisions() {
+    let start = Instant::now();
+    let mut layer = TransactionLayer::new(TimerConfig::default());
+    let options = request(Method::Options, Transport::Udp);
+    layer.send_request(options.clone(), start).unwrap();
+
+    // A request reusing the branch of one we sent starts a server
+    // transaction instead of replacing the client transaction.
+    assert_eq!(
+        layer.receive(options.clone(), start).unwrap(),
+        vec![TransactionEvent::Deliver(options.clone())]
+    );
+    assert_eq!(layer.len(), 2);
+
+    // The same branch from another sent-by is another transaction.
+    let mut other = options.clone();
+    other.via_header_mut().unwrap().sent_by = ip_domain!(192, 168, 1, 3, 5060);
+    assert_eq!(
+        layer.receive(other.clone(), start).unwrap(),
+        vec![TransactionEvent::Deliver(other.clone())]
+    );
+    assert_eq!(layer.len(), 3);
+    assert!(layer.send_response(response(&other, 200), start).is_ok());
+    assert!(layer.send_response(response(&options, 200), start).is_ok());
+
+    let ok = response(&options, 200);
+    assert_eq!(
+        layer.receive(ok.clone(), start).unwrap(),
+        vec![TransactionEvent::Deliver(ok)]
+    );
+    assert_eq!(
+        layer
+            .get(&TransactionKey::new("z9hG4bK776asdhds", Method::Options))
+            .map(Transaction::is_client),
+        Some(true)
+    );
+}
@@ -0,0 +1,47 @@
+mod client;
+mod layer;
+mod server;
+
+use libsip::*;
+
+/// Build a request with the headers required to create a transaction.
+pub fn request(method: Method, transport: Transport) -> SipMessage {
+    let uri = Uri::sip(domain!("example.com")).auth(uri_auth!("bob"));
+    let via = ViaHeader {
+        branch: Some("z9hG4bK776asdhds".into()),
+        ..ViaHeader::new(ip_domain!(192, 168, 1, 2, 5060), transport)
+    };
+    RequestGenerator::new()
+        .method(method)
+        .uri(uri.clone())
+        .header(Header::Via(via))
+        .header(Header::From(
+            NamedHeader::new(Uri::sip(domain!("example.com")).auth(uri_auth!("alice")))
+                .param("tag", Some("1928301774")),
+        ))
+        .header(Header::To(NamedHeader::new(uri)))
+        .header(Header::CallId("a84b4c76e66710".into()))
+        .header(Header::CSeq(314159, method))
+        .header(Header::ContentLength(0))
+        .build()
+        .unwrap()
+}
+
+/// Build a response to `request` with the given status code.
+pub fn response(request: &SipMessage, code: u32) -> SipMessage {
+    let mut res = ResponseGenerator::new().code(code);
+    for header in request.headers().iter() {
+        match header {
+            Header::To(to) if code > 100 => {
+                res = res.header(Header::To(to.clone().param("tag", Some("a6c85cf"))))
+            },
+            Header::Via(_)
+            | Header::From(_)
+            | Header::To(_)
+            | Header::CallId(_)
+            | Header::CSeq(..) => res = res.header(header.clone()),
+            _ => {},
+        }
+    }
+    res.build().unwrap()
+}
@@ -0,0 +1,112 @@
+use super::{request, response};
+
+use libsip::{
+    transaction::{ServerInviteTransaction, ServerNonInviteTransaction, TimerConfig},
+    *,
+};
+
+use std::time::{Duration, Instant};
+
+#[test]
+fn invite_final_response_retransmitted_until_ack() {
+    let start = Instant::now();
+    let invite = request(Method::Invite, Transport::Udp);
+    let mut tx =
+        ServerInviteTransaction::new(invite.clone(), TimerConfig::default(), start).unwrap();
+    assert_eq!(tx.state(), TransactionState::Proceeding);
+
+    let ringing = response(&invite, 180);
+    tx.send_response(ringing.clone(), start).unwrap();
+    // A retransmitted INVITE gets the latest provisional response.
+    assert_eq!(
+        tx.on_request(invite.clone(), start),
+        vec![TransactionEvent::Send(ringing)]
+    );
+
+    let busy = response(&invite, 486);
+    assert_eq!(
+        tx.send_response(busy.clone(), start).unwrap(),
+        vec![TransactionEvent::Send(busy.clone())]
+    );
+    assert_eq!(tx.state(), TransactionState::Completed);
+    assert!(tx.send_response(busy.clone(), start).is_err());
+
+    // Timer G: 0.5s, 1s, 2s, 4s, 4s
+    for millis in &[500, 1500, 3500, 7500, 11500] {
+        assert_eq!(
+            tx.on_timer(start + Duration::from_millis(*millis)),
+            vec![TransactionEvent::Send(busy.clone())]
+        );
+    }
+
+    let ack = transaction::non_2xx_ack(&invite, &busy).unwrap();
+    assert!(tx
+        .on_request(ack.clone(), start + Duration::from_secs(12))
+        .is_empty());
+    assert_eq!(tx.state(), TransactionState::Confirmed);
+    // Further ACKs are absorbed.
+    assert!(tx
+        .on_request(ack, start + Duration::from_secs(13))
+        .is_empty());
+    assert_eq!(
+        tx.on_timer(start + Duration::from_secs(17)),
+        vec![TransactionEvent::Terminated(tx.key().clone())]
+    );
+}
+
+#[test]
+fn invite_timer_h() {
+    let start = Instant::now();
+    let invite = request(Method::Invite, Transport::Tcp);
+    let mut tx =
+        ServerInviteTransaction::new(invite.clone(), TimerConfig::default(), start).unwrap();
+    tx.send_response(response(&invite, 603), start).unwrap();
+    assert_eq!(tx.next_timeout(), Some(start + Duration::from_secs(32)));
+    let key = tx.key().clone();
+    assert_eq!(
+        tx.on_timer(start + Duration::from_secs(32)),
+        vec![
+            TransactionEvent::Timeout(key.clone()),
+            TransactionEvent::Terminated(key)
+        ]
+    );
+}
+
+#[test]
+fn invite_2xx_terminates() {
+    let start = Instant::now();
+    let invite = request(Method::Invite, Transport::Udp);
+    let mut tx =
+        ServerInviteTransaction::new(invite.clone(), TimerConfig::default(), start).unwrap();
+    let ok = response(&invite, 200);
+    assert_eq!(
+        tx.send_response(ok.clone(), start).unwrap(),
+        vec![
+            TransactionEvent::Send(ok),
+            TransactionEvent::Terminated(tx.key().clone())
+        ]
+    );
+}
+
+#[test]
+fn non_invite_absorbs_retransmissions() {
+    let start = Instant::now();
+    let message = request(Method::Message, Transport::Udp);
+    let mut tx =
+        ServerNonInviteTransaction::new(message.clone(), TimerConfig::default(), start).unwrap();
+    assert_eq!(tx.state(), TransactionState::Trying);
+    assert!(tx.on_request(message.clone(), start).is_empty());
+
+    let ok = response(&message, 200);
+    tx.send_response(ok.clone(), start).unwrap();
+    assert_eq!(tx.state(), TransactionState::Completed);
+    assert_eq!(
+        tx.on_request(message, start + Duration::from_secs(1)),
+        vec![TransactionEvent::Send(ok)]
+    );
+    assert!(tx.on_timer(start + Duration::from_secs(31)).is_empty());
+    assert_eq!(
+        tx.on_timer(start + Duration::from_secs(32)),
+        vec![TransactionEvent::Terminated(tx.key().clone())]
+    );
+}
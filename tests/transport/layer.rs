@@ -0,0 +1,223 @@
+use crate::transaction::{request, response};
+
+use libsip::{transaction::TimerConfig, *};
+
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+fn client_addr() -> SocketAddr {
+    "192.168.1.2:5060".parse().unwrap()
+}
+
+fn server_addr() -> SocketAddr {
+    "203.0.113.1:5060".parse().unwrap()
+}
+
+fn messages(events: Vec<TransportEvent>) -> Vec<SipMessage> {
+    events
+        .into_iter()
+        .filter_map(|event| match event {
+            TransportEvent::Message(_, msg) => Some(msg),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn udp_round_trip() {
+    let now = Instant::now();
+    let mut network = Loopback::new();
+    let mut client = TransportLayer::new();
+    let mut server = TransportLayer::new();
+
+    let mut invite = request(Method::Invite, Transport::Udp);
+    invite.via_header_mut().unwrap().rport = Some(None);
+    let events = client.send(&invite, Transport::Udp, server_addr(), now);
+    assert!(network.deliver(client_addr(), events).is_empty());
+    assert!(client.connections().is_empty());
+
+    // The client is behind a NAT, the request arrives from another port.
+    let (_, data) = network.recv(server_addr()).unwrap();
+    let nat = "198.51.100.7:40000".parse().unwrap();
+    let key = ConnectionKey::new(Transport::Udp, nat);
+    let received = messages(server.receive(key, &data, now)).remove(0);
+    let via = received.via_header().unwrap();
+    assert_eq!(Some(ip_domain!(198, 51, 100, 7)), via.received);
+    assert_eq!(Some(Some(40000)), via.rport);
+
+    let events = server
+        .send_response(&response(&received, 180), now)
+        .unwrap();
+    match &events[..] {
+        [TransportEvent::Send(key, _)] => assert_eq!(&ConnectionKey::new(Transport::Udp, nat), key),
+        other => panic!("unexpected events: {:?}", other),
+    }
+
+    // Garbage datagrams are dropped.
+    assert!(server.receive(key, b"not sip", now).is_empty());
+}
+
+#[test]
+fn tcp_connection_reuse() {
+    let now = Instant::now();
+    let mut network = Loopback::new();
+    let mut client = TransportLayer::new();
+    let mut server = TransportLayer::new();
+
+    let register = request(Method::Register, Transport::Tcp);
+    let server_key = ConnectionKey::new(Transport::Tcp, server_addr());
+    let events = client.send(&register, Transport::Tcp, server_addr(), now);
+    assert_eq!(TransportEvent::Connect(server_key), events[0]);
+    network.deliver(client_addr(), events);
+    let events = client.send(&register, Transport::Tcp, server_addr(), now);
+    assert_eq!(1, events.len());
+    network.deliver(client_addr(), events);
+    assert_eq!(vec![&server_key], client.connections());
+
+    // Both requests are framed from the stream, even when split up.
+    let (key, first) = network.recv(server_addr()).unwrap();
+    let (_, second) = network.recv(server_addr()).unwrap();
+    let (head, tail) = second.split_at(20);
+    assert_eq!(1, server.receive(key, &first, now).len());
+    assert!(server.receive(key, head, now).is_empty());
+    let received = messages(server.receive(key, tail, now)).remove(0);
+    assert!(server.is_connected(&key));
+
+    // The response goes back over the connection the request arrived on.
+    let events = server
+        .send_response(&response(&received, 200), now)
+        .unwrap();
+    match &events[..] {
+        [TransportEvent::Send(sent, _)] => assert_eq!(&key, sent),
+        other => panic!("unexpected events: {:?}", other),
+    }
+    let remaining = network.deliver(server_addr(), events);
+    assert!(remaining.is_empty());
+    let (key, data) = network.recv(client_addr()).unwrap();
+    assert_eq!(server_key, key);
+    let received = messages(client.receive(key, &data, now)).remove(0);
+    assert_eq!(Ok(200), received.status_code());
+
+    // Once the connection is gone the response is sent to the Via address.
+    server.closed(&ConnectionKey::new(Transport::Tcp, client_addr()));
+    let events = server
+        .send_response(&response(&register, 200), now)
+        .unwrap();
+    assert_eq!(
+        TransportEvent::Connect(ConnectionKey::new(
+            Transport::Tcp,
+            "192.168.1.2:5060".parse().unwrap()
+        )),
+        events[0]
+    );
+}
+
+#[test]
+fn large_udp_request_uses_tcp() {
+    let now = Instant::now();
+    let mut layer = TransportLayer::new().mtu(600);
+    let mut invite = request(Method::Invite, Transport::Udp);
+    *invite.body_mut() = vec![b'a'; 500];
+
+    let transport = layer.select_transport(&mut invite, Transport::Udp);
+    assert_eq!(Transport::Tcp, transport);
+    assert_eq!(Transport::Tcp, invite.via_header().unwrap().transport);
+
+    // The transaction sees the reliable transport and doesn't retransmit.
+    let config = TimerConfig::default();
+    let mut transactions = TransactionLayer::new(config);
+    transactions.send_request(invite.clone(), now).unwrap();
+    assert_eq!(Some(now + config.t1 * 64), transactions.next_timeout());
+
+    let events = layer.send(&invite, transport, server_addr(), now);
+    let key = ConnectionKey::new(Transport::Tcp, server_addr());
+    assert_eq!(TransportEvent::Connect(key), events[0]);
+    match &events[1] {
+        TransportEvent::Send(sent, data) => {
+            assert_eq!(&key, sent);
+            assert!(String::from_utf8_lossy(data).contains("Via: SIP/2.0/TCP"));
+        },
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    // Small requests and responses are never moved.
+    let mut small = request(Method::Invite, Transport::Udp);
+    assert_eq!(Transport::Udp, layer.select_transport(&mut small, Transport::Udp));
+    let mut ok = response(&invite, 200);
+    *ok.body_mut() = vec![b'a'; 500];
+    assert_eq!(Transport::Udp, layer.select_transport(&mut ok, Transport::Udp));
+}
+
+#[test]
+fn forget_answered_requests() {
+    let now = Instant::now();
+    let mut layer = TransportLayer::new();
+    let key = ConnectionKey::new(Transport::Tcp, client_addr());
+    let register = format!("{}", request(Method::Register, Transport::Tcp));
+    let received = messages(layer.receive(key, register.as_bytes(), now)).remove(0);
+    // The request is remembered until the final response, or 64*T1.
+    assert_eq!(Some(now + Duration::from_secs(32)), layer.next_timeout());
+    layer.send_response(&response(&received, 100), now).unwrap();
+    assert_eq!(Some(now + Duration::from_secs(32)), layer.next_timeout());
+    layer.send_response(&response(&received, 200), now).unwrap();
+    assert_eq!(Some(now + Duration::from_secs(300)), layer.next_timeout());
+
+    // An unanswered request expires.
+    let options = format!("{}", request(Method::Options, Transport::Tcp));
+    layer.receive(key, options.as_bytes(), now);
+    assert!(layer.poll(now + Duration::from_secs(32)).is_empty());
+    assert_eq!(Some(now + Duration::from_secs(300)), layer.next_timeout());
+
+    // ACKs are never answered.
+    let ack = format!("{}", request(Method::Ack, Transport::Tcp));
+    layer.receive(key, ack.as_bytes(), now);
+    assert_eq!(Some(now + Duration::from_secs(300)), layer.next_timeout());
+}
+
+#[test]
+fn keep_alive_and_idle_timeout() {
+    let now = Instant::now();
+    let mut layer = TransportLayer::new().idle_timeout(Duration::from_secs(60));
+    let key = ConnectionKey::new(Transport::Tls, client_addr());
+
+    assert_eq!(
+        vec![TransportEvent::Send(key, KeepAlive::PONG.to_vec())],
+        layer.receive(key, KeepAlive::PING, now)
+    );
+    // A ping split over two reads is still a ping.
+    assert!(layer.receive(key, b"\r\n", now).is_empty());
+    assert_eq!(
+        vec![TransportEvent::Send(key, KeepAlive::PONG.to_vec())],
+        layer.receive(key, b"\r\n", now)
+    );
+    assert_eq!(Some(now + Duration::from_secs(60)), layer.next_timeout());
+
+    // Pongs arrive on the connections we opened.
+    let server = ConnectionKey::new(Transport::Tls, server_addr());
+    let register = request(Method::Register, Transport::Tls);
+    layer.send(&register, Transport::Tls, server_addr(), now);
+    assert_eq!(
+        vec![TransportEvent::Pong(server)],
+        layer.receive(server, KeepAlive::PONG, now)
+    );
+    layer.closed(&server);
+
+    let later = now + Duration::from_secs(30);
+    layer.receive(key, KeepAlive::PING, later);
+    assert!(layer.poll(now + Duration::from_secs(60)).is_empty());
+    assert_eq!(
+        vec![TransportEvent::Close(key)],
+        layer.poll(later + Duration::from_secs(60))
+    );
+    assert!(!layer.is_connected(&key));
+    assert_eq!(None, layer.next_timeout());
+
+    // A stream that can't be framed is closed.
+    assert_eq!(
+        vec![TransportEvent::Close(key)],
+        layer.receive(key, b"garbage\r\n\r\n", now)
+    );
+    assert!(!layer.is_connected(&key));
+}
@@ -0,0 +1,40 @@
+mod layer;
+mod resolver;
+
+use libsip::{core::parse_transport, *};
+
+use nom::error::VerboseError;
+
+#[test]
+fn read_and_write() {
+    let transports = vec![
+        ("UDP", Transport::Udp),
+        ("TCP", Transport::Tcp),
+        ("TLS", Transport::Tls),
+        ("SCTP", Transport::Sctp),
+        ("WS", Transport::Ws),
+        ("WSS", Transport::Wss),
+    ];
+    for (text, transport) in transports {
+        assert_eq!(text, format!("{}", transport));
+        let remains = vec![];
+        assert_eq!(
+            Ok((remains.as_ref(), transport)),
+            parse_transport::<VerboseError<&[u8]>>(text.to_lowercase().as_bytes())
+        );
+    }
+}
+
+#[test]
+fn properties() {
+    assert!(!Transport::Udp.is_reliable());
+    assert!(Transport::Sctp.is_reliable());
+    assert!(Transport::Wss.is_reliable());
+    assert!(Transport::Tls.is_stream());
+    assert!(!Transport::Ws.is_stream());
+    assert!(Transport::Wss.is_secure());
+    assert!(!Transport::Tcp.is_secure());
+    assert_eq!(5061, Transport::Tls.default_port());
+    assert_eq!(80, Transport::Ws.default_port());
+    assert_eq!(443, Transport::Wss.default_port());
+}
@@ -0,0 +1,200 @@
+use libsip::*;
+
+use nom::error::VerboseError;
+
+use std::net::{IpAddr, SocketAddr};
+
+fn uri(input: &str) -> Uri {
+    parse_uri::<VerboseError<&[u8]>>(input.as_bytes())
+        .unwrap()
+        .1
+}
+
+fn ip(input: &str) -> IpAddr {
+    input.parse().unwrap()
+}
+
+fn key(transport: Transport, addr: &str) -> ConnectionKey {
+    ConnectionKey::new(transport, addr.parse::<SocketAddr>().unwrap())
+}
+
+fn naptr(order: u16, service: &str, replacement: &str) -> NaptrRecord {
+    NaptrRecord {
+        order,
+        preference: 50,
+        flags: "s".into(),
+        service: service.into(),
+        replacement: replacement.into(),
+    }
+}
+
+fn srv(priority: u16, weight: u16, port: u16, target: &str) -> SrvRecord {
+    SrvRecord {
+        priority,
+        weight,
+        port,
+        target: target.into(),
+    }
+}
+
+fn zone() -> StaticZone {
+    StaticZone::new()
+        .naptr("example.com", naptr(90, "SIP+D2U", "_sip._udp.example.com"))
+        .naptr(
+            "example.com",
+            naptr(50, "SIPS+D2T", "_sips._tcp.example.com"),
+        )
+        .naptr("example.com", naptr(60, "SIP+D2T", "_sip._tcp.example.com"))
+        .naptr("example.com", naptr(10, "SIP+D2X", "_sip._x.example.com"))
+        .srv("_sips._tcp.example.com", srv(0, 0, 5061, "tls.example.com"))
+        .srv(
+            "_sip._tcp.example.com",
+            srv(10, 0, 5060, "backup.example.com"),
+        )
+        .srv("_sip._tcp.example.com", srv(0, 0, 5060, "tcp.example.com"))
+        .srv("_sip._udp.example.com", srv(0, 0, 5060, "tcp.example.com"))
+        .host("tls.example.com", ip("192.0.2.1"))
+        .host("tcp.example.com", ip("192.0.2.2"))
+        .host("tcp.example.com", ip("2001:db8::2"))
+        .host("backup.example.com", ip("192.0.2.3"))
+        .host("example.com", ip("192.0.2.10"))
+}
+
+#[test]
+fn naptr_srv_and_hosts() {
+    let resolver = Resolver::new(zone());
+    assert_eq!(
+        vec![
+            key(Transport::Tls, "192.0.2.1:5061"),
+            key(Transport::Tcp, "192.0.2.2:5060"),
+            key(Transport::Tcp, "[2001:db8::2]:5060"),
+            key(Transport::Tcp, "192.0.2.3:5060"),
+            key(Transport::Udp, "192.0.2.2:5060"),
+            key(Transport::Udp, "[2001:db8::2]:5060"),
+        ],
+        resolver.resolve(&uri("sip:bob@example.com"))
+    );
+
+    // Only the secure services are used for a sips uri.
+    assert_eq!(
+        vec![key(Transport::Tls, "192.0.2.1:5061")],
+        resolver.resolve(&uri("sips:bob@example.com"))
+    );
+
+    // Services the client doesn't support are skipped.
+    let resolver = Resolver::new(zone()).transports(vec![Transport::Udp]);
+    assert_eq!(
+        vec![
+            key(Transport::Udp, "192.0.2.2:5060"),
+            key(Transport::Udp, "[2001:db8::2]:5060"),
+        ],
+        resolver.resolve(&uri("sip:bob@example.com"))
+    );
+}
+
+#[test]
+fn explicit_parameters() {
+    let resolver = Resolver::new(zone());
+    assert_eq!(
+        vec![key(Transport::Udp, "192.0.2.10:5080")],
+        resolver.resolve(&uri("sip:bob@example.com:5080"))
+    );
+    assert_eq!(
+        vec![
+            key(Transport::Tcp, "192.0.2.2:5060"),
+            key(Transport::Tcp, "[2001:db8::2]:5060"),
+            key(Transport::Tcp, "192.0.2.3:5060"),
+        ],
+        resolver.resolve(&uri("sip:bob@example.com;transport=tcp"))
+    );
+    assert_eq!(
+        vec![key(Transport::Tls, "192.0.2.1:5061")],
+        resolver.resolve(&uri("sips:bob@example.com;transport=tcp"))
+    );
+    assert_eq!(
+        vec![key(Transport::Sctp, "192.0.2.10:5060")],
+        resolver.resolve(&uri("sip:bob@example.com;transport=sctp"))
+    );
+    assert_eq!(
+        vec![key(Transport::Tcp, "198.51.100.1:5060")],
+        resolver.resolve(&uri("sip:bob@example.com;maddr=198.51.100.1;transport=tcp"))
+    );
+    assert_eq!(
+        vec![key(Transport::Udp, "10.0.0.1:5070")],
+        resolver.resolve(&uri("sip:bob@example.com:5070;maddr=10.0.0.1"))
+    );
+    assert_eq!(
+        vec![key(Transport::Udp, "10.0.0.1:5080")],
+        resolver.resolve(&uri("sip:bob@example.com:5070;maddr=10.0.0.1:5080"))
+    );
+    assert_eq!(
+        vec![key(Transport::Tls, "198.51.100.1:5061")],
+        resolver.resolve(&uri("sips:bob@198.51.100.1"))
+    );
+    assert!(resolver
+        .resolve(&uri("sips:bob@example.com;transport=udp"))
+        .is_empty());
+}
+
+#[test]
+fn srv_without_naptr() {
+    let dns = StaticZone::new()
+        .srv("_sip._tcp.example.org", srv(0, 0, 5070, "sip.example.org."))
+        .srv("_sip._tcp.example.org", srv(0, 0, 5070, "."))
+        .host("sip.example.org", ip("203.0.113.5"))
+        .host("example.org", ip("203.0.113.9"));
+    let resolver = Resolver::new(dns);
+    assert_eq!(
+        vec![key(Transport::Tcp, "203.0.113.5:5070")],
+        resolver.resolve(&uri("sip:bob@example.org"))
+    );
+
+    // Without any SRV records the host is used with UDP.
+    assert_eq!(
+        vec![key(Transport::Udp, "203.0.113.9:5060")],
+        resolver.resolve(&uri("sip:bob@example.org;transport=udp"))
+    );
+    assert!(resolver.resolve(&uri("sip:bob@unknown.org")).is_empty());
+}
+
+#[test]
+fn srv_weights() {
+    let dns = StaticZone::new()
+        .srv(
+            "_sip._udp.example.net",
+            srv(1, 0, 5060, "never.example.net"),
+        )
+        .srv("_sip._udp.example.net", srv(0, 100, 5060, "a.example.net"))
+        .srv("_sip._udp.example.net", srv(0, 0, 5060, "b.example.net"))
+        .host("a.example.net", ip("192.0.2.1"))
+        .host("b.example.net", ip("192.0.2.2"))
+        .host("never.example.net", ip("192.0.2.3"));
+    let resolver = Resolver::new(dns).transports(vec![Transport::Udp]);
+    let mut first_a = 0;
+    for _ in 0..100 {
+        let targets = resolver.resolve(&uri("sip:example.net"));
+        assert_eq!(3, targets.len());
+        assert_eq!(key(Transport::Udp, "192.0.2.3:5060"), targets[2]);
+        if targets[0] == key(Transport::Udp, "192.0.2.1:5060") {
+            first_a += 1;
+        }
+    }
+    assert!(first_a > 90);
+}
+
+#[test]
+fn tel_uris() {
+    let dns = zone().host("5550100", ip("203.0.113.7"));
+    let resolver = Resolver::new(dns);
+    assert!(resolver
+        .resolve(&uri("tel:5550100;phone-context=example.com"))
+        .is_empty());
+    assert!(resolver.resolve(&uri("tel:+1-555-0100")).is_empty());
+
+    // A gateway is reached by turning the number into a sip uri.
+    let sip = TelUri::global("+1-555-0100").to_sip_uri(domain!("example.com"));
+    assert_eq!(
+        Some(&key(Transport::Tls, "192.0.2.1:5061")),
+        resolver.resolve(&sip).first()
+    );
+}
@@ -1,7 +1,7 @@
 use libsip::{uri::parse_domain, *};
 use nom::error::VerboseError;
 
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
 
 #[test]
 fn read_domain() {
@@ -20,6 +20,19 @@ fn read_domain() {
     );
 }
 
+#[test]
+fn reject_invalid_hostnames() {
+    for input in &["a..b", "-foo.com", "foo-.com", "foo.-com", ".foo.com", "foo.1com", ""] {
+        assert!(
+            parse_domain::<VerboseError<&[u8]>>(input.as_bytes()).is_err(),
+            "{} should not parse",
+            input
+        );
+    }
+    let (_, domain) = parse_domain::<VerboseError<&[u8]>>(b"a-1.example.com. ").unwrap();
+    assert_eq!(domain, domain!("a-1.example.com."));
+}
+
 #[test]
 fn read_ip_address() {
     let remains = vec![b' '];
@@ -54,3 +67,32 @@ fn write_ip_address() {
     let domain = Domain::Ipv4(Ipv4Addr::new(10, 1, 10, 1), Some(8080));
     assert_eq!("10.1.10.1:8080".to_string(), format!("{}", domain));
 }
+
+#[test]
+fn read_ipv6_address() {
+    let addr: Ipv6Addr = "2001:db8::1".parse().unwrap();
+    let remains = vec![b' '];
+    assert_eq!(
+        Ok((remains.as_ref(), Domain::Ipv6(addr, None))),
+        parse_domain::<VerboseError<&[u8]>>(b"[2001:db8::1] ")
+    );
+
+    let remains = vec![b';'];
+    assert_eq!(
+        Ok((remains.as_ref(), Domain::Ipv6(addr, Some(5060)))),
+        parse_domain::<VerboseError<&[u8]>>(b"[2001:db8::1]:5060;")
+    );
+
+    assert!(parse_domain::<VerboseError<&[u8]>>(b"[2001:db8::g]").is_err());
+}
+
+#[test]
+fn write_ipv6_address() {
+    let addr: Ipv6Addr = "2001:db8::1".parse().unwrap();
+    assert_eq!("[2001:db8::1]", format!("{}", Domain::Ipv6(addr, None)));
+    assert_eq!(
+        "[2001:db8::1]:5060",
+        format!("{}", Domain::Ipv6(addr, Some(5060)))
+    );
+    assert_eq!(Domain::Ipv6(addr, Some(5060)).port(), Some(5060));
+}
@@ -1,3 +1,6 @@
 mod auth;
 mod domain;
+mod params;
+#[allow(clippy::module_inception)]
 mod uri;
+mod tel;
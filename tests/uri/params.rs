@@ -0,0 +1,87 @@
+use libsip::*;
+
+use nom::error::VerboseError;
+
+#[test]
+fn read_typed_params() {
+    let input = "sip:alice@example.com;user=phone;method=REGISTER;ttl=15;maddr=239.255.255.1;lr;ob;gr=urn:uuid:f81d4fae;sigcomp-id=urn:uuid:0c67;comp=sigcomp;maxage=3600;x-key2=v";
+    let (remains, uri) = parse_uri::<VerboseError<&[u8]>>(input.as_bytes()).unwrap();
+    assert!(remains.is_empty());
+    assert_eq!(
+        uri.parameters,
+        vec![
+            UriParam::User("phone".into()),
+            UriParam::Method(Method::Register),
+            UriParam::Ttl(15),
+            UriParam::Maddr(ip_domain!(239, 255, 255, 1)),
+            UriParam::Lr,
+            UriParam::Ob,
+            UriParam::Gr(Some("urn:uuid:f81d4fae".into())),
+            UriParam::SigComp("urn:uuid:0c67".into()),
+            UriParam::Comp("sigcomp".into()),
+            UriParam::MaxAge(3600),
+            UriParam::Other("x-key2".into(), Some("v".into())),
+        ]
+    );
+    assert_eq!(format!("{}", uri), input);
+}
+
+#[test]
+fn read_flag_params() {
+    let (_, uri) = parse_uri::<VerboseError<&[u8]>>(b"sip:example.com;LR;gr;rport").unwrap();
+    assert_eq!(
+        uri.parameters,
+        vec![UriParam::Lr, UriParam::Gr(None), UriParam::RPort(None)]
+    );
+}
+
+#[test]
+fn keep_valued_flags() {
+    let input = "sip:p1.example.com;lr=on;ob=1";
+    let (_, uri) = parse_uri::<VerboseError<&[u8]>>(input.as_bytes()).unwrap();
+    assert_eq!(
+        uri.parameters,
+        vec![
+            UriParam::Other("lr".into(), Some("on".into())),
+            UriParam::Other("ob".into(), Some("1".into())),
+        ]
+    );
+    assert!(uri.parameters[0].is_lr());
+    assert_eq!(format!("{}", uri), input);
+}
+
+#[test]
+fn keep_invalid_values() {
+    let input = "sip:example.com;ttl=300;method=FOO;maxage=soon;user=;transport=carrier-pigeon";
+    let (remains, uri) = parse_uri::<VerboseError<&[u8]>>(input.as_bytes()).unwrap();
+    assert!(remains.is_empty());
+    assert_eq!(
+        uri.parameters,
+        vec![
+            UriParam::Other("ttl".into(), Some("300".into())),
+            UriParam::Other("method".into(), Some("FOO".into())),
+            UriParam::Other("maxage".into(), Some("soon".into())),
+            UriParam::Other("user".into(), Some("".into())),
+            UriParam::Other("transport".into(), Some("carrier-pigeon".into())),
+        ]
+    );
+    assert_eq!(format!("{}", uri), input);
+
+    let msg = "INVITE sip:bob@example.com;method=FOO SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+    let (_, msg) = parse_message::<VerboseError<&[u8]>>(msg.as_bytes()).unwrap();
+    assert_eq!(msg.headers().iter().count(), 1);
+}
+
+#[test]
+fn received_ipv6_forms() {
+    let addr: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+    let bracketed = "sip:example.com;received=[2001:db8::1]";
+    let (_, uri) = parse_uri::<VerboseError<&[u8]>>(bracketed.as_bytes()).unwrap();
+    assert_eq!(uri.parameters, vec![UriParam::Received(Domain::Ipv6(addr, None))]);
+    assert_eq!(format!("{}", uri), bracketed);
+
+    let bare = "sip:example.com;received=2001:db8::1";
+    let (_, uri) = parse_uri::<VerboseError<&[u8]>>(bare.as_bytes()).unwrap();
+    assert_eq!(uri.parameters, vec![UriParam::Received(Domain::Ipv6(addr, None))]);
+    assert_eq!(format!("{}", uri), bracketed);
+}
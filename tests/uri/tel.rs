@@ -0,0 +1,96 @@
+use libsip::{headers::parse::parse_to_header, uri::parse_tel_uri, *};
+
+use nom::error::VerboseError;
+
+#[test]
+fn read_tel_uri() {
+    let remains = vec![b' '];
+    let expected = TelUri::global("+1-555-0100").ext("22");
+    assert_eq!(
+        Ok((remains.as_ref(), expected)),
+        parse_tel_uri::<VerboseError<&[u8]>>(b"+1-555-0100;ext=22 ")
+    );
+
+    let remains = vec![];
+    let expected = TelUri::local("7042", "example.com").isub("1234");
+    assert_eq!(
+        Ok((remains.as_ref(), expected)),
+        parse_tel_uri::<VerboseError<&[u8]>>(b"7042;isub=1234;phone-context=example.com")
+    );
+
+    assert!(parse_tel_uri::<VerboseError<&[u8]>>(b"7042;ext=1").is_err());
+    assert!(parse_tel_uri::<VerboseError<&[u8]>>(b"+--").is_err());
+}
+
+#[test]
+fn write_tel_uri() {
+    let tel = TelUri::local("7042", "+1-555").ext("22");
+    assert_eq!("tel:7042;ext=22;phone-context=+1-555", format!("{}", tel));
+    assert_eq!(
+        "tel:7042;ext=22;phone-context=+1-555",
+        format!("{}", Uri::tel(tel))
+    );
+}
+
+#[test]
+fn read_tel_as_uri() {
+    let remains = vec![];
+    let (_, uri) =
+        parse_uri::<VerboseError<&[u8]>>(b"tel:+15550100;phone-context=example.com").unwrap();
+    assert_eq!(Some(UriSchema::Tel), uri.schema);
+    assert_eq!(
+        Some(TelUri::local("+15550100", "example.com")),
+        uri.tel_number()
+    );
+    assert_eq!(
+        Ok((remains.as_ref(), uri.clone())),
+        parse_uri::<VerboseError<&[u8]>>(format!("{}", uri).as_bytes())
+    );
+    assert_eq!(None, Uri::sip(domain!("example.com")).tel_number());
+}
+
+#[test]
+fn read_tel_to_header() {
+    let remains = vec![];
+    let uri = Uri::tel(TelUri::local("7042", "example.com"));
+    let header = Header::To(named_header!(uri, "Desk"));
+    assert_eq!(
+        Ok((remains.as_ref(), header)),
+        parse_to_header::<VerboseError<&[u8]>>(b"To: Desk <tel:7042;phone-context=example.com>\r\n")
+    );
+}
+
+#[test]
+fn tel_to_sip_uri() {
+    let tel = TelUri::global("+358-555-1234567").parameter("postd", Some("pp22".into()));
+    assert_eq!(
+        "sip:+358-555-1234567;postd=pp22@foo.com;user=phone",
+        format!("{}", tel.to_sip_uri(domain!("foo.com")))
+    );
+}
+
+#[test]
+fn tel_host_domain() {
+    assert_eq!(None, Uri::tel(TelUri::global("+15550100")).host_domain());
+    assert_eq!(
+        Some(&domain!("example.com")),
+        Uri::sip(domain!("example.com")).host_domain()
+    );
+}
+
+#[test]
+fn read_tel_addr_spec() {
+    let remains = vec![];
+    let uri = Uri::tel(TelUri::global("+15550100"));
+    let header = Header::To(named_header!(uri).param("tag", Some("1")));
+    assert_eq!(
+        Ok((remains.as_ref(), header)),
+        parse_to_header::<VerboseError<&[u8]>>(b"To: tel:+15550100;tag=1\r\n")
+    );
+
+    // The phone-context of a local number would belong to the header.
+    assert!(
+        parse_to_header::<VerboseError<&[u8]>>(b"To: tel:7042;phone-context=example.com\r\n")
+            .is_err()
+    );
+}
@@ -1,4 +1,4 @@
-use libsip::*;
+use libsip::{uri::parse_addr_spec, *};
 
 use nom::error::VerboseError;
 
@@ -131,3 +131,68 @@ fn write_uri() {
         format!("{}", uri)
     );
 }
+
+#[test]
+fn read_ipv6_uri() {
+    let input = "sip:alice@[2001:db8::10]:5070;transport=TCP";
+    let addr: std::net::Ipv6Addr = "2001:db8::10".parse().unwrap();
+    let uri = Uri::sip(Domain::Ipv6(addr, Some(5070)))
+        .auth(uri_auth!("alice"))
+        .parameter(UriParam::Transport(Transport::Tcp));
+    assert_eq!(
+        Ok((&b""[..], uri.clone())),
+        parse_uri::<VerboseError<&[u8]>>(input.as_bytes())
+    );
+    assert_eq!(format!("{}", uri), input);
+}
+
+#[test]
+fn read_user_charset() {
+    for input in &[
+        "sip:+1-555-0100@example.com",
+        "sip:alice.smith@example.com",
+        "sip:alice%40home@my-host.example.com",
+        "sip:alice:p%40ss&word@example.com",
+    ] {
+        let (remains, uri) = parse_uri::<VerboseError<&[u8]>>(input.as_bytes()).unwrap();
+        assert!(remains.is_empty(), "{} left {:?}", input, remains);
+        assert_eq!(&format!("{}", uri), input);
+    }
+
+    let (_, uri) =
+        parse_uri::<VerboseError<&[u8]>>(b"sip:alice%40home:p%40ss@my-host.example.com").unwrap();
+    assert_eq!(
+        uri,
+        Uri::sip(domain!("my-host.example.com")).auth(UriAuth::new("alice@home").password("p@ss"))
+    );
+}
+
+#[test]
+fn read_params_and_headers() {
+    let input = "sip:bob@example.com;maddr=[2001:db8::1];x-custom%20name=a%3Bb;lr?Subject=project%20x&Priority=urgent";
+    let (remains, uri) = parse_uri::<VerboseError<&[u8]>>(input.as_bytes()).unwrap();
+    assert!(remains.is_empty());
+    assert_eq!(
+        uri,
+        Uri::sip(domain!("example.com"))
+            .auth(uri_auth!("bob"))
+            .parameter(UriParam::Maddr(Domain::Ipv6("2001:db8::1".parse().unwrap(), None)))
+            .parameter(UriParam::Other("x-custom name".into(), Some("a;b".into())))
+            .parameter(UriParam::Lr)
+            .header("Subject", "project x")
+            .header("Priority", "urgent")
+    );
+    assert_eq!(format!("{}", uri), input);
+}
+
+#[test]
+fn read_addr_spec() {
+    let remains = b";transport=tcp".to_vec();
+    assert_eq!(
+        Ok((
+            remains.as_ref(),
+            Uri::sip(domain!("example.com")).auth(uri_auth!("guy"))
+        )),
+        parse_addr_spec::<VerboseError<&[u8]>>(b"sip:guy@example.com;transport=tcp")
+    );
+}